@@ -0,0 +1,21 @@
+-- Your SQL goes here
+-- Snapshot of a progress record taken when an attempt is reset or abandoned.
+-- repository_id is not a foreign key since the repository may be deleted
+-- together with the attempt.
+CREATE TABLE progress_archives (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    progress_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id),
+    challenge_id UUID NOT NULL REFERENCES challenges(id),
+    repository_id UUID NOT NULL,
+    language VARCHAR(255) NOT NULL,
+    status VARCHAR(255) NOT NULL,
+    progress_details JSONB,
+    score_earned INTEGER NOT NULL DEFAULT 0,
+    reason VARCHAR(255) NOT NULL CHECK (reason IN ('reset', 'abandoned')),
+    started_at TIMESTAMP NOT NULL,
+    archived_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_progress_archives_user_id ON progress_archives(user_id);
+CREATE INDEX idx_progress_archives_repository_id ON progress_archives(repository_id);
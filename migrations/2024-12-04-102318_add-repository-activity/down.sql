@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS repository_pushes;
+DROP INDEX IF EXISTS idx_repositories_last_pushed_at;
+ALTER TABLE repositories DROP COLUMN IF EXISTS push_count;
+ALTER TABLE repositories DROP COLUMN IF EXISTS last_push_branch;
+ALTER TABLE repositories DROP COLUMN IF EXISTS last_commit_sha;
+ALTER TABLE repositories DROP COLUMN IF EXISTS last_pushed_at;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+ALTER TABLE repositories ADD COLUMN IF NOT EXISTS last_pushed_at TIMESTAMP;
+ALTER TABLE repositories ADD COLUMN IF NOT EXISTS last_commit_sha VARCHAR(255);
+ALTER TABLE repositories ADD COLUMN IF NOT EXISTS last_push_branch VARCHAR(255);
+ALTER TABLE repositories ADD COLUMN IF NOT EXISTS push_count INTEGER NOT NULL DEFAULT 0;
+
+-- One row per push received from the webhook handler, used as the commit
+-- timeline of an attempt.
+CREATE TABLE repository_pushes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    repository_id UUID NOT NULL REFERENCES repositories(id),
+    commit_sha VARCHAR(255) NOT NULL,
+    branch VARCHAR(255),
+    pushed_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_repository_pushes_repository_id ON repository_pushes(repository_id, pushed_at DESC);
+CREATE INDEX idx_repositories_last_pushed_at ON repositories(last_pushed_at);
@@ -0,0 +1,21 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_submissions_repository_id;
+
+-- Results recorded without an exercise get the exercise of the step they
+-- covered, the ones whose challenge has no exercise for it can't be kept
+-- once the exercise is required again.
+UPDATE submissions SET exercise_id = numbered.id
+FROM repositories, (
+    SELECT id, challenge_id, row_number() OVER (
+        PARTITION BY challenge_id ORDER BY created_at ASC
+    ) AS step
+    FROM exercises
+) AS numbered
+WHERE submissions.exercise_id IS NULL
+    AND repositories.id = submissions.repository_id
+    AND numbered.challenge_id = repositories.challenge_id
+    AND numbered.step = submissions.step;
+DELETE FROM submissions WHERE exercise_id IS NULL;
+
+ALTER TABLE submissions DROP COLUMN IF EXISTS step;
+ALTER TABLE submissions ALTER COLUMN exercise_id SET NOT NULL;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- A test result is applied once per repository, commit and step, so a
+-- redelivered queue message can't advance progress a second time.
+CREATE UNIQUE INDEX idx_submissions_repository_commit_step ON submissions(repository_id, commit_id, step);
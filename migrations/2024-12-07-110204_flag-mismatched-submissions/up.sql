@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Set when a test result covers a different step than the attempt is on.
+-- Such results are kept for the history but don't move progress.
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS flagged_reason TEXT;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_submissions_repository_commit_step;
+DELETE FROM submissions WHERE test_run_id IS NOT NULL;
+ALTER TABLE submissions DROP COLUMN IF EXISTS test_run_id;
+CREATE UNIQUE INDEX idx_submissions_repository_commit_step ON submissions(repository_id, commit_id, step);
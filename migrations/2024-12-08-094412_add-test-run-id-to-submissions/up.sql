@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Runs requested through core carry their own id, so re-grading a commit
+-- is applied again instead of being dropped as a redelivery.
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS test_run_id UUID;
+
+DROP INDEX IF EXISTS idx_submissions_repository_commit_step;
+CREATE UNIQUE INDEX idx_submissions_repository_commit_step ON submissions(
+    repository_id,
+    commit_id,
+    step,
+    COALESCE(test_run_id, '00000000-0000-0000-0000-000000000000')
+);
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Every message consumed from the queues, stored as received together with
+-- the outcome of processing it, so that failed ones can be replayed.
+CREATE TABLE inbound_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    queue VARCHAR(255) NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(255) NOT NULL DEFAULT 'received',
+    error TEXT,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    received_at TIMESTAMP NOT NULL DEFAULT now(),
+    processed_at TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT inbound_events_status_check CHECK (status IN ('received', 'processed', 'failed', 'replaying'))
+);
+
+CREATE INDEX idx_inbound_events_status_received_at ON inbound_events(status, received_at);
+
+SELECT diesel_manage_updated_at('inbound_events');
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE repositories DROP COLUMN IF EXISTS remote_deleted_at;
+ALTER TABLE repositories DROP COLUMN IF EXISTS force_push_count;
+
+DELETE FROM repository_pushes WHERE kind <> 'push';
+ALTER TABLE repository_pushes DROP CONSTRAINT IF EXISTS repository_pushes_kind_check;
+ALTER TABLE repository_pushes DROP COLUMN IF EXISTS tag;
+ALTER TABLE repository_pushes DROP COLUMN IF EXISTS before_sha;
+ALTER TABLE repository_pushes DROP COLUMN IF EXISTS forced;
+ALTER TABLE repository_pushes DROP COLUMN IF EXISTS kind;
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- The attempt timeline also shows branch and tag creation, and flags
+-- force-pushes that rewrote history.
+ALTER TABLE repository_pushes ADD COLUMN IF NOT EXISTS kind VARCHAR(255) NOT NULL DEFAULT 'push';
+ALTER TABLE repository_pushes ADD COLUMN IF NOT EXISTS forced BOOLEAN NOT NULL DEFAULT false;
+ALTER TABLE repository_pushes ADD COLUMN IF NOT EXISTS before_sha VARCHAR(255);
+ALTER TABLE repository_pushes ADD COLUMN IF NOT EXISTS tag VARCHAR(255);
+ALTER TABLE repository_pushes ADD CONSTRAINT repository_pushes_kind_check CHECK (kind IN ('push', 'branch_created', 'tag_created'));
+
+ALTER TABLE repositories ADD COLUMN IF NOT EXISTS force_push_count INTEGER NOT NULL DEFAULT 0;
+-- Set when the repository was deleted on the git service, cleared once it
+-- points at a live repository again.
+ALTER TABLE repositories ADD COLUMN IF NOT EXISTS remote_deleted_at TIMESTAMP;
@@ -0,0 +1,17 @@
+-- Your SQL goes here
+-- One row per test case the runner reported for a submission.
+CREATE TABLE submission_test_cases (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    submission_id UUID NOT NULL REFERENCES submissions(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    suite TEXT,
+    status VARCHAR(255) NOT NULL,
+    duration_ms INTEGER,
+    failure_message TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT submission_test_cases_status_check CHECK (status IN ('passed', 'failed', 'skipped'))
+);
+
+CREATE INDEX idx_submission_test_cases_submission_id ON submission_test_cases(submission_id, position);
+CREATE INDEX idx_submission_test_cases_status ON submission_test_cases(status);
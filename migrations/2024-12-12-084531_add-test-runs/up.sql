@@ -0,0 +1,22 @@
+-- Your SQL goes here
+-- A run of the test suite against an attempt, from the push or request that
+-- queued it until the runner reports a result or it times out.
+CREATE TABLE test_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    repository_id UUID NOT NULL REFERENCES repositories(id) ON DELETE CASCADE,
+    commit_sha VARCHAR(255),
+    status VARCHAR(255) NOT NULL DEFAULT 'queued',
+    success BOOLEAN,
+    submission_id UUID REFERENCES submissions(id) ON DELETE SET NULL,
+    requested_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    queued_at TIMESTAMP NOT NULL DEFAULT now(),
+    started_at TIMESTAMP,
+    finished_at TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT test_runs_status_check CHECK (status IN ('queued', 'running', 'finished', 'timed_out'))
+);
+
+CREATE INDEX idx_test_runs_repository_id_queued_at ON test_runs(repository_id, queued_at);
+CREATE INDEX idx_test_runs_open ON test_runs(queued_at) WHERE status IN ('queued', 'running');
+
+SELECT diesel_manage_updated_at('test_runs');
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE test_runs DROP COLUMN IF EXISTS relayed_sequence;
+DROP TABLE IF EXISTS test_run_output_chunks;
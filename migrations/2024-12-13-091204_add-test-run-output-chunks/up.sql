@@ -0,0 +1,17 @@
+-- Your SQL goes here
+-- Output the test runner streams while a run is in progress. The output of
+-- the result is assembled from these once the run finishes.
+CREATE TABLE test_run_output_chunks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    test_run_id UUID NOT NULL REFERENCES test_runs(id) ON DELETE CASCADE,
+    sequence INTEGER NOT NULL,
+    output TEXT NOT NULL,
+    received_at TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT test_run_output_chunks_sequence_check CHECK (sequence > 0)
+);
+
+CREATE UNIQUE INDEX idx_test_run_output_chunks_test_run_id_sequence ON test_run_output_chunks(test_run_id, sequence);
+
+-- Chunks up to this one were relayed to the learner, later ones wait for
+-- the gap before them to be filled.
+ALTER TABLE test_runs ADD COLUMN relayed_sequence INTEGER NOT NULL DEFAULT 0;
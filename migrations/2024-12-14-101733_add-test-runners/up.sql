@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Test runners as last reported by their heartbeats.
+CREATE TABLE test_runners (
+    id VARCHAR(255) PRIMARY KEY,
+    queue VARCHAR(255) NOT NULL,
+    languages JSONB NOT NULL DEFAULT '[]',
+    capacity INTEGER NOT NULL,
+    busy INTEGER NOT NULL DEFAULT 0,
+    version VARCHAR(255),
+    registered_at TIMESTAMP NOT NULL DEFAULT now(),
+    last_heartbeat_at TIMESTAMP NOT NULL DEFAULT now(),
+    CONSTRAINT test_runners_capacity_check CHECK (capacity >= 0 AND busy >= 0)
+);
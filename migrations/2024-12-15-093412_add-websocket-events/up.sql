@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- Events published over the websocket, kept for a while so that a client
+-- that reconnects can be sent the ones it missed. Events about a repository
+-- belong to its owner, leaderboard updates and announcements to nobody.
+CREATE TABLE websocket_events (
+    id BIGSERIAL PRIMARY KEY,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    topic VARCHAR NOT NULL,
+    message_type VARCHAR NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX idx_websocket_events_user_id_id ON websocket_events(user_id, id);
+CREATE INDEX idx_websocket_events_created_at ON websocket_events(created_at);
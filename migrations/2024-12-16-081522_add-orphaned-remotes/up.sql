@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- When reconciliation first saw each repository on the git service that has
+-- no `repositories` row, so that ones still being created are left alone.
+CREATE TABLE orphaned_remotes (
+    repo_name VARCHAR(255) PRIMARY KEY,
+    first_seen_at TIMESTAMP NOT NULL DEFAULT now()
+);
@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_submissions_repository_commit_delivery;
+ALTER TABLE submissions DROP COLUMN IF EXISTS delivery_key;
+CREATE UNIQUE INDEX idx_submissions_repository_commit_step ON submissions(
+    repository_id,
+    commit_id,
+    step,
+    COALESCE(test_run_id, '00000000-0000-0000-0000-000000000000')
+);
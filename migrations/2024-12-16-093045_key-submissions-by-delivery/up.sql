@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Results are deduplicated on what the runner reported rather than on the
+-- step the attempt was on, which moves once a result is applied. Only a
+-- passed result that was applied makes later deliveries duplicates, so a
+-- passing run still counts after a failed one for the same commit.
+ALTER TABLE submissions ADD COLUMN IF NOT EXISTS delivery_key VARCHAR(255);
+
+UPDATE submissions SET delivery_key = CASE
+    WHEN test_run_id IS NOT NULL THEN 'run:' || test_run_id
+    WHEN step IS NOT NULL THEN 'stage:' || step
+    ELSE 'commit'
+END;
+
+DROP INDEX IF EXISTS idx_submissions_repository_commit_step;
+CREATE UNIQUE INDEX idx_submissions_repository_commit_delivery ON submissions(
+    repository_id,
+    commit_id,
+    delivery_key
+) WHERE status = 'passed' AND flagged_reason IS NULL;
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS idx_repository_pushes_repository_message;
+ALTER TABLE repository_pushes DROP COLUMN IF EXISTS message_id;
+DROP INDEX IF EXISTS idx_inbound_events_message_id;
+ALTER TABLE inbound_events DROP COLUMN IF EXISTS message_id;
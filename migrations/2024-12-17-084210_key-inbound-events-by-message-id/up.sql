@@ -0,0 +1,23 @@
+-- Your SQL goes here
+-- A message is stored once however often it is retried, keyed by the id it
+-- carries through every retry, so replaying only ever picks up messages
+-- that never got processed.
+ALTER TABLE inbound_events ADD COLUMN IF NOT EXISTS message_id VARCHAR(255);
+-- Copies stored for earlier retries can't be told apart by id, the failed
+-- ones of a message that was processed later on are dropped instead.
+DELETE FROM inbound_events AS failed
+WHERE failed.status = 'failed' AND EXISTS (
+    SELECT 1 FROM inbound_events AS processed
+    WHERE processed.status = 'processed'
+        AND processed.queue = failed.queue
+        AND processed.payload = failed.payload
+        AND processed.received_at > failed.received_at
+);
+UPDATE inbound_events SET message_id = id::text;
+ALTER TABLE inbound_events ALTER COLUMN message_id SET NOT NULL;
+CREATE UNIQUE INDEX idx_inbound_events_message_id ON inbound_events(message_id);
+
+-- Pushes are recorded once per delivered message, so retrying or replaying
+-- it doesn't add to the timeline or the push counts again.
+ALTER TABLE repository_pushes ADD COLUMN IF NOT EXISTS message_id VARCHAR(255);
+CREATE UNIQUE INDEX idx_repository_pushes_repository_message ON repository_pushes(repository_id, message_id);
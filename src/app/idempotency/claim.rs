@@ -0,0 +1,104 @@
+use crate::{service::database::models::IdempotencyKey, shared::errors::RepositoryError};
+use actix_web::{http::StatusCode, HttpRequest, HttpResponse};
+use diesel::PgConnection;
+use log::{error, warn};
+use serde_json::Value;
+use uuid::Uuid;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+pub enum IdempotencyClaim {
+    /// The key is now held by the current request.
+    Claimed(IdempotencyKey),
+    /// A previous request with the same key already completed.
+    Replay(HttpResponse),
+}
+
+pub fn idempotency_key_from_request(req: &HttpRequest) -> Result<Option<String>, RepositoryError> {
+    let key = match req.headers().get(IDEMPOTENCY_KEY_HEADER) {
+        Some(value) => value.to_str().map_err(|_| {
+            RepositoryError::BadRequest(format!("Invalid {} header", IDEMPOTENCY_KEY_HEADER))
+        })?,
+        None => return Ok(None),
+    };
+
+    let key = key.trim();
+    if key.is_empty() || key.len() > 255 {
+        return Err(RepositoryError::BadRequest(format!(
+            "{} header must be between 1 and 255 characters",
+            IDEMPOTENCY_KEY_HEADER
+        )));
+    }
+    Ok(Some(key.to_string()))
+}
+
+/// Claims `key` for the current request, or returns the stored response of a
+/// completed request that used the same key.
+pub fn claim_idempotency_key(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    key: &str,
+    request_path: &str,
+    request_body: Value,
+) -> Result<IdempotencyClaim, RepositoryError> {
+    // A second attempt is only needed when an expired or abandoned key is
+    // cleared out
+    for _ in 0..2 {
+        let new_key = IdempotencyKey::new(user_id, key, request_path, request_body.clone());
+        if let Some(claimed) = IdempotencyKey::claim(conn, new_key).map_err(|e| {
+            error!("Error claiming idempotency key: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })? {
+            return Ok(IdempotencyClaim::Claimed(claimed));
+        }
+
+        let existing = IdempotencyKey::get(conn, user_id, key).map_err(|e| {
+            error!("Error getting idempotency key: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+
+        if existing.is_expired() {
+            warn!("Idempotency key {} expired, releasing it", existing.id);
+            release_idempotency_key(conn, &existing);
+            continue;
+        }
+        if existing.is_abandoned() {
+            warn!(
+                "Idempotency key {} was never completed, releasing it",
+                existing.id
+            );
+            release_idempotency_key(conn, &existing);
+            continue;
+        }
+
+        if existing.request_path != request_path || existing.request_body != request_body {
+            return Err(RepositoryError::BadRequest(
+                "Idempotency key has already been used for a different request".to_string(),
+            ));
+        }
+
+        return match (existing.response_status, existing.response_body) {
+            (Some(status), Some(body)) => {
+                let status = StatusCode::from_u16(status as u16).unwrap_or(StatusCode::OK);
+                Ok(IdempotencyClaim::Replay(
+                    HttpResponse::build(status).json(body),
+                ))
+            }
+            _ => Err(RepositoryError::IdempotencyConflict(
+                "A request with this idempotency key is still being processed".to_string(),
+            )),
+        };
+    }
+
+    Err(RepositoryError::IdempotencyConflict(
+        "Could not claim idempotency key, please retry".to_string(),
+    ))
+}
+
+/// Frees a claimed key after a failed request so that the client can retry
+/// with the same key.
+pub fn release_idempotency_key(conn: &mut PgConnection, key: &IdempotencyKey) {
+    if let Err(e) = IdempotencyKey::release(conn, &key.id) {
+        error!("Error releasing idempotency key {}: {}", key.id, e);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    service::{
+        database::{conn::DbPool, models::InboundEvent},
+        queue::{InboundQueue, MessageHandler},
+        repository::inbound_event::InboundEventFilter,
+    },
+    shared::{errors::RepositoryError, primitives::InboundEventStatus},
+};
+use log::{error, info};
+use serde::Serialize;
+use uuid::Uuid;
+
+// Upper bound of events replayed by a single range replay.
+pub const MAX_REPLAY_BATCH: i64 = 500;
+
+#[derive(Debug, Serialize)]
+pub struct ReplayReport {
+    pub replayed: usize,
+    pub processed: Vec<Uuid>,
+    pub failed: Vec<Uuid>,
+    pub skipped: Vec<Uuid>,
+}
+
+/// Processes a failed event again through the same handler as the queue.
+/// Only failed events and those stalled past their lease are replayed, and
+/// each replay claims the event first, so an event that was processed is
+/// never applied a second time.
+pub async fn replay_event(
+    handler: &MessageHandler,
+    pool: &DbPool,
+    event_id: &Uuid,
+) -> Result<InboundEvent, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let event = match InboundEvent::claim_for_replay(&mut conn, event_id).map_err(|e| {
+        error!("Error claiming inbound event {}: {}", event_id, e);
+        RepositoryError::DatabaseError(e.to_string())
+    })? {
+        Some(event) => event,
+        None => {
+            let event = InboundEvent::get(&mut conn, event_id)
+                .map_err(|_| RepositoryError::NotFound("Event not found".to_string()))?;
+            return Err(RepositoryError::IdempotencyConflict(format!(
+                "Event is {}, only failed or stalled events can be replayed",
+                event.status
+            )));
+        }
+    };
+
+    let result = match InboundQueue::from_str(&event.queue) {
+        Ok(queue) => handler
+            .process(queue, &event.message_id, event.payload.clone().into_bytes())
+            .await
+            .map_err(|e| format!("{:#}", e)),
+        Err(e) => Err(format!("{}: {}", e, event.queue)),
+    };
+    if let Err(reason) = &result {
+        error!("Replay of inbound event {} failed: {}", event.id, reason);
+    } else {
+        info!("Replayed inbound event {}", event.id);
+    }
+
+    InboundEvent::finish(&mut conn, &event.id, result.err().as_deref()).map_err(|e| {
+        error!(
+            "Error recording replay of inbound event {}: {}",
+            event.id, e
+        );
+        RepositoryError::DatabaseError(e.to_string())
+    })
+}
+
+/// Replays the failed and stalled events received in `[from, to)`, oldest
+/// first. Events another replay is already working on are skipped. Retries
+/// of a message share its event, so a message that was processed on a
+/// later retry is not picked up again.
+pub async fn replay_range(
+    handler: &MessageHandler,
+    pool: &DbPool,
+    filter: InboundEventFilter<'_>,
+) -> Result<ReplayReport, RepositoryError> {
+    let ids = {
+        let mut conn = pool.get().map_err(|e| {
+            error!("Error getting db connection from pool: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+        let filter = InboundEventFilter {
+            replayable: true,
+            ..filter
+        };
+        InboundEvent::get_ids(&mut conn, &filter, MAX_REPLAY_BATCH).map_err(|e| {
+            error!("Error getting replayable inbound events: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?
+    };
+
+    let mut report = ReplayReport {
+        replayed: 0,
+        processed: Vec::new(),
+        failed: Vec::new(),
+        skipped: Vec::new(),
+    };
+    for id in ids {
+        match replay_event(handler, pool, &id).await {
+            Ok(event) => {
+                report.replayed += 1;
+                if event.status == InboundEventStatus::Processed.to_str() {
+                    report.processed.push(id);
+                } else {
+                    report.failed.push(id);
+                }
+            }
+            Err(RepositoryError::IdempotencyConflict(_)) => report.skipped.push(id),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(report)
+}
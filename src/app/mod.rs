@@ -1,6 +1,10 @@
 pub mod auth;
+pub mod idempotency;
+pub mod inbound_event;
 pub mod init;
 pub mod routes;
 pub mod progress;
 pub mod repo;
+pub mod runner;
+pub mod submission;
 pub mod websockets;
@@ -0,0 +1,48 @@
+use crate::app::progress::{fork_progress::is_forked, update_progress::earned_score};
+use crate::service::database::models::{Leaderboard, Progress, ProgressArchive};
+use crate::shared::primitives::ArchiveReason;
+use anyhow::{Context, Result};
+use diesel::PgConnection;
+
+/// Archives and removes `progress` for an attempt the learner gave up on.
+/// Starting the attempt added `module_count` to `expected_total_score`;
+/// the part of that which was never earned is taken off again, while the
+/// points already earned stay on the leaderboard. An attempt that was
+/// carried over into another language handed its remaining steps to the
+/// new repository, so nothing is taken off in that case.
+/// Callers are expected to run this inside a transaction.
+pub fn abandon_progress(
+    conn: &mut PgConnection,
+    progress: &Progress,
+    language: &str,
+    module_count: i32,
+) -> Result<ProgressArchive> {
+    let score_earned = earned_score(progress);
+    let archive = ProgressArchive::create(
+        conn,
+        ProgressArchive::new(progress, language, ArchiveReason::Abandoned, score_earned),
+    )
+    .context(format!("Failed to archive progress {}", progress.id))?;
+
+    Progress::delete(conn, &progress.id)
+        .context(format!("Failed to delete progress {}", progress.id))?;
+
+    let user_leaderboard = Leaderboard::get_leaderboard(conn, Some(&progress.user_id))
+        .context("Failed to get leaderboard")?;
+    let unearned_score = match is_forked(progress) {
+        true => 0,
+        false => (module_count - score_earned).max(0),
+    };
+    let new_expected_total_score =
+        (user_leaderboard[0].expected_total_score - unearned_score).max(user_leaderboard[0].score);
+    Leaderboard::update(
+        conn,
+        &progress.user_id,
+        None,
+        Some(new_expected_total_score),
+        None,
+    )
+    .context("Failed to update leaderboard")?;
+
+    Ok(archive)
+}
@@ -0,0 +1,102 @@
+use crate::app::progress::update_progress::with_current_step;
+use crate::service::database::models::{Progress, Repository};
+use crate::shared::primitives::Status;
+use anyhow::{Context, Result};
+use diesel::PgConnection;
+use serde_json::{json, Value};
+
+pub fn is_forked(progress: &Progress) -> bool {
+    progress
+        .progress_details
+        .as_ref()
+        .and_then(|details| details.get("forked_to"))
+        .is_some()
+}
+
+/// Creates the progress record for `target_repo`, an attempt at the same
+/// challenge as `source_repo` in another language.
+///
+/// With `carry_over` the new progress starts at the source's
+/// `current_step` and the source is marked as forked so it stops earning
+/// points; the points earned so far stay where they are on the leaderboard.
+/// Without it the new attempt starts from step 1.
+/// Either way the language history of the source is extended and stored on
+/// the new progress.
+/// Callers are expected to run this inside a transaction.
+pub fn fork_progress(
+    conn: &mut PgConnection,
+    source_progress: &Progress,
+    source_repo: &Repository,
+    target_repo: &Repository,
+    carry_over: bool,
+) -> Result<Progress> {
+    let now = chrono::Utc::now().naive_utc();
+    let mut language_history = source_progress
+        .progress_details
+        .as_ref()
+        .and_then(|details| details.get("language_history"))
+        .and_then(|history| history.as_array())
+        .cloned()
+        .unwrap_or_else(|| {
+            vec![json!({
+                "language": &source_repo.language,
+                "repository_id": &source_repo.id,
+                "started_at": &source_repo.created_at,
+            })]
+        });
+    language_history.push(json!({
+        "language": &target_repo.language,
+        "repository_id": &target_repo.id,
+        "started_at": now,
+        "carried_over": carry_over,
+    }));
+
+    let current_step = match carry_over {
+        true => source_progress
+            .progress_details
+            .as_ref()
+            .and_then(|details| details["current_step"].as_i64())
+            .unwrap_or(1),
+        false => 1,
+    };
+    let status = if current_step > 1 {
+        Status::InProgress
+    } else {
+        Status::NotStarted
+    };
+
+    let new_progress = Progress::new(
+        &target_repo.user_id,
+        &target_repo.challenge_id,
+        &target_repo.id,
+        status,
+        Some(json!({
+            "current_step": current_step,
+            "language_history": language_history,
+        })),
+    );
+    let new_progress = Progress::create_progress(conn, new_progress)
+        .context(format!("Failed to create progress for {}", target_repo.id))?;
+
+    if carry_over {
+        let mut source_details =
+            with_current_step(source_progress.progress_details.as_ref(), current_step);
+        if let Value::Object(details) = &mut source_details {
+            details.insert("forked_to".to_string(), json!(&target_repo.id));
+        }
+        let source_status = Status::from_str(&source_progress.status)
+            .map_err(|e| anyhow::anyhow!("{}: {}", e, source_progress.status))?;
+        Progress::update_progress(
+            conn,
+            &source_progress.id,
+            source_status,
+            Some(source_details),
+        )
+        .context(format!(
+            "Failed to mark progress {} as forked",
+            source_progress.id
+        ))?;
+    }
+
+    Ok(new_progress)
+}
@@ -1 +1,4 @@
-pub mod update_progress;
\ No newline at end of file
+pub mod abandon_progress;
+pub mod fork_progress;
+pub mod reset_progress;
+pub mod update_progress;
@@ -0,0 +1,53 @@
+use crate::app::progress::update_progress::{earned_score, with_current_step};
+use crate::service::database::models::{Leaderboard, Progress, ProgressArchive, Submission};
+use crate::shared::primitives::{ArchiveReason, Status};
+use anyhow::{Context, Result};
+use diesel::PgConnection;
+
+/// Archives `progress` and puts the attempt back at step 1.
+/// Points earned by the archived run are taken off the leaderboard score
+/// since they can be earned again; `expected_total_score` is left alone
+/// because the attempt itself carries on.
+/// Passing results applied so far are flagged so that pushing the same
+/// commits again moves the new run forward instead of being a duplicate.
+/// Callers are expected to run this inside a transaction.
+pub fn reset_progress(
+    conn: &mut PgConnection,
+    progress: &Progress,
+    language: &str,
+) -> Result<(Progress, ProgressArchive)> {
+    let score_earned = earned_score(progress);
+    let archive = ProgressArchive::create(
+        conn,
+        ProgressArchive::new(progress, language, ArchiveReason::Reset, score_earned),
+    )
+    .context(format!("Failed to archive progress {}", progress.id))?;
+
+    let reset = Progress::update_progress(
+        conn,
+        &progress.id,
+        Status::NotStarted,
+        Some(with_current_step(progress.progress_details.as_ref(), 1)),
+    )
+    .context(format!("Failed to reset progress {}", progress.id))?;
+
+    Submission::flag_applied_passes(
+        conn,
+        &progress.repository_id,
+        &format!("Applied before progress was reset to {}", archive.id),
+    )
+    .context(format!(
+        "Failed to flag submissions of repository {}",
+        progress.repository_id
+    ))?;
+
+    if score_earned > 0 {
+        let user_leaderboard = Leaderboard::get_leaderboard(conn, Some(&progress.user_id))
+            .context("Failed to get leaderboard")?;
+        let new_score = (user_leaderboard[0].score - score_earned).max(0);
+        Leaderboard::update(conn, &progress.user_id, Some(new_score), None, None)
+            .context("Failed to update leaderboard")?;
+    }
+
+    Ok((reset, archive))
+}
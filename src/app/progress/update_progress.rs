@@ -1,35 +1,37 @@
-use crate::service::database::{
-    conn::get_connection_pool,
-    models::{Challenge, Leaderboard, Progress, Repository},
-};
+use crate::app::progress::fork_progress::is_forked;
+use crate::service::database::models::{Challenge, Leaderboard, Progress, Repository};
 use crate::shared::primitives::Status;
 use anyhow::{Context, Result};
+use diesel::PgConnection;
 use log::warn;
-use serde_json::json;
-use uuid::Uuid;
+use serde_json::{json, Map, Value};
 
-pub async fn update_progress(soft_serve_url: &str, user_id: &Uuid) -> Result<Progress> {
-    let pool = get_connection_pool();
-    let mut conn = pool.get().context("Failed to get connection from pool")?;
-    let repo = Repository::get_repo(&mut conn, None, None, None, Some(soft_serve_url)).context(
-        format!("Failed to find repository with URL: {}", soft_serve_url),
-    )?;
-    let repo = repo
-        .first()
-        .ok_or_else(|| anyhow::anyhow!("Repository not found with URL: {}", soft_serve_url))?;
-
-    let challenge = Challenge::get_challenge(&mut conn, Some(&repo.challenge_id), None, None, None)
+/// Moves `progress` one step forward after a successful test run and
+/// awards the point for it. `progress` should have been read with
+/// `Progress::get_for_update` in the same transaction so that concurrent
+/// results for the repository are applied one after the other.
+pub fn update_progress(
+    conn: &mut PgConnection,
+    repo: &Repository,
+    progress: &Progress,
+) -> Result<Progress> {
+    let user_id = &repo.user_id;
+    let challenge = Challenge::get_challenge(conn, Some(&repo.challenge_id), None, None, None)
         .context(format!(
             "Failed to find challenge for challenge ID: {}",
             repo.challenge_id
         ))?;
 
-    let progress = Progress::get_progress(&mut conn, None, None, None, Some(&repo.id))
-        .context(format!("Failed to find progress for user ID: {}", user_id))?;
     if progress.status == Status::Completed.to_str().to_string() {
         warn!("Challenge is already completed");
         return Ok(progress.clone());
     }
+    // an attempt that was carried over into another language keeps its
+    // old repository around, but only the new one earns progress
+    if is_forked(progress) {
+        warn!("Attempt was carried over to another repository");
+        return Ok(progress.clone());
+    }
 
     // check progress.progress_details object for "current_step"
     // if new_current_step is equal to module_count, then set status to completed
@@ -47,10 +49,13 @@ pub async fn update_progress(soft_serve_url: &str, user_id: &Uuid) -> Result<Pro
     }
 
     let updated_progress = Progress::update_progress(
-        &mut conn,
+        conn,
         &progress.id,
         Status::from_str(&new_status).expect("Invalid status"),
-        Some(json!({ "current_step": new_current_step })),
+        Some(with_current_step(
+            progress.progress_details.as_ref(),
+            new_current_step,
+        )),
     )
     .context(format!(
         "Failed to update progress for user ID: {}",
@@ -59,12 +64,39 @@ pub async fn update_progress(soft_serve_url: &str, user_id: &Uuid) -> Result<Pro
 
     // update leaderboard
     if new_status != Status::Completed.to_str().to_string() {
-        let user_leaderboard = Leaderboard::get_leaderboard(&mut conn, Some(user_id))
+        let user_leaderboard = Leaderboard::get_leaderboard(conn, Some(user_id))
             .context("Failed to get leaderboard")?;
 
         let new_score = user_leaderboard[0].score + 1;
-        Leaderboard::update(&mut conn, user_id, Some(new_score), None, None)
+        Leaderboard::update(conn, user_id, Some(new_score), None, None)
             .context("Failed to update leaderboard")?;
     }
     Ok(updated_progress)
 }
+
+/// Number of leaderboard points a progress record has added so far.
+/// Mirrors the rules in `update_progress`: every passed step is worth a
+/// point, except the one that completes the challenge.
+pub fn earned_score(progress: &Progress) -> i32 {
+    let current_step = progress
+        .progress_details
+        .as_ref()
+        .and_then(|details| details["current_step"].as_i64())
+        .unwrap_or(1) as i32;
+    let mut passed_steps = current_step - 1;
+    if progress.status == Status::Completed.to_str() {
+        passed_steps -= 1;
+    }
+    passed_steps.max(0)
+}
+
+/// Returns `details` with `current_step` set to `step`, keeping any other
+/// keys such as the language history.
+pub fn with_current_step(details: Option<&Value>, step: i64) -> Value {
+    let mut details = match details {
+        Some(Value::Object(map)) => map.clone(),
+        _ => Map::new(),
+    };
+    details.insert("current_step".to_string(), json!(step));
+    Value::Object(details)
+}
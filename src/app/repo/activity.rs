@@ -0,0 +1,122 @@
+use crate::{
+    service::{
+        database::{
+            conn::DbPool,
+            models::{Repository, RepositoryPush},
+        },
+        git,
+        queue::invalid_message,
+    },
+    shared::{primitives::RepositoryEventKind, utils::repo_name_from_url},
+};
+use anyhow::{Context, Result};
+use diesel::{Connection, PgConnection};
+use log::warn;
+
+fn get_repo_by_url(conn: &mut PgConnection, soft_serve_url: &str) -> Result<Option<Repository>> {
+    let repos = Repository::get_repo(conn, None, None, None, Some(soft_serve_url)).context(
+        format!("Failed to find repository with URL: {}", soft_serve_url),
+    )?;
+    Ok(repos.into_iter().next())
+}
+
+/// Stores a push received from the webhook handler and updates the activity
+/// summary of the repository it belongs to. A force-push that rewrote
+/// history from `before_sha` is flagged as such on the timeline.
+/// Returns `None` when the push was recorded from `message_id` before, as
+/// happens when the message is retried or replayed.
+pub async fn record_push(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    message_id: &str,
+    commit_sha: &str,
+    branch: Option<&str>,
+    forced: bool,
+    before_sha: Option<&str>,
+) -> Result<Option<RepositoryPush>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = get_repo_by_url(&mut conn, soft_serve_url)?.ok_or_else(|| {
+        invalid_message(format!("Repository not found with URL: {}", soft_serve_url))
+    })?;
+
+    let mut push = RepositoryPush::new(&repo.id, commit_sha, branch);
+    push.forced = forced;
+    push.before_sha = before_sha.map(|sha| sha.to_string());
+    push.message_id = Some(message_id.to_string());
+
+    conn.transaction(|conn| {
+        let push = match RepositoryPush::create(conn, push)
+            .context(format!("Failed to record push for repository {}", repo.id))?
+        {
+            Some(push) => push,
+            None => return Ok(None),
+        };
+        Repository::record_push(conn, &push).context(format!(
+            "Failed to update activity for repository {}",
+            repo.id
+        ))?;
+        Ok(Some(push))
+    })
+}
+
+/// Adds the creation of a branch or tag to the timeline. Unlike a push it
+/// doesn't move the activity summary, no new commits were pushed. Like
+/// `record_push` it returns `None` when `message_id` was recorded before.
+pub async fn record_ref_created(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    message_id: &str,
+    kind: RepositoryEventKind,
+    commit_sha: &str,
+    branch: Option<&str>,
+    tag: Option<&str>,
+) -> Result<Option<RepositoryPush>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = get_repo_by_url(&mut conn, soft_serve_url)?.ok_or_else(|| {
+        invalid_message(format!("Repository not found with URL: {}", soft_serve_url))
+    })?;
+
+    let mut entry = RepositoryPush::new(&repo.id, commit_sha, branch);
+    entry.kind = kind.to_str().to_string();
+    entry.tag = tag.map(|tag| tag.to_string());
+    entry.message_id = Some(message_id.to_string());
+    RepositoryPush::create(&mut conn, entry).context(format!(
+        "Failed to record {} for repository {}",
+        kind.to_str(),
+        repo.id
+    ))
+}
+
+/// Marks the repository as deleted on the git service. Returns `None` when
+/// there is nothing to mark: the attempt was abandoned, or the repository
+/// was recreated in the meantime, as a reset does.
+pub async fn record_remote_deleted(
+    pool: &DbPool,
+    soft_serve_url: &str,
+) -> Result<Option<Repository>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = match get_repo_by_url(&mut conn, soft_serve_url)? {
+        Some(repo) => repo,
+        None => {
+            warn!("Deleted repository {} is not tracked", soft_serve_url);
+            return Ok(None);
+        }
+    };
+
+    let repo_name = repo_name_from_url(soft_serve_url)
+        .ok_or_else(|| anyhow::anyhow!("Invalid repository URL: {}", soft_serve_url))?;
+    let remote_repos = git::list_repos()
+        .await
+        .context("Failed to list repositories from git service")?;
+    if remote_repos.contains(&repo_name) {
+        warn!(
+            "Repository {} was deleted but exists again, not marking it",
+            repo_name
+        );
+        return Ok(None);
+    }
+
+    let repo = Repository::mark_remote_deleted(&mut conn, &repo.id)
+        .context(format!("Failed to mark repository {} as deleted", repo.id))?;
+    Ok(Some(repo))
+}
@@ -1 +1,2 @@
-pub mod match_repo;
\ No newline at end of file
+pub mod activity;
+pub mod reconcile;
\ No newline at end of file
@@ -0,0 +1,243 @@
+use crate::service::{
+    database::{
+        conn::DbPool,
+        models::{Challenge, OrphanedRemote, Repository},
+    },
+    git,
+};
+use crate::shared::utils::{repo_name_from_url, strip_url_credentials};
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use uuid::Uuid;
+
+const DEFAULT_RECONCILIATION_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_ORPHAN_GRACE_SECS: u64 = 900;
+
+/// Repairs are opt-in; without them reconciliation only reports.
+#[derive(Debug, Default, Deserialize)]
+pub struct RepairOptions {
+    #[serde(default)]
+    pub delete_orphaned_remotes: bool,
+    #[serde(default)]
+    pub recreate_missing_remotes: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissingRemote {
+    pub repository_id: Uuid,
+    pub user_id: Uuid,
+    pub repo_name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NamingMismatch {
+    pub repository_id: Uuid,
+    pub repo_name: String,
+    pub expected_repo_name: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReconciliationReport {
+    /// Repositories on the git service without a `repositories` row.
+    pub orphaned_remotes: Vec<String>,
+    /// Orphaned remotes first seen less than the grace period ago, which may
+    /// belong to a repository still being created and are never deleted.
+    pub recent_orphaned_remotes: Vec<String>,
+    /// `repositories` rows whose repository is gone from the git service.
+    pub missing_remotes: Vec<MissingRemote>,
+    /// Rows not following the `username__repo` naming convention.
+    pub naming_mismatches: Vec<NamingMismatch>,
+    pub deleted_remotes: Vec<String>,
+    pub recreated_remotes: Vec<Uuid>,
+    pub repair_errors: Vec<String>,
+}
+
+/// How long a remote has to be orphaned before it may be deleted,
+/// `REPO_ORPHAN_GRACE_SECS`.
+fn orphan_grace_secs() -> u64 {
+    std::env::var("REPO_ORPHAN_GRACE_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_ORPHAN_GRACE_SECS)
+}
+
+/// Compares the repositories known to the git service with the
+/// `repositories` table and optionally repairs the differences.
+pub async fn reconcile_repositories(
+    pool: &DbPool,
+    options: &RepairOptions,
+) -> Result<ReconciliationReport> {
+    let remote_repos = git::list_repos()
+        .await
+        .context("Failed to list repositories from git service")?;
+    let remote_repos: HashSet<String> = remote_repos.into_iter().collect();
+
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repos = Repository::get_all_with_owner(&mut conn)
+        .context("Failed to get repositories from database")?;
+    let starter_urls: HashMap<Uuid, serde_json::Value> = Challenge::get_all_challenges(&mut conn)
+        .context("Failed to get challenges from database")?
+        .into_iter()
+        .map(|challenge| (challenge.id, challenge.repo_urls))
+        .collect();
+
+    let mut report = ReconciliationReport::default();
+    let mut known_repos = HashSet::new();
+    let mut missing = Vec::new();
+
+    for (repo, username) in repos {
+        let repo_name = match repo_name_from_url(&repo.soft_serve_url) {
+            Some(name) => name,
+            None => {
+                warn!(
+                    "Repository {} has an unparsable url: {}",
+                    repo.id, repo.soft_serve_url
+                );
+                continue;
+            }
+        };
+        let starter_url = starter_urls
+            .get(&repo.challenge_id)
+            .and_then(|urls| urls.get(&repo.language))
+            .and_then(|url| url.as_str());
+
+        if let Some(starter_name) = starter_url.and_then(repo_name_from_url) {
+            let expected_repo_name = format!("{}__{}", username, starter_name);
+            if repo_name != expected_repo_name {
+                report.naming_mismatches.push(NamingMismatch {
+                    repository_id: repo.id,
+                    repo_name: repo_name.clone(),
+                    expected_repo_name,
+                });
+            }
+        }
+
+        if !remote_repos.contains(&repo_name) {
+            report.missing_remotes.push(MissingRemote {
+                repository_id: repo.id,
+                user_id: repo.user_id,
+                repo_name: repo_name.clone(),
+            });
+            missing.push((repo.id, repo_name.clone(), starter_url.map(str::to_string)));
+        }
+        known_repos.insert(repo_name);
+    }
+
+    report.orphaned_remotes = remote_repos
+        .difference(&known_repos)
+        .cloned()
+        .collect::<Vec<String>>();
+    report.orphaned_remotes.sort();
+
+    // A remote is created before its row is committed, so a remote only
+    // counts as orphaned once it has been seen without a row for a while.
+    let grace_cutoff =
+        chrono::Utc::now().naive_utc() - chrono::Duration::seconds(orphan_grace_secs() as i64);
+    let mut stale_orphans = Vec::new();
+    for orphan in OrphanedRemote::record_sightings(&mut conn, &report.orphaned_remotes)
+        .context("Failed to record orphaned remotes")?
+    {
+        if orphan.first_seen_at <= grace_cutoff {
+            stale_orphans.push(orphan.repo_name);
+        } else {
+            report.recent_orphaned_remotes.push(orphan.repo_name);
+        }
+    }
+
+    if options.delete_orphaned_remotes && !stale_orphans.is_empty() {
+        // check again right before deleting in case a row was added since
+        let known_repos: HashSet<String> = Repository::get_all_with_owner(&mut conn)
+            .context("Failed to get repositories from database")?
+            .into_iter()
+            .filter_map(|(repo, _)| repo_name_from_url(&repo.soft_serve_url))
+            .collect();
+        for repo_name in stale_orphans {
+            if known_repos.contains(&repo_name) {
+                continue;
+            }
+            match git::delete_repo(&repo_name).await {
+                Ok(_) => report.deleted_remotes.push(repo_name),
+                Err(e) => report
+                    .repair_errors
+                    .push(format!("Failed to delete {}: {}", repo_name, e)),
+            }
+        }
+    }
+
+    if options.recreate_missing_remotes {
+        for (repository_id, repo_name, starter_url) in missing {
+            let Some(starter_url) = starter_url else {
+                report
+                    .repair_errors
+                    .push(format!("No starter code to recreate {} from", repo_name));
+                continue;
+            };
+            let recreated = match git::create_repo(&repo_name, &starter_url).await {
+                Ok(response) => response,
+                Err(e) => {
+                    report
+                        .repair_errors
+                        .push(format!("Failed to recreate {}: {}", repo_name, e));
+                    continue;
+                }
+            };
+            let updated = strip_url_credentials(&recreated.repo_url)
+                .context("Failed to parse repository url")
+                .and_then(|soft_serve_url| {
+                    Repository::update_urls(
+                        &mut conn,
+                        &repository_id,
+                        &recreated.repo_url,
+                        &soft_serve_url,
+                    )
+                });
+            match updated {
+                Ok(_) => report.recreated_remotes.push(repository_id),
+                Err(e) => report
+                    .repair_errors
+                    .push(format!("Failed to update {}: {}", repo_name, e)),
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Runs a report-only reconciliation on an interval and logs any drift.
+/// Set `REPO_RECONCILIATION_INTERVAL_SECS` to 0 to disable it.
+pub async fn run_periodic_reconciliation(pool: DbPool) {
+    let interval_secs = std::env::var("REPO_RECONCILIATION_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_RECONCILIATION_INTERVAL_SECS);
+    if interval_secs == 0 {
+        info!("Repository reconciliation is disabled");
+        return;
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+    loop {
+        interval.tick().await;
+        match reconcile_repositories(&pool, &RepairOptions::default()).await {
+            Ok(report) => {
+                if report.orphaned_remotes.is_empty()
+                    && report.missing_remotes.is_empty()
+                    && report.naming_mismatches.is_empty()
+                {
+                    info!("Repository reconciliation found no drift");
+                } else {
+                    warn!(
+                        "Repository reconciliation found {} orphaned remotes, {} missing remotes and {} naming mismatches",
+                        report.orphaned_remotes.len(),
+                        report.missing_remotes.len(),
+                        report.naming_mismatches.len()
+                    );
+                }
+            }
+            Err(e) => error!("Repository reconciliation failed: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    app::{
+        auth::middleware::SessionInfo,
+        websockets::{delivery::record_event, manager::WebSocketManagerHandle, protocol::Topic},
+    },
+    service::database::{conn::DbPool, models::User},
+    shared::{errors::RepositoryError, primitives::UserRole},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Scope};
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+
+pub fn init() -> Scope {
+    web::scope("/announcements").route("", web::post().to(create_announcement))
+}
+
+#[derive(Debug, Deserialize)]
+struct AnnouncementRequest {
+    message: String,
+}
+
+/// Publishes a message to every websocket connection subscribed to
+/// `announcements`. Users who are offline get it when they reconnect within
+/// the retention window.
+async fn create_announcement(
+    req: HttpRequest,
+    body: Result<web::Json<AnnouncementRequest>, actix_web::Error>,
+    pool: web::Data<DbPool>,
+    manager_handle: web::Data<WebSocketManagerHandle>,
+) -> Result<HttpResponse, RepositoryError> {
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Error parsing request body: {}", e);
+            return Err(RepositoryError::BadRequest(
+                "Invalid request body".to_string(),
+            ));
+        }
+    };
+    if body.message.trim().is_empty() {
+        return Err(RepositoryError::BadRequest(
+            "Message is required".to_string(),
+        ));
+    }
+
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+    let user = User::get_user(&mut conn, Some(&user_id), None, None, None).map_err(|e| {
+        error!("Error getting user: {}", e);
+        RepositoryError::BadRequest("User not found".to_string())
+    })?;
+    if user.role != UserRole::Admin.to_str() {
+        return Ok(HttpResponse::Forbidden().json(json!({
+            "status": "error",
+            "message": "Forbidden. Only administrators can make announcements."
+        })));
+    }
+
+    let topic = Topic::Announcements;
+    let message = record_event(
+        &mut conn,
+        None,
+        &topic,
+        "announcement",
+        json!({ "message": body.message, "author": user.username }),
+    );
+    manager_handle
+        .publish(&topic, &message)
+        .await
+        .map_err(|e| {
+            error!("Error publishing announcement: {}", e);
+            RepositoryError::BadRequest("Failed to publish announcement".to_string())
+        })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "id": message.id,
+    })))
+}
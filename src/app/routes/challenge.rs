@@ -1,12 +1,22 @@
 use crate::{
-    app::auth::middleware::SessionInfo,
-    service::database::{
-        conn::DbPool,
-        models::{Challenge, Repository, User},
+    app::{
+        auth::middleware::SessionInfo, progress::fork_progress::is_forked,
+        submission::{
+            request_test_run::{job_for_progress, track_requested_test_run, untrack_test_run},
+            track_test_run::broadcast_test_run,
+        },
+        websockets::manager::WebSocketManagerHandle,
+    },
+    service::{
+        database::{
+            conn::DbPool,
+            models::{Challenge, Repository, SubmissionTestCase, User},
+        },
+        event_publisher::EventPublisherHandle,
     },
     shared::{
         errors::{CreateChallengeError, GetChallengeError, GetRepositoryError, RepositoryError},
-        primitives::{ChallengeMode, Difficulty, Period, UserRole},
+        primitives::{AttemptSort, ChallengeMode, Difficulty, Period, UserRole},
     },
 };
 use actix_web::{
@@ -21,10 +31,24 @@ pub fn init() -> Scope {
         .route("", web::get().to(get_challenge))
         .route("", web::post().to(create_challenge))
         .route("/{id}", web::put().to(update_challenge))
+        .route("/{id}/test-runs", web::post().to(create_test_runs))
+        .route(
+            "/{id}/test-cases/failures",
+            web::get().to(get_test_case_failures),
+        )
         .route("/attempts", web::get().to(get_all_attempts))
         .route("", web::delete().to(delete_challenge))
 }
 
+const DEFAULT_TEST_CASE_FAILURES_LIMIT: i64 = 20;
+const MAX_TEST_CASE_FAILURES_LIMIT: i64 = 100;
+
+#[derive(serde::Deserialize)]
+struct TestCaseFailuresQuery {
+    step: Option<i32>,
+    limit: Option<i64>,
+}
+
 #[derive(serde::Deserialize, serde::Serialize)]
 struct NewChallenge {
     title: String,
@@ -47,6 +71,7 @@ struct GetChallengeQuery {
 struct GetAllAttemptsQuery {
     period: Option<Period>,
     challenge_id: Option<Uuid>,
+    sort: Option<AttemptSort>,
 }
 
 #[derive(serde::Deserialize)]
@@ -275,7 +300,7 @@ async fn get_all_attempts(
         }
     };
 
-    let attempts = Repository::get_all_repos(&mut conn, period, challenge_id)
+    let attempts = Repository::get_all_repos(&mut conn, period, challenge_id, query.sort.as_ref())
         .map(|attempts| HttpResponse::Ok().json(attempts))
         .map_err(|e| match e.downcast_ref() {
             Some(RepositoryError::FailedToGetRepository(GetRepositoryError(e))) => {
@@ -356,6 +381,182 @@ async fn delete_challenge(
     }
 }
 
+/// Tests the head of every attempt at a challenge again, e.g. after its test
+/// suite was fixed. Results are applied as they come back from the runner.
+async fn create_test_runs(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    pool: web::Data<DbPool>,
+    events: web::Data<EventPublisherHandle>,
+    manager_handle: web::Data<WebSocketManagerHandle>,
+) -> Result<HttpResponse, Error> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Ok(HttpResponse::build(StatusCode::UNAUTHORIZED).json(json!({
+                "status": "error",
+                "message": "Unauthorized."
+            })));
+        }
+    };
+
+    let user = match User::get_user(&mut conn, Some(&user_id), None, None, None) {
+        Ok(user) => user,
+        Err(e) => {
+            error!("Error getting user: {}", e);
+            return Err(Error::from(RepositoryError::BadRequest(
+                "User not found".to_string(),
+            )));
+        }
+    };
+
+    if user.role != UserRole::Admin.to_str() {
+        return Ok(HttpResponse::build(StatusCode::FORBIDDEN).json(json!({
+            "status": "error",
+            "message": "Forbidden. Only admins can re-run tests."
+        })));
+    }
+
+    if Challenge::get_challenge(&mut conn, Some(&id), None, None, None).is_err() {
+        return Ok(HttpResponse::build(StatusCode::NOT_FOUND).json(json!({
+            "status": "error",
+            "message": "Challenge not found"
+        })));
+    }
+
+    let attempts = Repository::get_by_challenge_with_progress(&mut conn, &id).map_err(|e| {
+        error!("Error getting attempts: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let mut test_runs = Vec::new();
+    let mut failures = Vec::new();
+    // forked attempts no longer earn progress, so there is nothing to grade
+    for (repo, progress) in attempts.iter().filter(|(_, progress)| !is_forked(progress)) {
+        let job = job_for_progress(repo, progress, None, &user_id);
+        let test_run_id = job.test_run_id;
+        let test_run = json!({
+            "test_run_id": job.test_run_id,
+            "repository_id": repo.id,
+            "stage": job.stage,
+        });
+        // one attempt that can't be queued shouldn't keep the others from it
+        let tracked = match track_requested_test_run(&mut conn, repo, &test_run_id, None, &user_id)
+        {
+            Ok(tracked) => tracked,
+            Err(e) => {
+                error!("Error tracking test run {}: {:?}", test_run_id, e);
+                failures.push(json!({
+                    "repository_id": repo.id,
+                    "message": format!("Could not track test run: {}", e),
+                }));
+                continue;
+            }
+        };
+        if let Err(e) = events.queue_test_run(job) {
+            error!(
+                "Error queueing test run for repository {}: {:?}",
+                repo.id, e
+            );
+            if let Err(e) = untrack_test_run(&mut conn, &test_run_id) {
+                error!("Error dropping test run {}: {:?}", test_run_id, e);
+            }
+            failures.push(json!({
+                "repository_id": repo.id,
+                "message": format!("Could not queue test run: {}", e),
+            }));
+            continue;
+        }
+        test_runs.push(test_run);
+        broadcast_test_run(&pool, &manager_handle, &repo.soft_serve_url, &tracked).await;
+    }
+
+    let status = match (test_runs.is_empty(), failures.is_empty()) {
+        (_, true) => "queued",
+        (false, false) => "partially_queued",
+        (true, false) => "failed",
+    };
+    let response = json!({
+        "status": status,
+        "count": test_runs.len(),
+        "test_runs": test_runs,
+        "failures": failures,
+    });
+    if status == "failed" {
+        return Ok(HttpResponse::ServiceUnavailable().json(response));
+    }
+    Ok(HttpResponse::Accepted().json(response))
+}
+
+/// Test cases of a challenge that fail most often across all attempts, so
+/// authors can see where learners get stuck or which tests are flaky.
+async fn get_test_case_failures(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    query: web::Query<TestCaseFailuresQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, Error> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Ok(HttpResponse::build(StatusCode::UNAUTHORIZED).json(json!({
+                "status": "error",
+                "message": "Unauthorized."
+            })));
+        }
+    };
+
+    let user = match User::get_user(&mut conn, Some(&user_id), None, None, None) {
+        Ok(user) => user,
+        Err(e) => {
+            error!("Error getting user: {}", e);
+            return Err(Error::from(RepositoryError::BadRequest(
+                "User not found".to_string(),
+            )));
+        }
+    };
+
+    if user.role != UserRole::Admin.to_str() {
+        return Ok(HttpResponse::build(StatusCode::FORBIDDEN).json(json!({
+            "status": "error",
+            "message": "Forbidden. Only admins can view test case failures."
+        })));
+    }
+
+    if Challenge::get_challenge(&mut conn, Some(&id), None, None, None).is_err() {
+        return Ok(HttpResponse::build(StatusCode::NOT_FOUND).json(json!({
+            "status": "error",
+            "message": "Challenge not found"
+        })));
+    }
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_TEST_CASE_FAILURES_LIMIT)
+        .clamp(1, MAX_TEST_CASE_FAILURES_LIMIT);
+    let failures = SubmissionTestCase::get_failure_stats(&mut conn, &id, query.step, limit)
+        .map_err(|e| {
+            error!("Error getting test case failures: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "challenge_id": *id,
+        "step": query.step,
+        "test_cases": failures,
+    })))
+}
+
 async fn update_challenge(
     req: HttpRequest,
     id: web::Path<Uuid>,
@@ -0,0 +1,94 @@
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Scope};
+use log::error;
+use serde_json::{json, Value};
+
+use crate::{
+    app::auth::middleware::SessionInfo,
+    service::{
+        database::{conn::DbPool, models::User},
+        event_bus::memory::EventInjector,
+        queue::InboundQueue,
+    },
+    shared::{errors::RepositoryError, primitives::UserRole},
+};
+
+/// Only mounted when core runs on the in-memory event bus with
+/// `ENABLE_DEV_ROUTES=true`, and only usable by admins.
+pub fn init() -> Scope {
+    web::scope("/dev")
+        .route("/events/published", web::get().to(get_published_events))
+        .route("/events/{queue}", web::post().to(inject_event))
+}
+
+/// Processes `body` as if it had arrived on `queue`, e.g. `webhook` or
+/// `test-runner`, and responds once it has gone through the same handling
+/// as a message from the broker.
+async fn inject_event(
+    req: HttpRequest,
+    queue: web::Path<InboundQueue>,
+    body: web::Json<Value>,
+    injector: web::Data<EventInjector>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    if let Some(forbidden) = require_admin(&req, &pool)? {
+        return Ok(forbidden);
+    }
+
+    let queue = queue.into_inner();
+    let data = serde_json::to_vec(&body.into_inner())
+        .map_err(|e| RepositoryError::BadRequest(e.to_string()))?;
+
+    injector.inject(queue, data).await.map_err(|e| {
+        error!("Injected {:?} message was not processed: {:#}", queue, e);
+        RepositoryError::BadRequest(format!("Message was not processed: {:#}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "Message processed"
+    })))
+}
+
+/// Domain events and test run jobs published since startup, oldest first.
+async fn get_published_events(
+    req: HttpRequest,
+    injector: web::Data<EventInjector>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    if let Some(forbidden) = require_admin(&req, &pool)? {
+        return Ok(forbidden);
+    }
+    Ok(HttpResponse::Ok().json(injector.published().await))
+}
+
+/// Returns the response to send when the user is not an admin.
+fn require_admin(
+    req: &HttpRequest,
+    pool: &DbPool,
+) -> Result<Option<HttpResponse>, RepositoryError> {
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+    let user = User::get_user(&mut conn, Some(&user_id), None, None, None).map_err(|e| {
+        error!("Error getting user: {}", e);
+        RepositoryError::BadRequest("User not found".to_string())
+    })?;
+
+    if user.role != UserRole::Admin.to_str() {
+        return Ok(Some(HttpResponse::Forbidden().json(json!({
+            "status": "error",
+            "message": "Forbidden. Only administrators can use the dev routes."
+        }))));
+    }
+    Ok(None)
+}
@@ -19,10 +19,12 @@ impl ResponseError for RepositoryError {
             RepositoryError::BadRequest(_) => StatusCode::BAD_REQUEST,
             RepositoryError::RepositoryAlreadyExists => StatusCode::CONFLICT,
             RepositoryError::UserAlreadyExists => StatusCode::CONFLICT,
+            RepositoryError::IdempotencyConflict(_) => StatusCode::CONFLICT,
             RepositoryError::UserNotFound => StatusCode::NOT_FOUND,
             RepositoryError::FailedToGetUser(_) => StatusCode::NOT_FOUND,
             RepositoryError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             RepositoryError::ServerConfigurationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            RepositoryError::GitServiceError(_) => StatusCode::BAD_GATEWAY,
             RepositoryError::NotFound(_) => StatusCode::NOT_FOUND,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -1,17 +1,35 @@
 use actix_web::{web, HttpResponse, Responder, Scope};
 use serde_json::json;
 
-use crate::service::database::conn::DbPool;
+use crate::{
+    app::websockets::manager::WebSocketManagerHandle,
+    service::{database::conn::DbPool, queue_health::QueueHealthHandle},
+};
 
 pub fn init() -> Scope {
     web::scope("/health").route("", web::get().to(health_check))
 }
 
-async fn health_check(pool: web::Data<DbPool>) -> impl Responder {
+async fn health_check(
+    pool: web::Data<DbPool>,
+    queue_health: web::Data<QueueHealthHandle>,
+    manager_handle: web::Data<WebSocketManagerHandle>,
+) -> impl Responder {
+    // A broker outage doesn't take the API down, it is reported alongside
+    // so that it can be alerted on separately.
+    let consumers = queue_health.snapshot().await;
+    let queue_status = match queue_health.is_healthy().await {
+        true => "healthy",
+        false => "degraded",
+    };
+
     match pool.get() {
         Ok(_) => HttpResponse::Ok().json(json!({
             "status": "success",
-            "message": "Server and database are healthy"
+            "message": "Server and database are healthy",
+            "queue_status": queue_status,
+            "consumers": consumers,
+            "websocket": manager_handle.metrics()
         })),
         Err(_) => HttpResponse::ServiceUnavailable().json("Server or Database connection failed"),
     }
@@ -0,0 +1,198 @@
+use crate::{
+    app::{
+        auth::middleware::SessionInfo,
+        inbound_event::replay::{replay_event, replay_range},
+    },
+    service::{
+        database::{
+            conn::DbPool,
+            models::{InboundEvent, User},
+        },
+        queue::{InboundQueue, MessageHandler},
+        repository::inbound_event::InboundEventFilter,
+    },
+    shared::{
+        errors::RepositoryError,
+        primitives::{InboundEventStatus, PaginationParams, UserRole},
+    },
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Scope};
+use chrono::NaiveDateTime;
+use diesel::PgConnection;
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+#[derive(Deserialize)]
+pub struct GetInboundEventsQuery {
+    status: Option<InboundEventStatus>,
+    queue: Option<String>,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ReplayRangeRequest {
+    from: NaiveDateTime,
+    to: NaiveDateTime,
+    queue: Option<String>,
+}
+
+pub fn init() -> Scope {
+    web::scope("/inbound-events")
+        .route("", web::get().to(get_inbound_events))
+        .route("/replay", web::post().to(replay_inbound_events))
+        .route("/{id}", web::get().to(get_inbound_event))
+        .route("/{id}/replay", web::post().to(replay_inbound_event))
+}
+
+/// Stored queue messages, newest first, e.g. `?status=Failed` to find the
+/// ones worth replaying.
+async fn get_inbound_events(
+    req: HttpRequest,
+    query: web::Query<GetInboundEventsQuery>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+    if !is_admin(&req, &mut conn)? {
+        return Ok(forbidden());
+    }
+
+    let queue = parse_queue(query.queue.as_deref())?;
+    let filter = InboundEventFilter {
+        status: query.status.as_ref(),
+        queue: queue.map(|queue| queue.to_str()),
+        from: query.from,
+        to: query.to,
+        ..InboundEventFilter::default()
+    };
+    let pagination = PaginationParams {
+        page: query.page,
+        per_page: query.per_page,
+    };
+    let events = InboundEvent::list(&mut conn, &filter, &pagination).map_err(|e| {
+        error!("Error getting inbound events: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    Ok(HttpResponse::Ok().json(events))
+}
+
+async fn get_inbound_event(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+    if !is_admin(&req, &mut conn)? {
+        return Ok(forbidden());
+    }
+
+    let event = InboundEvent::get(&mut conn, &id)
+        .map_err(|_| RepositoryError::NotFound("Event not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(event))
+}
+
+async fn replay_inbound_event(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    pool: web::Data<DbPool>,
+    handler: web::Data<MessageHandler>,
+) -> Result<HttpResponse, RepositoryError> {
+    {
+        let mut conn = pool.get().map_err(|e| {
+            error!("Error getting db connection from pool: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+        if !is_admin(&req, &mut conn)? {
+            return Ok(forbidden());
+        }
+    }
+
+    let event = replay_event(&handler, &pool, &id).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "event": event
+    })))
+}
+
+/// Replays every failed event received between `from` and `to`.
+async fn replay_inbound_events(
+    req: HttpRequest,
+    body: web::Json<ReplayRangeRequest>,
+    pool: web::Data<DbPool>,
+    handler: web::Data<MessageHandler>,
+) -> Result<HttpResponse, RepositoryError> {
+    {
+        let mut conn = pool.get().map_err(|e| {
+            error!("Error getting db connection from pool: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+        if !is_admin(&req, &mut conn)? {
+            return Ok(forbidden());
+        }
+    }
+
+    if body.from >= body.to {
+        return Err(RepositoryError::BadRequest(
+            "from must be before to".to_string(),
+        ));
+    }
+    let queue = parse_queue(body.queue.as_deref())?;
+    let filter = InboundEventFilter {
+        queue: queue.map(|queue| queue.to_str()),
+        from: Some(body.from),
+        to: Some(body.to),
+        ..InboundEventFilter::default()
+    };
+    let report = replay_range(&handler, &pool, filter).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "report": report
+    })))
+}
+
+fn is_admin(req: &HttpRequest, conn: &mut PgConnection) -> Result<bool, RepositoryError> {
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let user = User::get_user(conn, Some(&user_id), None, None, None).map_err(|e| {
+        error!("Error getting user: {}", e);
+        RepositoryError::BadRequest("User not found".to_string())
+    })?;
+    Ok(user.role == UserRole::Admin.to_str())
+}
+
+fn forbidden() -> HttpResponse {
+    HttpResponse::Forbidden().json(json!({
+        "status": "error",
+        "message": "Forbidden. Only administrators can manage inbound events."
+    }))
+}
+
+fn parse_queue(queue: Option<&str>) -> Result<Option<InboundQueue>, RepositoryError> {
+    queue
+        .map(|queue| {
+            InboundQueue::from_str(queue)
+                .map_err(|e| RepositoryError::BadRequest(format!("{}: {}", e, queue)))
+        })
+        .transpose()
+}
@@ -9,6 +9,10 @@ pub mod repo;
 pub mod challenge;
 pub mod progress;
 pub mod leaderboard;
+pub mod dev;
+pub mod inbound_events;
+pub mod runners;
+pub mod announcements;
 
 pub fn init(cfg: &mut web::ServiceConfig) {
     cfg.service(health::init());
@@ -19,4 +23,7 @@ pub fn init(cfg: &mut web::ServiceConfig) {
     cfg.service(challenge::init());
     cfg.service(progress::init());
     cfg.service(leaderboard::init());
+    cfg.service(inbound_events::init());
+    cfg.service(runners::init());
+    cfg.service(announcements::init());
 }
@@ -1,33 +1,52 @@
 use crate::{
-    app::auth::middleware::SessionInfo,
-    service::database::{
-        conn::DbPool,
-        models::{Challenge, Leaderboard, Progress, Repository, User},
+    app::{
+        auth::middleware::SessionInfo,
+        idempotency::claim::{
+            claim_idempotency_key, idempotency_key_from_request, release_idempotency_key,
+            IdempotencyClaim,
+        },
+        progress::{
+            abandon_progress::abandon_progress,
+            fork_progress::{fork_progress, is_forked},
+            reset_progress::reset_progress,
+        },
+        repo::reconcile::{reconcile_repositories, RepairOptions},
+        submission::{
+            request_test_run::{job_for_progress, track_requested_test_run, untrack_test_run},
+            track_test_run::broadcast_test_run,
+        },
+        websockets::{manager::WebSocketManagerHandle, protocol::Topic},
+    },
+    service::{
+        database::{
+            conn::DbPool,
+            models::{
+                Challenge, IdempotencyKey, Leaderboard, LeaderboardWithChallenge, Progress,
+                ProgressArchive, Repository, RepositoryPush, Submission, SubmissionTestCase,
+                TestRun, User,
+            },
+        },
+        event_publisher::EventPublisherHandle,
+        events::{DomainEvent, RepoCreated},
+        git::{self, CreateRepoResponse},
     },
     shared::{
         errors::{
-            CreateProgressError, CreateRepositoryError, RepositoryError, UpdateLeaderboardError,
+            CreateProgressError, CreateRepositoryError, DeleteProgressError, DeleteRepositoryError,
+            RepositoryError, UpdateLeaderboardError, UpdateProgressError, UpdateRepositoryError,
         },
-        primitives::{PaginationParams, Status, UserRole},
+        primitives::{PaginationParams, Status, TestCaseStatus, UserRole},
+        utils::{repo_name_from_url, strip_url_credentials},
     },
 };
 use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Scope};
-use diesel::{
-    r2d2::{ConnectionManager, PooledConnection},
-    Connection, PgConnection,
-};
-use log::error;
+use diesel::{Connection, PgConnection};
+use log::{error, info, warn};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use uuid::Uuid;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateRepoResponse {
-    repo_name: String,
-    repo_url: String,
-}
-
 #[derive(Deserialize)]
 pub struct CreateRepoRequest {
     repo_url: String,
@@ -46,36 +65,57 @@ pub struct GetRepoQuery {
 }
 
 #[derive(Deserialize)]
-struct DeleteRepoRequest {
-    repo_name: String,
+pub struct ForkRepoRequest {
+    language: String,
+    #[serde(default)]
+    carry_over_progress: bool,
 }
 
-#[derive(Debug, Deserialize)]
-struct ListReposResponse {
-    repositories: Vec<String>,
+#[derive(Deserialize, Default)]
+pub struct TestRunRequest {
+    commit_sha: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct DeleteRepoResponse {
+#[derive(Deserialize)]
+struct DeleteRepoRequest {
     repo_name: String,
-    message: String,
 }
 
 pub fn init() -> Scope {
     web::scope("/repo")
         .route("", web::post().to(create_repo))
         .route("", web::get().to(get_repo))
+        .route("/{id}/reset", web::post().to(reset_repo))
+        .route("/{id}/fork", web::post().to(fork_repo))
+        .route("/{id}/activity", web::get().to(get_repo_activity))
+        .route("/{id}/submissions", web::get().to(get_repo_submissions))
+        .route(
+            "/{id}/submissions/{submission_id}/tests",
+            web::get().to(get_submission_tests),
+        )
+        .route("/{id}/test-runs", web::post().to(create_test_run))
+        .route("/{id}/test-runs", web::get().to(get_test_runs))
+        .route(
+            "/{id}/rotate-credentials",
+            web::post().to(rotate_repo_credentials),
+        )
         .route("/list_softserve_repo", web::get().to(list_softserve_repos))
+        .route("/reconcile", web::get().to(report_repo_drift))
+        .route("/reconcile", web::post().to(reconcile_repos))
         .route(
             "/delete_softserve_repo",
             web::delete().to(delete_softserve_repo),
         )
+        // registered after the fixed paths so they are not taken as an id
+        .route("/{id}", web::delete().to(abandon_repo))
 }
 
 async fn create_repo(
     req: HttpRequest,
     body: Result<web::Json<CreateRepoRequest>, actix_web::Error>,
     pool: web::Data<DbPool>,
+    events: web::Data<EventPublisherHandle>,
+    manager_handle: web::Data<WebSocketManagerHandle>,
 ) -> Result<HttpResponse, RepositoryError> {
     let body = match body {
         Ok(body) => body,
@@ -99,13 +139,6 @@ async fn create_repo(
         ));
     }
 
-    let client = reqwest::Client::new();
-    let git_service_url = std::env::var("GIT_SERVICE_URL").map_err(|_| {
-        error!("GIT_SERVICE_URL environment variable not set");
-        RepositoryError::ServerConfigurationError(
-            "GIT_SERVICE_URL environment variable not set".to_string(),
-        )
-    })?;
     let mut conn = pool.get().map_err(|e| {
         error!("Error getting db connection from pool: {}", e);
         RepositoryError::DatabaseError(e.to_string())
@@ -119,7 +152,51 @@ async fn create_repo(
             ));
         }
     };
-    let user = match User::get_user(&mut conn, Some(&user_id), None, None, None) {
+
+    // A retried request carrying an idempotency key that already succeeded
+    // gets the stored response back instead of a duplicate repository error.
+    let claimed_key = match idempotency_key_from_request(&req)? {
+        Some(key) => {
+            let request_body = json!({
+                "repo_url": &body.repo_url,
+                "language": body.language.to_lowercase(),
+            });
+            match claim_idempotency_key(&mut conn, &user_id, &key, req.path(), request_body)? {
+                IdempotencyClaim::Claimed(claimed) => Some(claimed),
+                IdempotencyClaim::Replay(response) => return Ok(response),
+            }
+        }
+        None => None,
+    };
+
+    match provision_repo(&mut conn, &user_id, &body, claimed_key.as_ref()).await {
+        Ok((new_repo, response)) => {
+            subscribe_owner(&manager_handle, &new_repo).await;
+            events.publish(DomainEvent::RepoCreated(RepoCreated {
+                repository_id: new_repo.id,
+                user_id: new_repo.user_id,
+                challenge_id: new_repo.challenge_id,
+                language: new_repo.language,
+                forked_from: None,
+            }));
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(e) => {
+            if let Some(key) = claimed_key.as_ref() {
+                release_idempotency_key(&mut conn, key);
+            }
+            Err(e)
+        }
+    }
+}
+
+async fn provision_repo(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    body: &CreateRepoRequest,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> Result<(Repository, serde_json::Value), RepositoryError> {
+    let user = match User::get_user(conn, Some(user_id), None, None, None) {
         Ok(user) => user,
         Err(e) => {
             error!("Error getting user: {}", e);
@@ -128,7 +205,7 @@ async fn create_repo(
     };
 
     let challenge = match Challenge::get_challenge_by_repo_url(
-        &mut conn,
+        conn,
         &body.repo_url,
         &body.language.to_lowercase(),
     ) {
@@ -172,20 +249,18 @@ async fn create_repo(
     }
 
     let existing_repos =
-        Repository::get_repo(&mut conn, None, Some(&user_id), None, None).unwrap_or_default();
+        Repository::get_repo(conn, None, Some(user_id), None, None).unwrap_or_default();
 
-    if existing_repos.len() > 0 {
-        for repo in existing_repos {
-            if repo.challenge_id == challenge.id && repo.language == body.language.to_lowercase() {
-                return Err(RepositoryError::BadRequest(format!(
-                    "You already have a repository for this challenge in {}",
-                    body.language
-                )));
-            }
+    for repo in existing_repos {
+        if repo.challenge_id == challenge.id && repo.language == body.language.to_lowercase() {
+            return Err(RepositoryError::BadRequest(format!(
+                "You already have a repository for this challenge in {}",
+                body.language
+            )));
         }
     }
 
-    let leaderboard = match Leaderboard::get_leaderboard(&mut conn, Some(&user_id)) {
+    let leaderboard = match Leaderboard::get_leaderboard(conn, Some(user_id)) {
         Ok(leaderboard) => leaderboard[0].clone(),
         Err(e) => {
             error!("Error getting leaderboard: {}", e);
@@ -202,54 +277,43 @@ async fn create_repo(
             error!("Invalid repository URL format: {}", body.repo_url);
             RepositoryError::BadRequest("Invalid repository URL format".to_string())
         })?;
-    let request_body = json!({
-        "repo_name": format!("{}__{}", user.username, repo_name),
-        "repo_url": &body.repo_url,
-    });
-    let response = client
-        .post(format!("{}/create_repo", git_service_url))
-        .header("Content-Type", "application/json")
-        .body(request_body.to_string())
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Error creating repository in git service: {:#?}", e);
-            RepositoryError::BadRequest("Error creating repository".to_string())
-        })?;
-    let create_repo_response = match response.status() {
-        StatusCode::OK => response.json::<CreateRepoResponse>().await.map_err(|e| {
-            error!("Error in git service response: {:#?}", e);
-            RepositoryError::BadRequest("Error decoding repository response".to_string())
-        })?,
-        StatusCode::CONFLICT => {
-            return Err(RepositoryError::RepositoryAlreadyExists);
-        }
-        _ => {
-            return Err(RepositoryError::BadRequest(
-                "Error creating repository".to_string(),
-            ));
-        }
-    };
+    let create_repo_response =
+        git::create_repo(&format!("{}__{}", user.username, repo_name), &body.repo_url).await?;
+
+    // From here on the repository exists on the git service. If anything
+    // below fails, delete it again so that a retry doesn't run into
+    // `RepositoryAlreadyExists` for a repository we never recorded.
+    let result = persist_repo(
+        conn,
+        user_id,
+        &challenge,
+        &leaderboard,
+        &body.language.to_lowercase(),
+        &create_repo_response,
+        idempotency_key,
+    );
+    if result.is_err() {
+        compensate_remote_repo(&create_repo_response.repo_name).await;
+    }
+    result
+}
 
+fn persist_repo(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    challenge: &Challenge,
+    leaderboard: &LeaderboardWithChallenge,
+    language: &str,
+    create_repo_response: &CreateRepoResponse,
+    idempotency_key: Option<&IdempotencyKey>,
+) -> Result<(Repository, serde_json::Value), RepositoryError> {
     // Parse the soft_serve_url from the repo_url by removing the user token in the url
     // This is necessary because the user token is not appended to the repo_url in the
     // response from the git service.
     // So both the repo_url with the token (provided to client for cloning) and and
     // the url without the token (for matching with queue events) are stored in the database
-    let (scheme, rest) = create_repo_response
-        .repo_url
-        .split_once("://")
-        .ok_or_else(|| {
-            error!(
-                "Error parsing repository url: {}",
-                create_repo_response.repo_url
-            );
-            RepositoryError::BadRequest("Error parsing repository url".to_string())
-        })?;
-    let soft_serve_url = rest
-        .split_once("@")
-        .map(|(_, path)| format!("{}://{}", scheme, path))
-        .ok_or_else(|| {
+    let soft_serve_url =
+        strip_url_credentials(&create_repo_response.repo_url).ok_or_else(|| {
             error!(
                 "Error parsing repository url: {}",
                 create_repo_response.repo_url
@@ -257,18 +321,18 @@ async fn create_repo(
             RepositoryError::BadRequest("Error parsing repository url".to_string())
         })?;
     let repo = Repository::new(
-        &user_id,
+        user_id,
         &challenge.id,
         &create_repo_response.repo_url,
         &soft_serve_url,
-        &body.language.to_lowercase(),
+        language,
     );
 
     // assign progress detail of 1 for new repositories
     // this is used to track the progress of the user
     // in the challenge
     let new_progress = Progress::new(
-        &user_id,
+        user_id,
         &challenge.id,
         &repo.id,
         Status::NotStarted,
@@ -277,57 +341,97 @@ async fn create_repo(
         })),
     );
 
-    // update leaderboard with expected total score
-    let expected_total_score = leaderboard.score + challenge.module_count;
-
-    let result = conn.transaction::<_, RepositoryError, _>(
-        |conn: &mut PooledConnection<ConnectionManager<PgConnection>>| {
-            let new_repo = match Repository::create_repo(conn, repo) {
-                Ok(repo) => repo,
-                Err(e) => {
-                    error!("Error creating repository in database: {:#?}", e);
-                    return Err(RepositoryError::FailedToCreateRepository(
-                        CreateRepositoryError(diesel::result::Error::DatabaseError(
-                            diesel::result::DatabaseErrorKind::Unknown,
-                            Box::new(e.to_string()),
-                        )),
-                    ));
-                }
-            };
+    // update leaderboard with expected total score. Every attempt started
+    // adds its modules to what is already expected, so that abandoning one
+    // of several open attempts can take exactly its unearned part off again
+    let expected_total_score = leaderboard.expected_total_score + challenge.module_count;
 
-            if let Err(e) = Progress::create_progress(conn, new_progress) {
-                error!("Error creating progress in database: {:#?}", e);
-                return Err(RepositoryError::FailedToCreateProgress(
-                    CreateProgressError(diesel::result::Error::DatabaseError(
+    conn.transaction::<_, RepositoryError, _>(|conn| {
+        let new_repo = match Repository::create_repo(conn, repo) {
+            Ok(repo) => repo,
+            Err(e) => {
+                error!("Error creating repository in database: {:#?}", e);
+                return Err(RepositoryError::FailedToCreateRepository(
+                    CreateRepositoryError(diesel::result::Error::DatabaseError(
                         diesel::result::DatabaseErrorKind::Unknown,
                         Box::new(e.to_string()),
                     )),
                 ));
             }
+        };
 
-            if let Err(e) =
-                Leaderboard::update(conn, &user_id, None, Some(expected_total_score), None)
-            {
-                error!("Error updating leaderboard in database: {:#?}", e);
-                return Err(RepositoryError::FailedToUpdateLeaderboard(
-                    UpdateLeaderboardError(diesel::result::Error::DatabaseError(
-                        diesel::result::DatabaseErrorKind::Unknown,
-                        Box::new(e.to_string()),
-                    )),
-                ));
+        if let Err(e) = Progress::create_progress(conn, new_progress) {
+            error!("Error creating progress in database: {:#?}", e);
+            return Err(RepositoryError::FailedToCreateProgress(
+                CreateProgressError(diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::Unknown,
+                    Box::new(e.to_string()),
+                )),
+            ));
+        }
+
+        if let Err(e) = Leaderboard::update(conn, user_id, None, Some(expected_total_score), None) {
+            error!("Error updating leaderboard in database: {:#?}", e);
+            return Err(RepositoryError::FailedToUpdateLeaderboard(
+                UpdateLeaderboardError(diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::Unknown,
+                    Box::new(e.to_string()),
+                )),
+            ));
+        }
+
+        let response = json!({
+            "repo_name": &create_repo_response.repo_name,
+            "repo_url": &create_repo_response.repo_url,
+            "id": &new_repo.id,
+        });
+
+        // Storing the response in the same transaction means a replayed
+        // request can never observe a key without the rows it created. A
+        // claim that lapsed while the remote was created rolls everything
+        // back, and the caller deletes the remote again.
+        if let Some(key) = idempotency_key {
+            match IdempotencyKey::complete(conn, key, StatusCode::OK.as_u16() as i32, &response) {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    warn!("Idempotency key {} lapsed before it was completed", key.id);
+                    return Err(RepositoryError::IdempotencyConflict(
+                        "The idempotency key expired while the request was processed, please retry"
+                            .to_string(),
+                    ));
+                }
+                Err(e) => {
+                    error!("Error storing idempotent response: {:#?}", e);
+                    return Err(RepositoryError::DatabaseError(e.to_string()));
+                }
             }
+        }
 
-            Ok(HttpResponse::Ok().json(json!({
-                "repo_name": &create_repo_response.repo_name,
-                "repo_url": &create_repo_response.repo_url,
-                "id": &new_repo.id,
-            })))
-        },
-    );
+        Ok((new_repo, response))
+    })
+}
 
-    match result {
-        Ok(response) => Ok(response),
-        Err(e) => Err(e),
+/// Subscribes the open websocket connections of the owner to a repository
+/// created after they connected.
+async fn subscribe_owner(manager_handle: &WebSocketManagerHandle, repo: &Repository) {
+    if let Err(e) = manager_handle
+        .subscribe_user(&repo.user_id, &Topic::Repo(repo.id))
+        .await
+    {
+        error!("Error subscribing to repository {}: {}", repo.id, e);
+    }
+}
+
+/// Deletes a git service repository whose database records could not be
+/// written. Failures are only logged since the original error is what the
+/// client needs to see.
+async fn compensate_remote_repo(repo_name: &str) {
+    match git::delete_repo(repo_name).await {
+        Ok(_) => info!("Deleted orphaned repository {} from git service", repo_name),
+        Err(e) => error!(
+            "Failed to delete orphaned repository {} from git service: {}",
+            repo_name, e
+        ),
     }
 }
 
@@ -390,130 +494,901 @@ async fn get_repo(
     Ok(HttpResponse::Ok().json(repositories))
 }
 
-async fn list_softserve_repos(
+/// Commit timeline of an attempt, newest push first.
+async fn get_repo_activity(
     req: HttpRequest,
+    id: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
     pool: web::Data<DbPool>,
 ) -> Result<HttpResponse, RepositoryError> {
-    let extensions = req.extensions();
-    let session_info = extensions
-        .get::<SessionInfo>()
-        .ok_or_else(|| RepositoryError::BadRequest("User not authenticated".to_string()))?;
-
-    // Get user from database to check role
     let mut conn = pool.get().map_err(|e| {
         error!("Error getting db connection from pool: {}", e);
         RepositoryError::DatabaseError(e.to_string())
     })?;
 
-    let user =
-        User::get_user(&mut conn, Some(&session_info.user_id), None, None, None).map_err(|e| {
-            error!("Error getting user: {}", e);
-            RepositoryError::BadRequest("User not found".to_string())
-        })?;
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
 
-    // Check if user is admin
-    if user.role != UserRole::Admin.to_str() {
-        return Ok(HttpResponse::Forbidden().json(json!({
-            "status": "error",
-            "message": "Forbidden. Only administrators can list all repositories."
-        })));
-    }
+    let repo = get_visible_repo(&mut conn, &id, &user_id)?;
+    let pushes = RepositoryPush::get_by_repository(&mut conn, &repo.id, &query).map_err(|e| {
+        error!("Error fetching repository activity: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
 
-    let client = reqwest::Client::new();
-    let git_service_url = std::env::var("GIT_SERVICE_URL").map_err(|_| {
-        error!("GIT_SERVICE_URL environment variable not set");
-        RepositoryError::ServerConfigurationError(
-            "GIT_SERVICE_URL environment variable not set".to_string(),
-        )
+    Ok(HttpResponse::Ok().json(pushes))
+}
+
+/// Test runs recorded for an attempt, newest first.
+async fn get_repo_submissions(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
     })?;
 
-    let response = client
-        .get(format!("{}/list_repos", git_service_url))
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Error listing repositories from git service: {:#?}", e);
-            RepositoryError::BadRequest("Error listing repositories".to_string())
-        })?;
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
 
-    if !response.status().is_success() {
-        return Err(RepositoryError::BadRequest(
-            "Failed to list repositories".to_string(),
-        ));
-    }
+    let repo = get_visible_repo(&mut conn, &id, &user_id)?;
+    let submissions = Submission::get_by_repository(&mut conn, &repo.id, &query).map_err(|e| {
+        error!("Error fetching submissions: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    Ok(HttpResponse::Ok().json(submissions))
+}
 
-    let list_repos_response = response.json::<ListReposResponse>().await.map_err(|e| {
-        error!("Error parsing repository list response: {:#?}", e);
-        RepositoryError::BadRequest("Error parsing repository list".to_string())
+/// Test cases of a submission, for runs that reported structured results.
+async fn get_submission_tests(
+    req: HttpRequest,
+    path: web::Path<(Uuid, Uuid)>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let (id, submission_id) = path.into_inner();
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
     })?;
 
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let repo = get_visible_repo(&mut conn, &id, &user_id)?;
+    let submission = Submission::get_submission(&mut conn, Some(submission_id.to_string()), None)
+        .ok()
+        .and_then(|submissions| submissions.into_iter().next())
+        .filter(|submission| submission.repository_id == repo.id)
+        .ok_or_else(|| RepositoryError::NotFound("Submission not found".to_string()))?;
+    let test_cases =
+        SubmissionTestCase::get_by_submission(&mut conn, &submission.id).map_err(|e| {
+            error!("Error fetching submission test cases: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+
+    let count = |status: TestCaseStatus| {
+        test_cases
+            .iter()
+            .filter(|test_case| test_case.status == status.to_str())
+            .count()
+    };
     Ok(HttpResponse::Ok().json(json!({
-        "status": "success",
-        "repositories": list_repos_response.repositories
+        "submission_id": submission.id,
+        "status": submission.status,
+        "total": test_cases.len(),
+        "passed": count(TestCaseStatus::Passed),
+        "failed": count(TestCaseStatus::Failed),
+        "skipped": count(TestCaseStatus::Skipped),
+        "test_cases": test_cases,
     })))
 }
 
-async fn delete_softserve_repo(
+/// Asks the test runner to test an attempt at `commit_sha`, or at its head
+/// when no commit is given. The result arrives on the test runner queue like
+/// any other run and is recorded as a new submission, so a commit can be
+/// graded again after its test suite was fixed.
+async fn create_test_run(
     req: HttpRequest,
-    body: web::Json<DeleteRepoRequest>,
+    id: web::Path<Uuid>,
+    body: Option<web::Json<TestRunRequest>>,
     pool: web::Data<DbPool>,
+    events: web::Data<EventPublisherHandle>,
+    manager_handle: web::Data<WebSocketManagerHandle>,
 ) -> Result<HttpResponse, RepositoryError> {
-    let extensions = req.extensions();
-    let session_info = extensions
-        .get::<SessionInfo>()
-        .ok_or_else(|| RepositoryError::BadRequest("User not authenticated".to_string()))?;
-
-    // Get user from database to check role
     let mut conn = pool.get().map_err(|e| {
         error!("Error getting db connection from pool: {}", e);
         RepositoryError::DatabaseError(e.to_string())
     })?;
 
-    let user =
-        User::get_user(&mut conn, Some(&session_info.user_id), None, None, None).map_err(|e| {
-            error!("Error getting user: {}", e);
-            RepositoryError::BadRequest("User not found".to_string())
-        })?;
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
 
-    // Check if user is admin
-    if user.role != UserRole::Admin.to_str() {
-        return Ok(HttpResponse::Forbidden().json(json!({
-            "status": "error",
-            "message": "Forbidden. Only administrators can delete repositories."
-        })));
+    let commit_sha = body.and_then(|body| body.into_inner().commit_sha);
+    if let Some(commit_sha) = &commit_sha {
+        if commit_sha.is_empty() || !commit_sha.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(RepositoryError::BadRequest(
+                "commit_sha must be a hexadecimal commit hash".to_string(),
+            ));
+        }
     }
 
-    let client = reqwest::Client::new();
-    let git_service_url = std::env::var("GIT_SERVICE_URL").map_err(|_| {
-        error!("GIT_SERVICE_URL environment variable not set");
-        RepositoryError::ServerConfigurationError(
-            "GIT_SERVICE_URL environment variable not set".to_string(),
-        )
-    })?;
-
-    let response = client
-        .delete(format!("{}/delete_repo", git_service_url))
-        .json(&json!({
-            "repo_name": body.repo_name
-        }))
-        .send()
-        .await
-        .map_err(|e| {
-            error!("Error deleting repository from git service: {:#?}", e);
-            RepositoryError::BadRequest("Error deleting repository".to_string())
+    let repo = get_visible_repo(&mut conn, &id, &user_id)?;
+    let progress =
+        Progress::get_progress(&mut conn, None, None, None, Some(&repo.id)).map_err(|e| {
+            error!("Error getting progress: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
         })?;
-
-    if !response.status().is_success() {
+    if is_forked(&progress) {
         return Err(RepositoryError::BadRequest(
-            "Failed to delete repository".to_string(),
+            "This attempt was carried over to another language and is no longer tested".to_string(),
         ));
     }
 
-    let delete_response = response.json::<DeleteRepoResponse>().await.map_err(|e| {
-        error!("Error parsing delete response: {:#?}", e);
-        RepositoryError::BadRequest("Error parsing delete response".to_string())
+    let job = job_for_progress(&repo, &progress, commit_sha, &user_id);
+    let test_run_id = job.test_run_id;
+    let commit_sha = job.commit_sha.clone();
+    let response = json!({
+        "status": "queued",
+        "test_run_id": job.test_run_id,
+        "repository_id": repo.id,
+        "commit_sha": job.commit_sha,
+        "stage": job.stage,
+    });
+    let test_run = track_requested_test_run(
+        &mut conn,
+        &repo,
+        &test_run_id,
+        commit_sha.as_deref(),
+        &user_id,
+    )
+    .map_err(|e| {
+        error!("Error tracking test run {}: {:?}", test_run_id, e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+    if let Err(e) = events.queue_test_run(job) {
+        if let Err(e) = untrack_test_run(&mut conn, &test_run_id) {
+            error!("Error dropping test run {}: {:?}", test_run_id, e);
+        }
+        return Err(RepositoryError::ServerConfigurationError(format!(
+            "Could not queue test run: {}",
+            e
+        )));
+    }
+    broadcast_test_run(&pool, &manager_handle, &repo.soft_serve_url, &test_run).await;
+
+    Ok(HttpResponse::Accepted().json(response))
+}
+
+/// Test runs of an attempt, newest first, with the state each one is in.
+async fn get_test_runs(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let repo = get_visible_repo(&mut conn, &id, &user_id)?;
+    let test_runs = TestRun::get_by_repository(&mut conn, &repo.id, &query).map_err(|e| {
+        error!("Error fetching test runs: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    Ok(HttpResponse::Ok().json(test_runs))
+}
+
+async fn reset_repo(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
     })?;
 
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let repo = get_owned_repo(&mut conn, &id, &user_id)?;
+    let challenge = Challenge::get_challenge(&mut conn, Some(&repo.challenge_id), None, None, None)
+        .map_err(|e| {
+            error!("Error getting challenge: {}", e);
+            RepositoryError::NotFound("Challenge not found".to_string())
+        })?;
+    let starter_url = challenge
+        .repo_urls
+        .get(&repo.language)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| {
+            RepositoryError::BadRequest(format!(
+                "Language {} is no longer supported for this challenge",
+                repo.language
+            ))
+        })?;
+    let progress =
+        Progress::get_progress(&mut conn, None, None, None, Some(&repo.id)).map_err(|e| {
+            error!("Error getting progress: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+    if is_forked(&progress) {
+        return Err(RepositoryError::BadRequest(
+            "This attempt was carried over to another language and can no longer be reset"
+                .to_string(),
+        ));
+    }
+    let repo_name = repo_name_from_url(&repo.soft_serve_url).ok_or_else(|| {
+        error!("Error parsing repository name: {}", repo.soft_serve_url);
+        RepositoryError::BadRequest("Error parsing repository url".to_string())
+    })?;
+
+    // Recreate the remote under the same name so the learner only has to
+    // re-clone. A remote that is already gone is fine, we are replacing it.
+    // Deleting it can't be undone, so if a later step fails the repository
+    // is marked as deleted on the git service instead, and resetting again
+    // finishes the job.
+    match git::delete_repo(&repo_name).await {
+        Ok(_) => {}
+        Err(RepositoryError::NotFound(_)) => {
+            warn!(
+                "Repository {} was missing on git service during reset",
+                repo_name
+            );
+        }
+        Err(e) => return Err(e),
+    }
+    let create_repo_response = match git::create_repo(&repo_name, starter_url).await {
+        Ok(response) => response,
+        Err(e) => {
+            mark_reset_incomplete(&mut conn, &repo);
+            return Err(e);
+        }
+    };
+
+    let result = persist_reset(&mut conn, &repo, &create_repo_response);
+    if result.is_err() {
+        compensate_remote_repo(&create_repo_response.repo_name).await;
+        mark_reset_incomplete(&mut conn, &repo);
+    }
+    let (updated_repo, reset_progress, archive) = result?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "repo_name": &create_repo_response.repo_name,
+        "repo_url": &updated_repo.repo_url,
+        "id": &updated_repo.id,
+        "progress": reset_progress,
+        "archived_progress": archive,
+    })))
+}
+
+/// Points the repository at its recreated remote and resets the progress
+/// of the attempt, which is read again under the transaction in case it
+/// changed while the remote was replaced.
+fn persist_reset(
+    conn: &mut PgConnection,
+    repo: &Repository,
+    create_repo_response: &CreateRepoResponse,
+) -> Result<(Repository, Progress, ProgressArchive), RepositoryError> {
+    let soft_serve_url =
+        strip_url_credentials(&create_repo_response.repo_url).ok_or_else(|| {
+            error!(
+                "Error parsing repository url: {}",
+                create_repo_response.repo_url
+            );
+            RepositoryError::BadRequest("Error parsing repository url".to_string())
+        })?;
+
+    conn.transaction::<_, RepositoryError, _>(|conn| {
+        let progress =
+            Progress::get_progress(conn, None, None, None, Some(&repo.id)).map_err(|e| {
+                error!("Error getting progress: {}", e);
+                RepositoryError::DatabaseError(e.to_string())
+            })?;
+        if is_forked(&progress) {
+            return Err(RepositoryError::BadRequest(
+                "This attempt was carried over to another language and can no longer be reset"
+                    .to_string(),
+            ));
+        }
+        let updated_repo = Repository::update_urls(
+            conn,
+            &repo.id,
+            &create_repo_response.repo_url,
+            &soft_serve_url,
+        )
+        .map_err(|e| {
+            error!("Error updating repository in database: {:#?}", e);
+            RepositoryError::FailedToUpdateRepository(UpdateRepositoryError(
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::Unknown,
+                    Box::new(e.to_string()),
+                ),
+            ))
+        })?;
+        let (reset_progress, archive) =
+            reset_progress(conn, &progress, &repo.language).map_err(|e| {
+                error!("Error resetting progress in database: {:#?}", e);
+                RepositoryError::FailedToUpdateProgress(UpdateProgressError(
+                    diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::Unknown,
+                        Box::new(e.to_string()),
+                    ),
+                ))
+            })?;
+        Ok((updated_repo, reset_progress, archive))
+    })
+}
+
+/// Records that a reset left the repository without a usable remote.
+fn mark_reset_incomplete(conn: &mut PgConnection, repo: &Repository) {
+    if let Err(e) = Repository::mark_remote_deleted(conn, &repo.id) {
+        error!(
+            "Error marking repository {} as deleted after a failed reset: {}",
+            repo.id, e
+        );
+    }
+}
+
+async fn fork_repo(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    body: Result<web::Json<ForkRepoRequest>, actix_web::Error>,
+    pool: web::Data<DbPool>,
+    events: web::Data<EventPublisherHandle>,
+    manager_handle: web::Data<WebSocketManagerHandle>,
+) -> Result<HttpResponse, RepositoryError> {
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Error parsing request body: {}", e);
+            return Err(RepositoryError::BadRequest(
+                "Invalid request body".to_string(),
+            ));
+        }
+    };
+    let language = body.language.to_lowercase();
+    if language.is_empty() {
+        return Err(RepositoryError::BadRequest(
+            "Programming language is required".to_string(),
+        ));
+    }
+
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let repo = get_owned_repo(&mut conn, &id, &user_id)?;
+    if repo.language == language {
+        return Err(RepositoryError::BadRequest(format!(
+            "Repository is already in {}",
+            language
+        )));
+    }
+    let user = User::get_user(&mut conn, Some(&user_id), None, None, None).map_err(|e| {
+        error!("Error getting user: {}", e);
+        RepositoryError::BadRequest("User not found".to_string())
+    })?;
+    let challenge = Challenge::get_challenge(&mut conn, Some(&repo.challenge_id), None, None, None)
+        .map_err(|e| {
+            error!("Error getting challenge: {}", e);
+            RepositoryError::NotFound("Challenge not found".to_string())
+        })?;
+    let starter_url = challenge
+        .repo_urls
+        .get(&language)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| {
+            RepositoryError::BadRequest(format!(
+                "Language {} is not supported for this challenge",
+                body.language
+            ))
+        })?
+        .to_string();
+
+    let existing_repos =
+        Repository::get_repo(&mut conn, None, Some(&user_id), None, None).unwrap_or_default();
+    if existing_repos
+        .iter()
+        .any(|existing| existing.challenge_id == challenge.id && existing.language == language)
+    {
+        return Err(RepositoryError::BadRequest(format!(
+            "You already have a repository for this challenge in {}",
+            body.language
+        )));
+    }
+
+    let progress =
+        Progress::get_progress(&mut conn, None, None, None, Some(&repo.id)).map_err(|e| {
+            error!("Error getting progress: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+    if progress.status == Status::Completed.to_str() {
+        return Err(RepositoryError::BadRequest(
+            "Challenge is already completed".to_string(),
+        ));
+    }
+    if is_forked(&progress) {
+        return Err(RepositoryError::BadRequest(
+            "This attempt was already carried over to another language".to_string(),
+        ));
+    }
+
+    let repo_name = starter_url
+        .rsplit("/")
+        .next()
+        .and_then(|name| if name.is_empty() { None } else { Some(name) })
+        .ok_or_else(|| {
+            error!("Invalid repository URL format: {}", starter_url);
+            RepositoryError::BadRequest("Invalid repository URL format".to_string())
+        })?;
+    let create_repo_response =
+        git::create_repo(&format!("{}__{}", user.username, repo_name), &starter_url).await?;
+
+    let result = conn.transaction::<_, RepositoryError, _>(|conn| {
+        let soft_serve_url =
+            strip_url_credentials(&create_repo_response.repo_url).ok_or_else(|| {
+                error!(
+                    "Error parsing repository url: {}",
+                    create_repo_response.repo_url
+                );
+                RepositoryError::BadRequest("Error parsing repository url".to_string())
+            })?;
+        let new_repo = Repository::create_repo(
+            conn,
+            Repository::new(
+                &user_id,
+                &challenge.id,
+                &create_repo_response.repo_url,
+                &soft_serve_url,
+                &language,
+            ),
+        )
+        .map_err(|e| {
+            error!("Error creating repository in database: {:#?}", e);
+            RepositoryError::FailedToCreateRepository(CreateRepositoryError(
+                diesel::result::Error::DatabaseError(
+                    diesel::result::DatabaseErrorKind::Unknown,
+                    Box::new(e.to_string()),
+                ),
+            ))
+        })?;
+
+        let new_progress =
+            fork_progress(conn, &progress, &repo, &new_repo, body.carry_over_progress).map_err(
+                |e| {
+                    error!("Error creating progress in database: {:#?}", e);
+                    RepositoryError::FailedToCreateProgress(CreateProgressError(
+                        diesel::result::Error::DatabaseError(
+                            diesel::result::DatabaseErrorKind::Unknown,
+                            Box::new(e.to_string()),
+                        ),
+                    ))
+                },
+            )?;
+
+        // A carried over attempt continues the steps already counted for the
+        // source repository, a fresh one is a new attempt of its own.
+        if !body.carry_over_progress {
+            let leaderboard = Leaderboard::get_leaderboard(conn, Some(&user_id)).map_err(|e| {
+                error!("Error getting leaderboard: {}", e);
+                RepositoryError::DatabaseError(e.to_string())
+            })?;
+            let expected_total_score = leaderboard[0].expected_total_score + challenge.module_count;
+            Leaderboard::update(conn, &user_id, None, Some(expected_total_score), None).map_err(
+                |e| {
+                    error!("Error updating leaderboard in database: {:#?}", e);
+                    RepositoryError::FailedToUpdateLeaderboard(UpdateLeaderboardError(
+                        diesel::result::Error::DatabaseError(
+                            diesel::result::DatabaseErrorKind::Unknown,
+                            Box::new(e.to_string()),
+                        ),
+                    ))
+                },
+            )?;
+        }
+
+        Ok((new_repo, new_progress))
+    });
+    let (new_repo, new_progress) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            compensate_remote_repo(&create_repo_response.repo_name).await;
+            return Err(e);
+        }
+    };
+
+    subscribe_owner(&manager_handle, &new_repo).await;
+    events.publish(DomainEvent::RepoCreated(RepoCreated {
+        repository_id: new_repo.id,
+        user_id: new_repo.user_id,
+        challenge_id: new_repo.challenge_id,
+        language: new_repo.language.clone(),
+        forked_from: Some(repo.id),
+    }));
+
+    Ok(HttpResponse::Ok().json(json!({
+        "repo_name": &create_repo_response.repo_name,
+        "repo_url": &new_repo.repo_url,
+        "id": &new_repo.id,
+        "forked_from": &repo.id,
+        "progress": new_progress,
+    })))
+}
+
+async fn rotate_repo_credentials(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let repo = get_owned_repo(&mut conn, &id, &user_id)?;
+    let repo_name = repo_name_from_url(&repo.soft_serve_url).ok_or_else(|| {
+        error!("Error parsing repository name: {}", repo.soft_serve_url);
+        RepositoryError::BadRequest("Error parsing repository url".to_string())
+    })?;
+
+    let rotate_response = git::rotate_credentials(&repo_name).await?;
+
+    // Only the token may change. Queue events are matched on
+    // `soft_serve_url`, so a url pointing anywhere else is reported as a
+    // fault of the git service and never stored.
+    if strip_url_credentials(&rotate_response.repo_url).as_deref()
+        != Some(repo.soft_serve_url.as_str())
+    {
+        error!(
+            "Rotated url for {} does not match soft serve url {}",
+            repo_name, repo.soft_serve_url
+        );
+        return Err(RepositoryError::GitServiceError(
+            "Git service returned an unexpected repository url".to_string(),
+        ));
+    }
+
+    let updated_repo = Repository::update_urls(
+        &mut conn,
+        &repo.id,
+        &rotate_response.repo_url,
+        &repo.soft_serve_url,
+    )
+    .map_err(|e| {
+        error!("Error updating repository in database: {:#?}", e);
+        RepositoryError::FailedToUpdateRepository(UpdateRepositoryError(
+            diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::Unknown,
+                Box::new(e.to_string()),
+            ),
+        ))
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "repo_name": &rotate_response.repo_name,
+        "repo_url": &updated_repo.repo_url,
+        "id": &updated_repo.id,
+    })))
+}
+
+async fn abandon_repo(
+    req: HttpRequest,
+    id: web::Path<Uuid>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let repo = get_owned_repo(&mut conn, &id, &user_id)?;
+    let challenge = Challenge::get_challenge(&mut conn, Some(&repo.challenge_id), None, None, None)
+        .map_err(|e| {
+            error!("Error getting challenge: {}", e);
+            RepositoryError::NotFound("Challenge not found".to_string())
+        })?;
+    let repo_name = repo_name_from_url(&repo.soft_serve_url).ok_or_else(|| {
+        error!("Error parsing repository name: {}", repo.soft_serve_url);
+        RepositoryError::BadRequest("Error parsing repository url".to_string())
+    })?;
+
+    // The remote goes first: if the database step fails afterwards, a retry
+    // finds the remote already gone and carries on with the cleanup.
+    match git::delete_repo(&repo_name).await {
+        Ok(_) => {}
+        Err(RepositoryError::NotFound(_)) => {
+            warn!(
+                "Repository {} was already missing on git service",
+                repo_name
+            );
+        }
+        Err(e) => return Err(e),
+    }
+
+    // progress is read under the lock so that a test result applied in the
+    // meantime is archived and accounted for
+    let archive = conn.transaction::<_, RepositoryError, _>(|conn| {
+        let progress = Progress::get_for_update(conn, &repo.id).map_err(|e| {
+            error!("Error getting progress: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+        let archive = abandon_progress(conn, &progress, &repo.language, challenge.module_count)
+            .map_err(|e| {
+                error!("Error archiving progress in database: {:#?}", e);
+                RepositoryError::FailedToDeleteProgress(DeleteProgressError(
+                    diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::Unknown,
+                        Box::new(e.to_string()),
+                    ),
+                ))
+            })?;
+        Submission::delete_by_repository(conn, &repo.id)
+            .and_then(|_| RepositoryPush::delete_by_repository(conn, &repo.id))
+            .and_then(|_| Repository::delete(conn, &repo.id))
+            .map_err(|e| {
+                error!("Error deleting repository in database: {:#?}", e);
+                RepositoryError::FailedToDeleteRepository(DeleteRepositoryError(
+                    diesel::result::Error::DatabaseError(
+                        diesel::result::DatabaseErrorKind::Unknown,
+                        Box::new(e.to_string()),
+                    ),
+                ))
+            })?;
+        Ok(archive)
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "message": "Repository deleted successfully",
+        "archived_progress": archive,
+    })))
+}
+
+/// Looks up a repository by id and makes sure it belongs to `user_id`.
+/// Repositories owned by someone else are reported as not found.
+fn get_owned_repo(
+    conn: &mut PgConnection,
+    id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Repository, RepositoryError> {
+    let repo = Repository::get_repo(conn, Some(id), None, None, None)
+        .ok()
+        .and_then(|repos| repos.into_iter().next())
+        .filter(|repo| &repo.user_id == user_id)
+        .ok_or_else(|| RepositoryError::NotFound("Repository not found".to_string()))?;
+    Ok(repo)
+}
+
+/// Like `get_owned_repo`, but also lets admins look at any repository.
+fn get_visible_repo(
+    conn: &mut PgConnection,
+    id: &Uuid,
+    user_id: &Uuid,
+) -> Result<Repository, RepositoryError> {
+    let repo = Repository::get_repo(conn, Some(id), None, None, None)
+        .ok()
+        .and_then(|repos| repos.into_iter().next())
+        .ok_or_else(|| RepositoryError::NotFound("Repository not found".to_string()))?;
+    if &repo.user_id == user_id {
+        return Ok(repo);
+    }
+
+    let user = User::get_user(conn, Some(user_id), None, None, None).map_err(|e| {
+        error!("Error getting user: {}", e);
+        RepositoryError::BadRequest("User not found".to_string())
+    })?;
+    if user.role != UserRole::Admin.to_str() {
+        return Err(RepositoryError::NotFound(
+            "Repository not found".to_string(),
+        ));
+    }
+    Ok(repo)
+}
+
+/// Reports drift between the git service and the database without
+/// repairing anything.
+async fn report_repo_drift(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    reconcile(req, RepairOptions::default(), pool).await
+}
+
+async fn reconcile_repos(
+    req: HttpRequest,
+    body: Option<web::Json<RepairOptions>>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let options = body.map(|body| body.into_inner()).unwrap_or_default();
+    reconcile(req, options, pool).await
+}
+
+async fn reconcile(
+    req: HttpRequest,
+    options: RepairOptions,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user = User::get_user(&mut conn, Some(&user_id), None, None, None).map_err(|e| {
+        error!("Error getting user: {}", e);
+        RepositoryError::BadRequest("User not found".to_string())
+    })?;
+
+    // Check if user is admin
+    if user.role != UserRole::Admin.to_str() {
+        return Ok(HttpResponse::Forbidden().json(json!({
+            "status": "error",
+            "message": "Forbidden. Only administrators can reconcile repositories."
+        })));
+    }
+    drop(conn);
+
+    let report = reconcile_repositories(&pool, &options).await.map_err(|e| {
+        error!("Error reconciling repositories: {:?}", e);
+        RepositoryError::BadRequest(format!("Error reconciling repositories: {}", e))
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "report": report
+    })))
+}
+
+async fn list_softserve_repos(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let extensions = req.extensions();
+    let session_info = extensions
+        .get::<SessionInfo>()
+        .ok_or_else(|| RepositoryError::BadRequest("User not authenticated".to_string()))?;
+
+    // Get user from database to check role
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user =
+        User::get_user(&mut conn, Some(&session_info.user_id), None, None, None).map_err(|e| {
+            error!("Error getting user: {}", e);
+            RepositoryError::BadRequest("User not found".to_string())
+        })?;
+
+    // Check if user is admin
+    if user.role != UserRole::Admin.to_str() {
+        return Ok(HttpResponse::Forbidden().json(json!({
+            "status": "error",
+            "message": "Forbidden. Only administrators can list all repositories."
+        })));
+    }
+
+    let repositories = git::list_repos().await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "status": "success",
+        "repositories": repositories
+    })))
+}
+
+async fn delete_softserve_repo(
+    req: HttpRequest,
+    body: web::Json<DeleteRepoRequest>,
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, RepositoryError> {
+    let extensions = req.extensions();
+    let session_info = extensions
+        .get::<SessionInfo>()
+        .ok_or_else(|| RepositoryError::BadRequest("User not authenticated".to_string()))?;
+
+    // Get user from database to check role
+    let mut conn = pool.get().map_err(|e| {
+        error!("Error getting db connection from pool: {}", e);
+        RepositoryError::DatabaseError(e.to_string())
+    })?;
+
+    let user =
+        User::get_user(&mut conn, Some(&session_info.user_id), None, None, None).map_err(|e| {
+            error!("Error getting user: {}", e);
+            RepositoryError::BadRequest("User not found".to_string())
+        })?;
+
+    // Check if user is admin
+    if user.role != UserRole::Admin.to_str() {
+        return Ok(HttpResponse::Forbidden().json(json!({
+            "status": "error",
+            "message": "Forbidden. Only administrators can delete repositories."
+        })));
+    }
+
+    let delete_response = git::delete_repo(&body.repo_name).await?;
+
     Ok(HttpResponse::Ok().json(json!({
         "status": "success",
         "repo_name": delete_response.repo_name,
@@ -0,0 +1,58 @@
+use crate::{
+    app::{auth::middleware::SessionInfo, runner::registry::runner_registry},
+    service::{
+        database::{conn::DbPool, models::User},
+        event_bus::{routing::JobRoutes, QueueInspector},
+    },
+    shared::{errors::RepositoryError, primitives::UserRole},
+};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, Scope};
+use log::error;
+use serde_json::json;
+
+pub fn init() -> Scope {
+    web::scope("/runners").route("", web::get().to(get_runners))
+}
+
+/// Test runners with their health, and the jobs queues with their depth,
+/// routing rules and the capacity of the healthy runners consuming them.
+async fn get_runners(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    job_routes: web::Data<JobRoutes>,
+    inspector: web::Data<QueueInspector>,
+) -> Result<HttpResponse, RepositoryError> {
+    let user_id = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => session_info.user_id,
+        None => {
+            return Err(RepositoryError::BadRequest(
+                "User not authenticated".to_string(),
+            ));
+        }
+    };
+
+    {
+        let mut conn = pool.get().map_err(|e| {
+            error!("Error getting db connection from pool: {}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+        let user = User::get_user(&mut conn, Some(&user_id), None, None, None).map_err(|e| {
+            error!("Error getting user: {}", e);
+            RepositoryError::BadRequest("User not found".to_string())
+        })?;
+        if user.role != UserRole::Admin.to_str() {
+            return Ok(HttpResponse::Forbidden().json(json!({
+                "status": "error",
+                "message": "Forbidden. Only administrators can view test runners."
+            })));
+        }
+    }
+
+    let registry = runner_registry(&pool, &job_routes, &inspector)
+        .await
+        .map_err(|e| {
+            error!("Error getting runner registry: {:?}", e);
+            RepositoryError::DatabaseError(e.to_string())
+        })?;
+    Ok(HttpResponse::Ok().json(registry))
+}
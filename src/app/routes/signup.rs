@@ -1,7 +1,11 @@
 use crate::{
-    service::database::{
-        conn::DbPool,
-        models::{Leaderboard, Session, User},
+    service::{
+        database::{
+            conn::DbPool,
+            models::{Leaderboard, Session, User},
+        },
+        event_publisher::EventPublisherHandle,
+        events::{DomainEvent, UserSignedUp},
     },
     shared::{
         errors::{CreateLeaderboardError, CreateUserError, RepositoryError},
@@ -33,6 +37,7 @@ pub fn init() -> Scope {
 async fn signup(
     user: Result<web::Json<NewUser>, actix_web::Error>,
     pool: web::Data<DbPool>,
+    events: web::Data<EventPublisherHandle>,
 ) -> Result<HttpResponse, RepositoryError> {
     let user = match user {
         Ok(user) => {
@@ -93,15 +98,27 @@ async fn signup(
             Session::create(conn, session)
                 .map_err(|e| RepositoryError::BadRequest(e.to_string()))?;
 
-            Ok(HttpResponse::Ok().json(json!({
-                "user_id": user_id,
-                "session_token": token
-            })))
+            Ok((
+                created_user,
+                HttpResponse::Ok().json(json!({
+                    "user_id": user_id,
+                    "session_token": token
+                })),
+            ))
         },
     );
 
     match result {
-        Ok(response) => Ok(response),
+        Ok((created_user, response)) => {
+            events.publish(DomainEvent::UserSignedUp(UserSignedUp {
+                user_id: created_user.id,
+                username: created_user.username,
+                github_username: created_user.github_username,
+                email: created_user.email,
+                role: created_user.role,
+            }));
+            Ok(response)
+        }
         Err(e) => Err(e),
     }
 }
@@ -0,0 +1,140 @@
+use crate::service::{
+    database::{conn::DbPool, models::TestRunner},
+    event_bus::{
+        routing::{JobRoute, JobRoutes},
+        QueueInspector,
+    },
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+const DEFAULT_RUNNER_HEARTBEAT_TIMEOUT_SECS: i64 = 60;
+
+/// What a test runner reports about itself on every heartbeat.
+pub struct Heartbeat<'a> {
+    pub runner_id: &'a str,
+    pub queue: &'a str,
+    pub languages: &'a [String],
+    pub capacity: i32,
+    pub busy: i32,
+    pub version: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunnerStatus {
+    #[serde(flatten)]
+    pub runner: TestRunner,
+    pub healthy: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueueStatus {
+    pub queue: String,
+    pub messages: Option<u32>,
+    pub consumers: Option<u32>,
+    /// Set for the queue jobs go to when no rule matches.
+    pub default: bool,
+    pub routes: Vec<JobRoute>,
+    pub runners: usize,
+    pub healthy_runners: usize,
+    /// Jobs the healthy runners can take at once, and how many they run.
+    pub capacity: i32,
+    pub busy: i32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunnerRegistry {
+    pub heartbeat_timeout_secs: i64,
+    pub runners: Vec<RunnerStatus>,
+    pub queues: Vec<QueueStatus>,
+}
+
+fn heartbeat_timeout_secs() -> i64 {
+    std::env::var("RUNNER_HEARTBEAT_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<i64>().ok())
+        .unwrap_or(DEFAULT_RUNNER_HEARTBEAT_TIMEOUT_SECS)
+}
+
+pub async fn record_heartbeat(pool: &DbPool, heartbeat: &Heartbeat<'_>) -> Result<TestRunner> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let runner = TestRunner::new(
+        heartbeat.runner_id,
+        heartbeat.queue,
+        heartbeat.languages,
+        heartbeat.capacity,
+        heartbeat.busy,
+        heartbeat.version,
+    );
+    TestRunner::record_heartbeat(&mut conn, runner).context(format!(
+        "Failed to record heartbeat of runner {}",
+        heartbeat.runner_id
+    ))
+}
+
+/// Every runner that sent a heartbeat, healthy when the last one is within
+/// `RUNNER_HEARTBEAT_TIMEOUT_SECS`, together with the queues jobs are routed
+/// to and the queues runners consume that no rule routes to.
+pub async fn runner_registry(
+    pool: &DbPool,
+    job_routes: &JobRoutes,
+    inspector: &QueueInspector,
+) -> Result<RunnerRegistry> {
+    let runners = {
+        let mut conn = pool.get().context("Failed to get connection from pool")?;
+        TestRunner::get_all(&mut conn).context("Failed to get test runners")?
+    };
+    let timeout_secs = heartbeat_timeout_secs();
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(timeout_secs);
+    let runners: Vec<RunnerStatus> = runners
+        .into_iter()
+        .map(|runner| RunnerStatus {
+            healthy: runner.last_heartbeat_at >= cutoff,
+            runner,
+        })
+        .collect();
+
+    let mut queue_names = job_routes.queues();
+    for status in &runners {
+        if !queue_names.contains(&status.runner.queue.as_str()) {
+            queue_names.push(&status.runner.queue);
+        }
+    }
+    let depths = inspector.queue_depths(&queue_names).await;
+
+    let queues = depths
+        .into_iter()
+        .map(|depth| {
+            let queue_runners: Vec<&RunnerStatus> = runners
+                .iter()
+                .filter(|status| status.runner.queue == depth.queue)
+                .collect();
+            let healthy: Vec<&&RunnerStatus> = queue_runners
+                .iter()
+                .filter(|status| status.healthy)
+                .collect();
+            QueueStatus {
+                default: depth.queue == job_routes.default_queue,
+                routes: job_routes
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.queue == depth.queue)
+                    .cloned()
+                    .collect(),
+                runners: queue_runners.len(),
+                healthy_runners: healthy.len(),
+                capacity: healthy.iter().map(|status| status.runner.capacity).sum(),
+                busy: healthy.iter().map(|status| status.runner.busy).sum(),
+                queue: depth.queue,
+                messages: depth.messages,
+                consumers: depth.consumers,
+            }
+        })
+        .collect();
+
+    Ok(RunnerRegistry {
+        heartbeat_timeout_secs: timeout_secs,
+        runners,
+        queues,
+    })
+}
@@ -0,0 +1,5 @@
+pub mod process_test_result;
+pub mod request_test_run;
+pub mod test_report;
+pub mod track_test_run;
+pub mod test_output;
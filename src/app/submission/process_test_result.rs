@@ -0,0 +1,234 @@
+use crate::app::progress::update_progress::update_progress;
+use crate::app::submission::test_report::TestCaseResult;
+use crate::service::database::{
+    conn::DbPool,
+    models::{Exercise, Progress, Repository, Submission, SubmissionTestCase},
+};
+use crate::service::queue::invalid_message;
+use crate::shared::primitives::SubmissionStatus;
+use anyhow::{Context, Result};
+use diesel::{Connection, PgConnection};
+use log::warn;
+use uuid::Uuid;
+
+/// A test run as reported by the test runner.
+pub struct TestResult<'a> {
+    pub commit_sha: &'a str,
+    pub success: bool,
+    pub output: &'a str,
+    /// Step the runner evaluated, counted from 1 like `current_step`.
+    pub stage: Option<i64>,
+    /// Exercise the runner evaluated, takes precedence over `stage`.
+    pub exercise_id: Option<Uuid>,
+    /// Set for runs requested through core, see `TestRunJob`.
+    pub test_run_id: Option<Uuid>,
+    /// Parsed from the structured results of the run, if it sent any.
+    pub test_cases: &'a [TestCaseResult],
+}
+
+impl TestResult<'_> {
+    /// Identifies the delivery by what the runner reported, which stays the
+    /// same however often the message is delivered: the test run, or else
+    /// the exercise or stage it evaluated.
+    pub fn delivery_key(&self) -> String {
+        match (self.test_run_id, self.exercise_id, self.stage) {
+            (Some(test_run_id), _, _) => format!("run:{}", test_run_id),
+            (None, Some(exercise_id), _) => format!("exercise:{}", exercise_id),
+            (None, None, Some(stage)) => format!("stage:{}", stage),
+            (None, None, None) => "commit".to_string(),
+        }
+    }
+}
+
+pub struct ProcessedTestResult {
+    pub progress: Progress,
+    /// A passing result was already applied for this commit and delivery
+    /// key, e.g. because the queue message was delivered again.
+    pub duplicate: bool,
+    /// Set when the result doesn't cover the step the attempt is on. Such a
+    /// result is stored but leaves progress alone.
+    pub flagged_reason: Option<String>,
+    /// Step this result completed, if it moved the attempt forward.
+    pub completed_step: Option<i64>,
+    /// The submission stored for the result, `None` for a duplicate.
+    pub submission_id: Option<Uuid>,
+}
+
+/// Stores the outcome of a test run as a submission against the step it
+/// evaluated and, if it passed the step the attempt is on, moves the attempt
+/// forward.
+/// Both happen in one transaction keyed by repository, commit and
+/// `delivery_key`, so processing the same passing result twice leaves
+/// progress and leaderboard as they were after the first time.
+pub async fn process_test_result(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    result: &TestResult<'_>,
+) -> Result<ProcessedTestResult> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = Repository::get_repo(&mut conn, None, None, None, Some(soft_serve_url)).context(
+        format!("Failed to find repository with URL: {}", soft_serve_url),
+    )?;
+    let repo = repo.first().ok_or_else(|| {
+        invalid_message(format!("Repository not found with URL: {}", soft_serve_url))
+    })?;
+
+    conn.transaction(|conn| {
+        let progress = Progress::get_for_update(conn, &repo.id).context(format!(
+            "Failed to find progress for repository {}",
+            repo.id
+        ))?;
+        let current_step = progress
+            .progress_details
+            .as_ref()
+            .and_then(|details| details["current_step"].as_i64())
+            .unwrap_or(1);
+
+        let (step, exercise_id, flagged_reason) = evaluated_step(conn, repo, result, current_step)?;
+
+        let status = match result.success {
+            true => SubmissionStatus::Passed,
+            false => SubmissionStatus::Failed,
+        };
+        let mut submission = Submission::new(
+            exercise_id.as_ref(),
+            &repo.user_id,
+            status,
+            &repo.id,
+            result.commit_sha,
+            step.map(|step| step as i32),
+            Some(result.output),
+        );
+        submission.flagged_reason = flagged_reason.clone();
+        submission.test_run_id = result.test_run_id;
+        submission.delivery_key = Some(result.delivery_key());
+        let created = Submission::create_if_absent(conn, submission).context(format!(
+            "Failed to create submission for repository {}",
+            repo.id
+        ))?;
+        let created = match created {
+            Some(created) => created,
+            None => {
+                return Ok(ProcessedTestResult {
+                    progress,
+                    duplicate: true,
+                    flagged_reason,
+                    completed_step: None,
+                    submission_id: None,
+                });
+            }
+        };
+        if !result.test_cases.is_empty() {
+            let test_cases = result
+                .test_cases
+                .iter()
+                .enumerate()
+                .map(|(position, test_case)| {
+                    SubmissionTestCase::new(
+                        &created.id,
+                        position as i32,
+                        &test_case.name,
+                        test_case.suite.as_deref(),
+                        test_case.status,
+                        test_case.duration_ms,
+                        test_case.failure_message.as_deref(),
+                    )
+                })
+                .collect();
+            SubmissionTestCase::create_many(conn, test_cases).context(format!(
+                "Failed to store test cases of submission {}",
+                created.id
+            ))?;
+        }
+
+        if !result.success || flagged_reason.is_some() {
+            return Ok(ProcessedTestResult {
+                progress,
+                duplicate: false,
+                flagged_reason,
+                completed_step: None,
+                submission_id: Some(created.id),
+            });
+        }
+        let updated_progress = update_progress(conn, repo, &progress)?;
+        // completed or forked attempts are handed back unchanged
+        let completed_step = match updated_progress.progress_details == progress.progress_details {
+            true => None,
+            false => Some(current_step),
+        };
+        Ok(ProcessedTestResult {
+            progress: updated_progress,
+            duplicate: false,
+            flagged_reason,
+            completed_step,
+            submission_id: Some(created.id),
+        })
+    })
+}
+
+/// Works out which step a result belongs to and whether that is the step the
+/// attempt is on. Returns the step, the exercise and the reason the result
+/// is flagged, if any.
+fn evaluated_step(
+    conn: &mut PgConnection,
+    repo: &Repository,
+    result: &TestResult<'_>,
+    current_step: i64,
+) -> Result<(Option<i64>, Option<Uuid>, Option<String>)> {
+    let step = match (result.exercise_id, result.stage) {
+        (Some(exercise_id), stage) => {
+            match Exercise::get_step(conn, &repo.challenge_id, &exercise_id)
+                .context(format!("Failed to find step of exercise {}", exercise_id))?
+            {
+                Some(step) if stage.is_some_and(|stage| stage != step) => {
+                    return Ok((
+                        Some(step),
+                        Some(exercise_id),
+                        Some(format!(
+                            "Stage {} does not match exercise {}",
+                            stage.unwrap_or_default(),
+                            exercise_id
+                        )),
+                    ));
+                }
+                Some(step) => step,
+                None => {
+                    return Ok((
+                        None,
+                        None,
+                        Some(format!(
+                            "Exercise {} is not part of this challenge",
+                            exercise_id
+                        )),
+                    ));
+                }
+            }
+        }
+        (None, Some(stage)) => stage,
+        (None, None) => {
+            // runners that predate stage reporting are trusted as before
+            warn!(
+                "Test result for {} does not say which stage it evaluated",
+                result.commit_sha
+            );
+            current_step
+        }
+    };
+
+    let exercise = Exercise::get_for_step(conn, &repo.challenge_id, step).context(format!(
+        "Failed to find exercise for step {} of {}",
+        step, repo.challenge_id
+    ))?;
+    let flagged_reason = match step == current_step {
+        true => None,
+        false => Some(format!(
+            "Result covers step {} but the attempt is on step {}",
+            step, current_step
+        )),
+    };
+    Ok((
+        Some(step),
+        exercise.map(|exercise| exercise.id),
+        flagged_reason,
+    ))
+}
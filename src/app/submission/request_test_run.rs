@@ -0,0 +1,55 @@
+use crate::service::{
+    database::models::{Progress, Repository, TestRun},
+    queue::TestRunJob,
+};
+use anyhow::{Context, Result};
+use diesel::PgConnection;
+use uuid::Uuid;
+
+/// Builds a job testing `repo` at `commit_sha`, or at its head when `None`,
+/// against the step its attempt is currently on.
+pub fn job_for_progress(
+    repo: &Repository,
+    progress: &Progress,
+    commit_sha: Option<String>,
+    requested_by: &Uuid,
+) -> TestRunJob {
+    let stage = progress
+        .progress_details
+        .as_ref()
+        .and_then(|details| details["current_step"].as_i64())
+        .unwrap_or(1);
+    TestRunJob::new(
+        &repo.soft_serve_url,
+        commit_sha,
+        &repo.language,
+        &repo.challenge_id,
+        stage,
+        requested_by,
+    )
+}
+
+/// Tracks a job as a test run under the same id before it is queued, so
+/// that the runner events echoing `testRunId` find it however fast they
+/// come back. See `untrack_test_run` for a job that can't be queued.
+pub fn track_requested_test_run(
+    conn: &mut PgConnection,
+    repo: &Repository,
+    test_run_id: &Uuid,
+    commit_sha: Option<&str>,
+    requested_by: &Uuid,
+) -> Result<TestRun> {
+    let test_run = TestRun::new(test_run_id, &repo.id, commit_sha, Some(requested_by));
+    TestRun::create(conn, test_run).context(format!(
+        "Failed to track test run for repository {}",
+        repo.id
+    ))
+}
+
+/// Drops the run tracked for a job that could not be queued after all, so
+/// that it isn't left queued until it times out.
+pub fn untrack_test_run(conn: &mut PgConnection, test_run_id: &Uuid) -> Result<()> {
+    TestRun::delete(conn, test_run_id)
+        .context(format!("Failed to drop test run {}", test_run_id))?;
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use crate::{
+    app::{
+        submission::track_test_run::{find_test_run, get_repo_by_url},
+        websockets::manager::WebSocketManagerHandle,
+    },
+    service::{
+        database::{
+            conn::DbPool,
+            models::{TestRun, TestRunOutputChunk},
+        },
+        queue::publish_repo_event,
+    },
+    shared::primitives::TestRunStatus,
+};
+use anyhow::{Context, Result};
+use diesel::{Connection, PgConnection};
+use log::{info, warn};
+use serde_json::json;
+use uuid::Uuid;
+
+pub struct RecordedChunk {
+    pub test_run: TestRun,
+    /// Set when the chunk was the first sign of the run being picked up.
+    pub started: bool,
+    /// Chunks that can be relayed now, in order. Empty while an earlier
+    /// chunk is still missing.
+    pub relay: Vec<TestRunOutputChunk>,
+}
+
+pub struct AssembledOutput {
+    pub output: String,
+    pub test_run: TestRun,
+    /// Chunks that were held back by a gap and not relayed yet.
+    pub unrelayed: Vec<TestRunOutputChunk>,
+}
+
+/// Stores a chunk of output streamed by the test runner and works out which
+/// chunks can be relayed to the learner without breaking their order. A
+/// chunk received twice is relayed once. Returns `None` when the run is not
+/// tracked or already finished.
+pub async fn record_output_chunk(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    commit_sha: &str,
+    test_run_id: Option<Uuid>,
+    sequence: i32,
+    output: &str,
+) -> Result<Option<RecordedChunk>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = get_repo_by_url(&mut conn, soft_serve_url)?;
+
+    conn.transaction(|conn| {
+        let test_run = match find_test_run(conn, &repo, commit_sha, test_run_id)? {
+            Some(test_run) if test_run.status != TestRunStatus::Finished.to_str() => test_run,
+            _ => return Ok(None),
+        };
+        TestRunOutputChunk::create_if_absent(
+            conn,
+            TestRunOutputChunk::new(&test_run.id, sequence, output),
+        )
+        .context(format!(
+            "Failed to store output of test run {}",
+            test_run.id
+        ))?;
+
+        let test_run = TestRun::get_for_update(conn, &test_run.id)
+            .context(format!("Failed to lock test run {}", test_run.id))?;
+        // runners that don't report status are running once they stream
+        let started = TestRun::transition(conn, &test_run.id, TestRunStatus::Running, None, None)
+            .context(format!("Failed to start test run {}", test_run.id))?;
+        let relay = take_relayable(conn, &test_run)?;
+
+        Ok(Some(RecordedChunk {
+            test_run: started.clone().unwrap_or(test_run),
+            started: started.is_some(),
+            relay,
+        }))
+    })
+}
+
+/// Chunks that follow the last relayed one without a gap, marked as relayed.
+fn take_relayable(conn: &mut PgConnection, test_run: &TestRun) -> Result<Vec<TestRunOutputChunk>> {
+    let pending = TestRunOutputChunk::get_after(conn, &test_run.id, test_run.relayed_sequence)
+        .context(format!("Failed to get output of test run {}", test_run.id))?;
+    let relay: Vec<TestRunOutputChunk> = pending
+        .into_iter()
+        .enumerate()
+        .take_while(|(offset, chunk)| {
+            chunk.sequence == test_run.relayed_sequence + 1 + *offset as i32
+        })
+        .map(|(_, chunk)| chunk)
+        .collect();
+    if let Some(last) = relay.last() {
+        TestRun::set_relayed_sequence(conn, &test_run.id, last.sequence)
+            .context(format!("Failed to update test run {}", test_run.id))?;
+    }
+    Ok(relay)
+}
+
+/// Puts the output of a run back together from the chunks it streamed.
+/// Returns `None` when it streamed none, the output of the result is used
+/// then. Missing chunks are logged and left out, `expected_chunks` is the
+/// count the runner reported, if any.
+pub async fn assemble_output(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    commit_sha: &str,
+    test_run_id: Option<Uuid>,
+    expected_chunks: Option<i32>,
+) -> Result<Option<AssembledOutput>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = get_repo_by_url(&mut conn, soft_serve_url)?;
+
+    conn.transaction(|conn| {
+        let test_run = match find_test_run(conn, &repo, commit_sha, test_run_id)? {
+            Some(test_run) => test_run,
+            None => return Ok(None),
+        };
+        let test_run = TestRun::get_for_update(conn, &test_run.id)
+            .context(format!("Failed to lock test run {}", test_run.id))?;
+        let chunks = TestRunOutputChunk::get_after(conn, &test_run.id, 0)
+            .context(format!("Failed to get output of test run {}", test_run.id))?;
+        let last_sequence = match chunks.last() {
+            Some(last) => last.sequence,
+            None => return Ok(None),
+        };
+
+        let expected = expected_chunks.unwrap_or(last_sequence).max(last_sequence);
+        if chunks.len() < expected as usize {
+            warn!(
+                "Output of test run {} is missing {} of {} chunks",
+                test_run.id,
+                expected as usize - chunks.len(),
+                expected
+            );
+        }
+        let unrelayed: Vec<TestRunOutputChunk> = chunks
+            .iter()
+            .filter(|chunk| chunk.sequence > test_run.relayed_sequence)
+            .cloned()
+            .collect();
+        if !unrelayed.is_empty() {
+            TestRun::set_relayed_sequence(conn, &test_run.id, last_sequence)
+                .context(format!("Failed to update test run {}", test_run.id))?;
+        }
+
+        Ok(Some(AssembledOutput {
+            output: chunks.into_iter().map(|chunk| chunk.output).collect(),
+            test_run,
+            unrelayed,
+        }))
+    })
+}
+
+/// Publishes a chunk of output on the topic of the repository.
+pub async fn broadcast_output_chunk(
+    pool: &DbPool,
+    manager_handle: &WebSocketManagerHandle,
+    repo_url: &str,
+    test_run: &TestRun,
+    chunk: &TestRunOutputChunk,
+) {
+    let payload = json!({
+        "testRunId": test_run.id,
+        "repoUrl": repo_url,
+        "commitSha": test_run.commit_sha,
+        "sequence": chunk.sequence,
+        "output": chunk.output,
+    });
+    publish_repo_event(pool, manager_handle, repo_url, "test_output", payload).await;
+}
+
+/// Relays the chunks a run streamed that were not sent yet, e.g. because
+/// the result arrived before a gap was filled.
+pub async fn flush_output(
+    pool: &DbPool,
+    manager_handle: &WebSocketManagerHandle,
+    repo_url: &str,
+    test_run: &TestRun,
+    chunks: &[TestRunOutputChunk],
+) {
+    if chunks.is_empty() {
+        return;
+    }
+    info!(
+        "Relaying {} remaining output chunks of test run {}",
+        chunks.len(),
+        test_run.id
+    );
+    for chunk in chunks {
+        broadcast_output_chunk(pool, manager_handle, repo_url, test_run, chunk).await;
+    }
+}
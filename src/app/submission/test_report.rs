@@ -0,0 +1,264 @@
+use crate::shared::primitives::TestCaseStatus;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Reports with more test cases than this are cut off.
+pub const MAX_TEST_CASES: usize = 1000;
+/// Failure messages are cut off after this many characters.
+const MAX_FAILURE_MESSAGE_CHARS: usize = 4000;
+
+/// Formats the test runner can report structured results in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TestReportFormat {
+    Json,
+    Tap,
+    Junit,
+}
+
+impl TestReportFormat {
+    pub fn from_str(format: &str) -> Result<TestReportFormat, &'static str> {
+        match format {
+            "json" => Ok(TestReportFormat::Json),
+            "tap" => Ok(TestReportFormat::Tap),
+            "junit" => Ok(TestReportFormat::Junit),
+            _ => Err("Invalid test report format"),
+        }
+    }
+}
+
+/// A single test case as reported by the test runner.
+#[derive(Debug, Clone, Serialize)]
+pub struct TestCaseResult {
+    pub name: String,
+    pub suite: Option<String>,
+    pub status: TestCaseStatus,
+    #[serde(rename = "durationMs")]
+    pub duration_ms: Option<i32>,
+    #[serde(rename = "failureMessage")]
+    pub failure_message: Option<String>,
+}
+
+impl TestCaseResult {
+    fn new(
+        name: &str,
+        suite: Option<&str>,
+        status: TestCaseStatus,
+        duration_ms: Option<f64>,
+        failure_message: Option<&str>,
+    ) -> Self {
+        TestCaseResult {
+            name: name.trim().to_string(),
+            suite: suite
+                .map(str::trim)
+                .filter(|suite| !suite.is_empty())
+                .map(str::to_string),
+            status,
+            duration_ms: duration_ms.and_then(to_duration_ms),
+            failure_message: failure_message.and_then(to_failure_message),
+        }
+    }
+}
+
+fn to_duration_ms(duration_ms: f64) -> Option<i32> {
+    match duration_ms.is_finite() && duration_ms >= 0.0 {
+        true => Some(duration_ms.round().min(i32::MAX as f64) as i32),
+        false => None,
+    }
+}
+
+fn to_failure_message(message: &str) -> Option<String> {
+    match message.trim() {
+        "" => None,
+        message => Some(message.chars().take(MAX_FAILURE_MESSAGE_CHARS).collect()),
+    }
+}
+
+/// Parses the structured results of a test run. JSON reports are passed as
+/// they are, TAP and JUnit XML ones as a string.
+pub fn parse_test_report(format: TestReportFormat, report: &Value) -> Result<Vec<TestCaseResult>> {
+    let mut test_cases = match (format, report) {
+        (TestReportFormat::Json, Value::String(report)) => {
+            let report = serde_json::from_str(report).context("Invalid JSON test report")?;
+            parse_json(report)?
+        }
+        (TestReportFormat::Json, report) => parse_json(report.clone())?,
+        (TestReportFormat::Tap, Value::String(report)) => parse_tap(report),
+        (TestReportFormat::Junit, Value::String(report)) => parse_junit(report)?,
+        _ => return Err(anyhow!("TAP and JUnit test reports must be a string")),
+    };
+    test_cases.truncate(MAX_TEST_CASES);
+    Ok(test_cases)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonReport {
+    Tests(Vec<JsonTestCase>),
+    Wrapped { tests: Vec<JsonTestCase> },
+}
+
+#[derive(Deserialize)]
+struct JsonTestCase {
+    name: String,
+    #[serde(default, alias = "classname")]
+    suite: Option<String>,
+    status: String,
+    #[serde(default, alias = "durationMs")]
+    duration_ms: Option<f64>,
+    #[serde(default, alias = "failureMessage")]
+    message: Option<String>,
+}
+
+/// Accepts a list of test cases or an object holding them under `tests`.
+fn parse_json(report: Value) -> Result<Vec<TestCaseResult>> {
+    let tests = match serde_json::from_value(report).context("Invalid JSON test report")? {
+        JsonReport::Tests(tests) | JsonReport::Wrapped { tests } => tests,
+    };
+    tests
+        .iter()
+        .map(|test| {
+            let status = match test.status.to_lowercase().as_str() {
+                "pass" | "passed" | "ok" => TestCaseStatus::Passed,
+                "fail" | "failed" | "error" => TestCaseStatus::Failed,
+                "skip" | "skipped" | "pending" | "todo" => TestCaseStatus::Skipped,
+                status => return Err(anyhow!("Invalid status {} of test {}", status, test.name)),
+            };
+            Ok(TestCaseResult::new(
+                &test.name,
+                test.suite.as_deref(),
+                status,
+                test.duration_ms,
+                test.message.as_deref(),
+            ))
+        })
+        .collect()
+}
+
+/// Reads the top-level test points of a TAP stream. Points marked SKIP or
+/// TODO count as skipped, and the `message` and `duration_ms` of a YAML
+/// diagnostic block are picked up for the point before it.
+fn parse_tap(report: &str) -> Vec<TestCaseResult> {
+    let mut test_cases: Vec<TestCaseResult> = Vec::new();
+    let mut in_diagnostics = false;
+    let mut message: Option<String> = None;
+    let mut duration_ms: Option<f64> = None;
+
+    for line in report.lines() {
+        if in_diagnostics {
+            let trimmed = line.trim();
+            if trimmed == "..." {
+                in_diagnostics = false;
+                if let Some(last) = test_cases.last_mut() {
+                    last.duration_ms = duration_ms.and_then(to_duration_ms);
+                    if last.status == TestCaseStatus::Failed {
+                        last.failure_message = message.as_deref().and_then(to_failure_message);
+                    }
+                }
+            } else if let Some(value) = trimmed.strip_prefix("message:") {
+                message = Some(unquote_yaml(value));
+            } else if let Some(value) = trimmed.strip_prefix("duration_ms:") {
+                duration_ms = value.trim().parse().ok();
+            }
+            continue;
+        }
+        if line.trim() == "---" && !test_cases.is_empty() {
+            in_diagnostics = true;
+            message = None;
+            duration_ms = None;
+            continue;
+        }
+
+        // subtests are indented and summed up by a top-level point
+        let (passed, rest) = match line.strip_prefix("not ok") {
+            Some(rest) => (false, rest),
+            None => match line.strip_prefix("ok") {
+                Some(rest) => (true, rest),
+                None => continue,
+            },
+        };
+        if !rest.is_empty() && !rest.starts_with(' ') {
+            continue;
+        }
+        let rest = rest
+            .trim_start()
+            .trim_start_matches(|c: char| c.is_ascii_digit());
+        let rest = rest.trim_start().trim_start_matches('-').trim_start();
+        let (description, directive) = match rest.split_once(" # ") {
+            Some((description, directive)) => (description, Some(directive.to_uppercase())),
+            None => match rest.strip_prefix("# ") {
+                Some(directive) => ("", Some(directive.to_uppercase())),
+                None => (rest, None),
+            },
+        };
+        let skipped = directive.as_deref().is_some_and(|directive| {
+            directive.starts_with("SKIP") || directive.starts_with("TODO")
+        });
+        let status = match (skipped, passed) {
+            (true, _) => TestCaseStatus::Skipped,
+            (false, true) => TestCaseStatus::Passed,
+            (false, false) => TestCaseStatus::Failed,
+        };
+        let name = match description.trim() {
+            "" => format!("test {}", test_cases.len() + 1),
+            description => description.to_string(),
+        };
+        test_cases.push(TestCaseResult::new(&name, None, status, None, None));
+    }
+    test_cases
+}
+
+fn unquote_yaml(value: &str) -> String {
+    let value = value.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner.to_string();
+        }
+    }
+    value.to_string()
+}
+
+/// Reads every `testcase` of a JUnit XML report, whether it sits in a single
+/// `testsuite` or a `testsuites` document.
+fn parse_junit(report: &str) -> Result<Vec<TestCaseResult>> {
+    let document = roxmltree::Document::parse(report).context("Invalid JUnit test report")?;
+
+    let test_cases = document
+        .descendants()
+        .filter(|node| node.has_tag_name("testcase"))
+        .map(|node| {
+            let suite = node.attribute("classname").or_else(|| {
+                node.ancestors()
+                    .find(|ancestor| ancestor.has_tag_name("testsuite"))
+                    .and_then(|suite| suite.attribute("name"))
+            });
+            let duration_ms = node
+                .attribute("time")
+                .and_then(|time| time.trim().parse::<f64>().ok())
+                .map(|seconds| seconds * 1000.0);
+            let failure = node
+                .children()
+                .find(|child| child.has_tag_name("failure") || child.has_tag_name("error"));
+            let skipped = node.children().any(|child| child.has_tag_name("skipped"));
+            let (status, message) = match (failure, skipped) {
+                (Some(failure), _) => (
+                    TestCaseStatus::Failed,
+                    failure.attribute("message").or_else(|| failure.text()),
+                ),
+                (None, true) => (TestCaseStatus::Skipped, None),
+                (None, false) => (TestCaseStatus::Passed, None),
+            };
+            TestCaseResult::new(
+                node.attribute("name").unwrap_or_default(),
+                suite,
+                status,
+                duration_ms,
+                message,
+            )
+        })
+        .collect();
+    Ok(test_cases)
+}
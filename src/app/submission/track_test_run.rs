@@ -0,0 +1,187 @@
+use crate::{
+    app::websockets::manager::WebSocketManagerHandle,
+    service::{
+        database::{
+            conn::DbPool,
+            models::{Repository, TestRun},
+        },
+        queue::{invalid_message, publish_repo_event},
+    },
+    shared::primitives::TestRunStatus,
+};
+use anyhow::{Context, Result};
+use diesel::PgConnection;
+use log::{error, info, warn};
+use serde_json::json;
+use std::time::Duration;
+use uuid::Uuid;
+
+const DEFAULT_TEST_RUN_TIMEOUT_SECS: u64 = 600;
+const TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+pub fn get_repo_by_url(conn: &mut PgConnection, soft_serve_url: &str) -> Result<Repository> {
+    let repos = Repository::get_repo(conn, None, None, None, Some(soft_serve_url)).context(
+        format!("Failed to find repository with URL: {}", soft_serve_url),
+    )?;
+    repos.into_iter().next().ok_or_else(|| {
+        invalid_message(format!("Repository not found with URL: {}", soft_serve_url))
+    })
+}
+
+/// The run a runner event is about, by its id when the runner echoed one and
+/// otherwise the latest unfinished run of the commit.
+pub fn find_test_run(
+    conn: &mut PgConnection,
+    repo: &Repository,
+    commit_sha: &str,
+    test_run_id: Option<Uuid>,
+) -> Result<Option<TestRun>> {
+    match test_run_id {
+        Some(test_run_id) => Ok(TestRun::get(conn, &test_run_id)
+            .context(format!("Failed to get test run {}", test_run_id))?
+            .filter(|test_run| test_run.repository_id == repo.id)),
+        None => TestRun::get_unfinished(conn, &repo.id, commit_sha)
+            .context(format!("Failed to get test run of commit {}", commit_sha)),
+    }
+}
+
+/// Queues a run for a commit that was just pushed, the test runner picks
+/// the push up on its own. A push delivered again gets the run queued the
+/// first time, unless that one timed out already.
+pub async fn queue_test_run_for_push(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    commit_sha: &str,
+) -> Result<TestRun> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = get_repo_by_url(&mut conn, soft_serve_url)?;
+
+    if let Some(test_run) = TestRun::get_pending(&mut conn, &repo.id, commit_sha)
+        .context(format!("Failed to get test run of commit {}", commit_sha))?
+    {
+        return Ok(test_run);
+    }
+    let test_run = TestRun::new(&Uuid::new_v4(), &repo.id, Some(commit_sha), None);
+    TestRun::create(&mut conn, test_run).context(format!(
+        "Failed to queue test run for repository {}",
+        repo.id
+    ))
+}
+
+/// Marks a run as picked up by the test runner. Returns `None` when there is
+/// no such run or it already moved on.
+pub async fn start_test_run(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    commit_sha: &str,
+    test_run_id: Option<Uuid>,
+) -> Result<Option<TestRun>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = get_repo_by_url(&mut conn, soft_serve_url)?;
+
+    let test_run = match find_test_run(&mut conn, &repo, commit_sha, test_run_id)? {
+        Some(test_run) => test_run,
+        None => return Ok(None),
+    };
+    TestRun::transition(&mut conn, &test_run.id, TestRunStatus::Running, None, None)
+        .context(format!("Failed to start test run {}", test_run.id))
+}
+
+/// Records the result of a run. A run that timed out is finished all the
+/// same when its result turns up late.
+pub async fn finish_test_run(
+    pool: &DbPool,
+    soft_serve_url: &str,
+    commit_sha: &str,
+    test_run_id: Option<Uuid>,
+    success: bool,
+    submission_id: Option<Uuid>,
+) -> Result<Option<TestRun>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let repo = get_repo_by_url(&mut conn, soft_serve_url)?;
+
+    let test_run = match find_test_run(&mut conn, &repo, commit_sha, test_run_id)? {
+        Some(test_run) => test_run,
+        None => return Ok(None),
+    };
+    TestRun::transition(
+        &mut conn,
+        &test_run.id,
+        TestRunStatus::Finished,
+        Some(success),
+        submission_id,
+    )
+    .context(format!("Failed to finish test run {}", test_run.id))
+}
+
+/// Publishes the current state of a run on the topic of its repository.
+pub async fn broadcast_test_run(
+    pool: &DbPool,
+    manager_handle: &WebSocketManagerHandle,
+    repo_url: &str,
+    test_run: &TestRun,
+) {
+    let payload = json!({
+        "testRunId": test_run.id,
+        "repoUrl": repo_url,
+        "commitSha": test_run.commit_sha,
+        "status": test_run.status,
+        "success": test_run.success,
+        "submissionId": test_run.submission_id,
+        "queuedAt": test_run.queued_at,
+        "startedAt": test_run.started_at,
+        "finishedAt": test_run.finished_at,
+    });
+    publish_repo_event(pool, manager_handle, repo_url, "test_run", payload).await;
+}
+
+/// Periodically times out runs that got no result and showed no activity
+/// for `TEST_RUN_TIMEOUT_SECS` and tells their owners.
+pub async fn run_test_run_timeouts(pool: DbPool, manager_handle: WebSocketManagerHandle) {
+    let timeout_secs = std::env::var("TEST_RUN_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_TEST_RUN_TIMEOUT_SECS);
+    if timeout_secs == 0 {
+        info!("Test run timeouts are disabled");
+        return;
+    }
+
+    let mut interval = tokio::time::interval(TIMEOUT_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let timed_out = match time_out_test_runs(&pool, timeout_secs) {
+            Ok(timed_out) => timed_out,
+            Err(e) => {
+                error!("Failed to time out test runs: {:?}", e);
+                continue;
+            }
+        };
+        for (test_run, repo_url) in timed_out {
+            warn!(
+                "Test run {} showed no activity for {}s",
+                test_run.id, timeout_secs
+            );
+            broadcast_test_run(&pool, &manager_handle, &repo_url, &test_run).await;
+        }
+    }
+}
+
+fn time_out_test_runs(pool: &DbPool, timeout_secs: u64) -> Result<Vec<(TestRun, String)>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(timeout_secs as i64);
+    let test_runs = TestRun::time_out_stale(&mut conn, cutoff)?;
+
+    let mut timed_out = Vec::new();
+    for test_run in test_runs {
+        let repo = Repository::get_repo(&mut conn, Some(&test_run.repository_id), None, None, None)
+            .context(format!(
+                "Failed to find repository {}",
+                test_run.repository_id
+            ))?;
+        if let Some(repo) = repo.into_iter().next() {
+            timed_out.push((test_run, repo.soft_serve_url));
+        }
+    }
+    Ok(timed_out)
+}
@@ -0,0 +1,108 @@
+use crate::{
+    app::websockets::protocol::{ServerMessage, Topic},
+    service::database::{
+        conn::DbPool,
+        models::{NewWebsocketEvent, WebsocketEvent},
+    },
+};
+use anyhow::{Context, Result};
+use diesel::PgConnection;
+use log::{error, info};
+use serde_json::Value;
+use std::time::Duration;
+use uuid::Uuid;
+
+const DEFAULT_EVENT_RETENTION_SECS: u64 = 86400;
+const RETENTION_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+const REPLAY_BATCH_SIZE: i64 = 500;
+
+/// How long events are kept for reconnecting clients,
+/// `WEBSOCKET_EVENT_RETENTION_SECS`.
+fn retention_secs() -> u64 {
+    std::env::var("WEBSOCKET_EVENT_RETENTION_SECS")
+        .ok()
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_EVENT_RETENTION_SECS)
+}
+
+/// Stores an event before it is published so that a client that is not
+/// connected can be sent it later. Events about a repository belong to its
+/// owner, shared ones such as announcements to nobody. If it can't be
+/// stored the event is still published, without an id.
+pub fn record_event(
+    conn: &mut PgConnection,
+    user_id: Option<&Uuid>,
+    topic: &Topic,
+    message_type: &str,
+    payload: Value,
+) -> ServerMessage {
+    let event = NewWebsocketEvent::new(user_id, &topic.to_string(), message_type, payload.clone());
+    match WebsocketEvent::create(conn, event) {
+        Ok(event) => ServerMessage::from_event(&event),
+        Err(e) => {
+            error!(
+                "Failed to store {} event on {}: {:?}",
+                message_type, topic, e
+            );
+            ServerMessage::new(message_type, Some(topic), payload)
+        }
+    }
+}
+
+/// The retained events after `last_event_id` a connection would have been
+/// sent, oldest first: those of the user and the shared ones on `topics`.
+pub fn missed_events(
+    pool: &DbPool,
+    user_id: &Uuid,
+    topics: &[Topic],
+    last_event_id: i64,
+) -> Result<Vec<ServerMessage>> {
+    let mut conn = pool.get().context("Failed to get connection from pool")?;
+    let since = chrono::Utc::now().naive_utc() - chrono::Duration::seconds(retention_secs() as i64);
+    let shared_topics: Vec<String> = topics
+        .iter()
+        .filter(|topic| !matches!(topic, Topic::Repo(_)))
+        .map(|topic| topic.to_string())
+        .collect();
+
+    let mut missed = Vec::new();
+    let mut after = last_event_id;
+    loop {
+        let events = WebsocketEvent::get_missed(
+            &mut conn,
+            user_id,
+            &shared_topics,
+            after,
+            since,
+            REPLAY_BATCH_SIZE,
+        )?;
+        let done = (events.len() as i64) < REPLAY_BATCH_SIZE;
+        if let Some(last) = events.last() {
+            after = last.id;
+        }
+        missed.extend(events.iter().map(ServerMessage::from_event));
+        if done {
+            return Ok(missed);
+        }
+    }
+}
+
+/// Periodically deletes the events older than the retention window.
+pub async fn run_event_retention(pool: DbPool) {
+    let retention_secs = retention_secs();
+    let mut interval = tokio::time::interval(RETENTION_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let cutoff =
+            chrono::Utc::now().naive_utc() - chrono::Duration::seconds(retention_secs as i64);
+        let deleted = pool
+            .get()
+            .context("Failed to get connection from pool")
+            .and_then(|mut conn| WebsocketEvent::delete_before(&mut conn, cutoff));
+        match deleted {
+            Ok(0) => {}
+            Ok(deleted) => info!("Deleted {} expired websocket events", deleted),
+            Err(e) => error!("Failed to delete expired websocket events: {:?}", e),
+        }
+    }
+}
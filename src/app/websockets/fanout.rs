@@ -0,0 +1,268 @@
+use crate::{
+    app::websockets::{
+        manager::WebSocketManagerHandle,
+        protocol::{ServerMessage, Topic},
+    },
+    service::database::{conn::DbPool, models::WebsocketEvent},
+};
+use anyhow::{Context, Result};
+use diesel::{sql_types::Text, RunQueryDsl};
+use futures_util::StreamExt;
+use log::{error, info, warn};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, time::Duration};
+use tokio::sync::mpsc;
+use tokio_postgres::{AsyncMessage, Config};
+use uuid::Uuid;
+
+const DEFAULT_FANOUT_CHANNEL: &str = "hxckr_websocket";
+const LISTENER_RETRY_DELAY: Duration = Duration::from_secs(5);
+// Postgres rejects notification payloads of 8000 bytes or more
+const MAX_NOTIFICATION_SIZE: usize = 7999;
+
+/// How events reach the websocket connections, which may be spread over
+/// several instances of core.
+#[derive(Clone)]
+pub enum WebsocketFanout {
+    /// Events only reach the connections of this instance.
+    InProcess,
+    /// Events are sent through Postgres `NOTIFY` and every instance listening
+    /// delivers them to its own connections.
+    Postgres(PostgresFanout),
+}
+
+#[derive(Clone)]
+pub struct PostgresFanout {
+    pool: DbPool,
+    database_url: String,
+    tls: MakeTlsConnector,
+    channel: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum FanoutNotification {
+    /// A stored event, loaded by id since it may not fit a notification.
+    Event { id: i64 },
+    /// An event that could not be stored.
+    Message {
+        topic: String,
+        message: ServerMessage,
+    },
+    Subscribe {
+        #[serde(rename = "userId")]
+        user_id: Uuid,
+        topic: String,
+    },
+}
+
+impl WebsocketFanout {
+    /// Picks the fan-out from `WEBSOCKET_FANOUT` (`in_process` or
+    /// `postgres`), in-process by default. The Postgres one notifies on
+    /// `WEBSOCKET_FANOUT_CHANNEL` of the `DATABASE_URL` database, which
+    /// fails here if the listener can't connect with it.
+    pub fn from_env(pool: &DbPool) -> Result<Self> {
+        let kind = std::env::var("WEBSOCKET_FANOUT")
+            .map(|kind| kind.to_lowercase())
+            .unwrap_or_else(|_| "in_process".to_string());
+        match kind.as_str() {
+            "in_process" => Ok(WebsocketFanout::InProcess),
+            "postgres" => {
+                let database_url =
+                    std::env::var("DATABASE_URL").context("DATABASE_URL must be set")?;
+                // only sslmode disable, prefer and require are understood here
+                Config::from_str(&database_url)
+                    .context("DATABASE_URL can't be used by the websocket fan-out listener")?;
+                let channel = std::env::var("WEBSOCKET_FANOUT_CHANNEL")
+                    .unwrap_or_else(|_| DEFAULT_FANOUT_CHANNEL.to_string());
+                if channel.is_empty()
+                    || channel.len() > 63
+                    || !channel
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+                {
+                    return Err(anyhow::anyhow!(
+                        "Invalid WEBSOCKET_FANOUT_CHANNEL {}, expected up to 63 lowercase letters, digits or underscores",
+                        channel
+                    ));
+                }
+                Ok(WebsocketFanout::Postgres(PostgresFanout {
+                    pool: pool.clone(),
+                    database_url,
+                    tls: tls_connector()?,
+                    channel,
+                }))
+            }
+            other => Err(anyhow::anyhow!(
+                "Unknown WEBSOCKET_FANOUT {}, expected in_process or postgres",
+                other
+            )),
+        }
+    }
+}
+
+/// Connects with TLS when the `sslmode` of the URL asks for it. Like libpq
+/// for these modes, which the pool connects through, the certificate of the
+/// server is not verified.
+fn tls_connector() -> Result<MakeTlsConnector> {
+    let connector = TlsConnector::builder()
+        .danger_accept_invalid_certs(true)
+        .danger_accept_invalid_hostnames(true)
+        .build()
+        .context("Failed to set up TLS for the websocket fan-out listener")?;
+    Ok(MakeTlsConnector::new(connector))
+}
+
+impl PostgresFanout {
+    pub async fn notify_publish(&self, topic: &Topic, message: &ServerMessage) -> Result<()> {
+        let notification = match message.id {
+            Some(id) => FanoutNotification::Event { id },
+            None => FanoutNotification::Message {
+                topic: topic.to_string(),
+                message: message.clone(),
+            },
+        };
+        self.notify(&notification).await
+    }
+
+    pub async fn notify_subscribe(&self, user_id: &Uuid, topic: &Topic) -> Result<()> {
+        self.notify(&FanoutNotification::Subscribe {
+            user_id: *user_id,
+            topic: topic.to_string(),
+        })
+        .await
+    }
+
+    async fn notify(&self, notification: &FanoutNotification) -> Result<()> {
+        let payload = serde_json::to_string(notification)?;
+        if payload.len() > MAX_NOTIFICATION_SIZE {
+            return Err(anyhow::anyhow!(
+                "Notification of {} bytes is too large",
+                payload.len()
+            ));
+        }
+        let pool = self.pool.clone();
+        let channel = self.channel.clone();
+        // diesel blocks, so the notification is sent off the async workers
+        tokio::task::spawn_blocking(move || {
+            let mut conn = pool.get().context("Failed to get connection from pool")?;
+            diesel::sql_query("SELECT pg_notify($1, $2)")
+                .bind::<Text, _>(channel)
+                .bind::<Text, _>(payload)
+                .execute(&mut conn)
+                .context("Failed to send notification")?;
+            Ok(())
+        })
+        .await
+        .context("Failed to send notification")?
+    }
+}
+
+/// Delivers the events any instance publishes to the connections of this
+/// one. The listener reconnects after losing its connection; clients get
+/// the events published meanwhile when they reconnect with their last
+/// event id.
+pub async fn run_fanout_listener(fanout: PostgresFanout, manager_handle: WebSocketManagerHandle) {
+    loop {
+        if let Err(e) = listen(&fanout, &manager_handle).await {
+            error!("Websocket fan-out listener stopped: {:?}", e);
+        }
+        tokio::time::sleep(LISTENER_RETRY_DELAY).await;
+    }
+}
+
+async fn listen(fanout: &PostgresFanout, manager_handle: &WebSocketManagerHandle) -> Result<()> {
+    let (client, mut connection) =
+        tokio_postgres::connect(&fanout.database_url, fanout.tls.clone())
+            .await
+            .context("Failed to connect to database")?;
+
+    // the connection has to be polled for notifications to come in
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let driver = tokio::spawn(async move {
+        let mut messages = futures_util::stream::poll_fn(move |cx| connection.poll_message(cx));
+        while let Some(message) = messages.next().await {
+            match message {
+                Ok(AsyncMessage::Notification(notification)) => {
+                    if tx.send(notification.payload().to_string()).is_err() {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Websocket fan-out connection failed: {:?}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    client
+        .batch_execute(&format!("LISTEN \"{}\"", fanout.channel))
+        .await
+        .context("Failed to listen for notifications")?;
+    info!("Listening for websocket events on {}", fanout.channel);
+
+    while let Some(payload) = rx.recv().await {
+        deliver(&fanout.pool, manager_handle, &payload).await;
+    }
+    driver.abort();
+    Err(anyhow::anyhow!("Connection closed"))
+}
+
+async fn deliver(pool: &DbPool, manager_handle: &WebSocketManagerHandle, payload: &str) {
+    let notification: FanoutNotification = match serde_json::from_str(payload) {
+        Ok(notification) => notification,
+        Err(e) => {
+            warn!("Ignoring websocket fan-out notification {}: {}", payload, e);
+            return;
+        }
+    };
+
+    let delivered = match notification {
+        FanoutNotification::Event { id } => match load_event(pool, id).await {
+            Ok(Some((topic, message))) => manager_handle.publish_local(&topic, &message).await,
+            Ok(None) => return,
+            Err(e) => {
+                error!("Failed to load websocket event {}: {:?}", id, e);
+                return;
+            }
+        },
+        FanoutNotification::Message { topic, message } => match Topic::from_str(&topic) {
+            Ok(topic) => manager_handle.publish_local(&topic, &message).await,
+            Err(e) => {
+                warn!("{} {} in fan-out notification", e, topic);
+                return;
+            }
+        },
+        FanoutNotification::Subscribe { user_id, topic } => match Topic::from_str(&topic) {
+            Ok(topic) => manager_handle.subscribe_user_local(&user_id, &topic).await,
+            Err(e) => {
+                warn!("{} {} in fan-out notification", e, topic);
+                return;
+            }
+        },
+    };
+    if let Err(e) = delivered {
+        error!("Failed to deliver websocket fan-out notification: {:?}", e);
+    }
+}
+
+async fn load_event(pool: &DbPool, id: i64) -> Result<Option<(Topic, ServerMessage)>> {
+    let pool = pool.clone();
+    let event = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().context("Failed to get connection from pool")?;
+        WebsocketEvent::get(&mut conn, id)
+    })
+    .await
+    .context("Failed to load event")??;
+    let event = match event {
+        Some(event) => event,
+        // deleted by retention before it got here
+        None => return Ok(None),
+    };
+    let topic = Topic::from_str(&event.topic).map_err(anyhow::Error::msg)?;
+    Ok(Some((topic, ServerMessage::from_event(&event))))
+}
@@ -1,24 +1,50 @@
-use actix_web::{web, Error, HttpMessage, HttpRequest, HttpResponse};
+use actix_web::{error::ErrorBadRequest, web, Error, HttpMessage, HttpRequest, HttpResponse};
 use actix_ws::{Message, Session};
 use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
 use std::time::Duration;
 use tokio::time::{interval, Instant};
+use uuid::Uuid;
 
-use super::manager::{ConnId, SessionToken, WebSocketManagerHandle};
-use crate::app::auth::middleware::SessionInfo;
+use super::{
+    delivery::missed_events,
+    manager::{ConnId, WebSocketManagerHandle},
+    protocol::{
+        parse_client_message, ClientRequest, ErrorCode, ProtocolError, ServerMessage, Topic,
+    },
+};
+use crate::{
+    app::auth::middleware::SessionInfo,
+    service::database::{
+        conn::DbPool,
+        models::{Repository, User},
+    },
+    shared::primitives::UserRole,
+};
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
+#[derive(Debug, Deserialize)]
+struct ConnectParams {
+    /// Id of the last event the client got before it disconnected. Events
+    /// after it are sent first, in order.
+    last_event_id: Option<i64>,
+}
+
 pub async fn websocket_handler(
     req: HttpRequest,
     body: web::Payload,
     manager_handle: web::Data<WebSocketManagerHandle>,
+    pool: web::Data<DbPool>,
 ) -> Result<HttpResponse, Error> {
+    let params = web::Query::<ConnectParams>::from_query(req.query_string())
+        .map_err(|e| ErrorBadRequest(e.to_string()))?;
     let (response, session, mut msg_stream) = actix_ws::handle(&req, body)?;
 
-    let session_token = match req.extensions().get::<SessionInfo>() {
-        Some(session_info) => session_info.token.clone(),
+    let (session_token, user_id) = match req.extensions().get::<SessionInfo>() {
+        Some(session_info) => (session_info.token.clone(), session_info.user_id),
         None => {
             return Err(Error::from(std::io::Error::new(
                 std::io::ErrorKind::Other,
@@ -27,14 +53,49 @@ pub async fn websocket_handler(
         }
     };
 
+    // connections start subscribed to the user's own repositories so that
+    // pushes and test results reach them without a round trip
+    let mut topics = vec![Topic::Announcements];
+    if let Ok(mut conn) = pool.get() {
+        let repos =
+            Repository::get_repo(&mut conn, None, Some(&user_id), None, None).unwrap_or_default();
+        topics.extend(repos.iter().map(|repo| Topic::Repo(repo.id)));
+    }
+
     let conn_id = manager_handle
-        .connect(&session_token, &session)
+        .connect(
+            &session_token,
+            user_id,
+            &session,
+            topics.clone(),
+            params.last_event_id.is_some(),
+        )
         .await
         .map_err(Error::from)?;
 
     log::info!("WebSocket connected: Connection ID {:?}", conn_id);
 
+    let last_event_id = params.last_event_id;
     actix_web::rt::spawn(async move {
+        if let Some(last_event_id) = last_event_id {
+            let mut missed =
+                missed_events(&pool, &user_id, &topics, last_event_id).unwrap_or_else(|e| {
+                    log::error!("Failed to get missed events: {:?}", e);
+                    Vec::new()
+                });
+            let count = missed.len();
+            log::info!("Replaying {} events to connection {:?}", count, conn_id);
+            missed.push(ServerMessage::new(
+                "replayed",
+                None,
+                json!({ "count": count }),
+            ));
+            if manager_handle.finish_replay(conn_id, missed).await.is_err() {
+                manager_handle.disconnect(conn_id).await.ok();
+                return;
+            }
+        }
+
         let mut last_heartbeat = Instant::now();
         let mut interval = interval(HEARTBEAT_INTERVAL);
         let mut session = Some(session);
@@ -44,7 +105,7 @@ pub async fn websocket_handler(
                 Some(msg) = msg_stream.next() => {
                     match msg {
                         Ok(msg) => {
-                            if handle_message(msg, &mut session, &manager_handle, conn_id, &session_token).await.is_err() {
+                            if handle_message(msg, &mut session, &manager_handle, &pool, conn_id, user_id).await.is_err() {
                                 break;
                             }
                             last_heartbeat = Instant::now();
@@ -81,8 +142,9 @@ async fn handle_message(
     msg: Message,
     session: &mut Option<Session>,
     manager_handle: &WebSocketManagerHandle,
+    pool: &DbPool,
     conn_id: ConnId,
-    session_token: &SessionToken,
+    user_id: Uuid,
 ) -> Result<(), Error> {
     match msg {
         Message::Ping(bytes) => {
@@ -98,16 +160,32 @@ async fn handle_message(
         }
         Message::Text(text) => {
             log::info!("Text message received: {:?}", text);
-            let message = Message::Text(text);
-            manager_handle
-                .send_message_to_session(session_token, message, conn_id)
-                .await?;
+            let (request_id, request) = parse_client_message(&text);
+            let reply = match request {
+                Ok(request) => {
+                    handle_request(
+                        request,
+                        request_id.as_deref(),
+                        manager_handle,
+                        pool,
+                        conn_id,
+                        user_id,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            }
+            .unwrap_or_else(|e| ServerMessage::error(&e, request_id.as_deref()));
+            manager_handle.send_to_connection(conn_id, &reply).await?;
         }
         Message::Binary(bin) => {
             log::info!("Binary message received: {:?}", bin);
-            let message = Message::Binary(bin);
+            let error = ProtocolError::new(
+                ErrorCode::InvalidMessage,
+                "Binary messages are not supported",
+            );
             manager_handle
-                .send_message_to_session(session_token, message, conn_id)
+                .send_to_connection(conn_id, &ServerMessage::error(&error, None))
                 .await?;
         }
         Message::Close(reason) => {
@@ -124,3 +202,62 @@ async fn handle_message(
     }
     Ok(())
 }
+
+/// Applies a client request and builds the reply to it.
+async fn handle_request(
+    request: ClientRequest,
+    request_id: Option<&str>,
+    manager_handle: &WebSocketManagerHandle,
+    pool: &DbPool,
+    conn_id: ConnId,
+    user_id: Uuid,
+) -> Result<ServerMessage, ProtocolError> {
+    let reply = json!({ "requestId": request_id });
+    match request {
+        ClientRequest::Subscribe(topic) => {
+            authorize_topic(pool, &user_id, &topic)?;
+            manager_handle
+                .subscribe(conn_id, topic.clone())
+                .await
+                .map_err(internal_error)?;
+            Ok(ServerMessage::new("subscribed", Some(&topic), reply))
+        }
+        ClientRequest::Unsubscribe(topic) => {
+            manager_handle
+                .unsubscribe(conn_id, &topic)
+                .await
+                .map_err(internal_error)?;
+            Ok(ServerMessage::new("unsubscribed", Some(&topic), reply))
+        }
+        ClientRequest::Ping => Ok(ServerMessage::new("pong", None, reply)),
+    }
+}
+
+/// Repository topics are limited to their owner and administrators, the
+/// other topics are open to every user.
+fn authorize_topic(pool: &DbPool, user_id: &Uuid, topic: &Topic) -> Result<(), ProtocolError> {
+    let repo_id = match topic {
+        Topic::Repo(repo_id) => repo_id,
+        Topic::Leaderboard | Topic::Announcements => return Ok(()),
+    };
+    let mut conn = pool.get().map_err(internal_error)?;
+    let repo =
+        Repository::get_repo(&mut conn, Some(repo_id), None, None, None).map_err(internal_error)?;
+    if repo.first().is_some_and(|repo| repo.user_id == *user_id) {
+        return Ok(());
+    }
+    let user =
+        User::get_user(&mut conn, Some(user_id), None, None, None).map_err(internal_error)?;
+    if !repo.is_empty() && user.role == UserRole::Admin.to_str() {
+        return Ok(());
+    }
+    Err(ProtocolError::new(
+        ErrorCode::Forbidden,
+        format!("Not allowed to subscribe to {}", topic),
+    ))
+}
+
+fn internal_error(e: impl std::fmt::Debug) -> ProtocolError {
+    log::error!("Error handling websocket request: {:?}", e);
+    ProtocolError::new(ErrorCode::InternalError, "Internal error")
+}
@@ -1,265 +1,425 @@
-use actix_ws::{Message, Session};
-use std::{collections::HashMap, io, sync::Arc, time::Instant};
-use tokio::sync::{mpsc, RwLock};
+use actix_ws::{CloseCode, CloseReason, Session};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    mpsc::{self, error::TrySendError},
+    oneshot,
+};
 use uuid::Uuid;
 
-use crate::shared::utils::clone_websocket_message;
+use super::{
+    fanout::WebsocketFanout,
+    protocol::{ServerMessage, Topic},
+};
 
 pub type ConnId = Uuid;
 pub type SessionToken = String;
 
+const DEFAULT_QUEUE_CAPACITY: usize = 256;
+// how long a replay may wait for room in the queue of the connection
+const REPLAY_SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
 struct Connection {
     session_token: SessionToken,
+    user_id: Uuid,
+    subscriptions: HashSet<Topic>,
+    /// Set while the connection is sent the events it missed. Events
+    /// published meanwhile wait here, with their id, to be sent after those.
+    replay_buffer: Option<Vec<(Option<i64>, String)>>,
     last_heartbeat: Instant,
-    #[allow(dead_code)]
-    sender: mpsc::UnboundedSender<Message>,
-    session: Session,
+    /// Messages waiting for the writer task of the connection.
+    sender: mpsc::Sender<String>,
+    /// Makes the writer task close the session instead of draining the queue.
+    closer: oneshot::Sender<CloseReason>,
+}
+
+/// Queue depths of the connections of this instance.
+#[derive(Debug, Serialize)]
+pub struct WebSocketMetrics {
+    pub connections: usize,
+    pub queue_capacity: usize,
+    pub queued_messages: usize,
+    pub max_queue_depth: usize,
+    pub slow_consumer_disconnects: u64,
 }
 
 pub struct WebSocketManager {
-    connections: Arc<RwLock<HashMap<ConnId, Connection>>>,
-    sessions: Arc<RwLock<HashMap<SessionToken, Vec<ConnId>>>>,
+    connections: DashMap<ConnId, Connection>,
+    queue_capacity: usize,
+    slow_consumer_disconnects: AtomicU64,
 }
 
 impl WebSocketManager {
+    /// The queue of every connection holds `WEBSOCKET_QUEUE_CAPACITY`
+    /// messages. A client that lets it fill up is disconnected.
     pub fn new() -> Self {
+        let queue_capacity = std::env::var("WEBSOCKET_QUEUE_CAPACITY")
+            .ok()
+            .and_then(|capacity| capacity.parse::<usize>().ok())
+            .filter(|capacity| *capacity > 0)
+            .unwrap_or(DEFAULT_QUEUE_CAPACITY);
         WebSocketManager {
-            connections: Arc::new(RwLock::new(HashMap::new())),
-            sessions: Arc::new(RwLock::new(HashMap::new())),
+            connections: DashMap::new(),
+            queue_capacity,
+            slow_consumer_disconnects: AtomicU64::new(0),
         }
     }
 
     pub async fn connect(
         &self,
         session_token: &SessionToken,
+        user_id: Uuid,
         session: Session,
+        topics: Vec<Topic>,
+        replaying: bool,
     ) -> io::Result<ConnId> {
         let conn_id = ConnId::new_v4();
-        let (tx, mut rx) = mpsc::unbounded_channel();
+        let (tx, rx) = mpsc::channel(self.queue_capacity);
+        let (closer, close_rx) = oneshot::channel();
 
         let connection = Connection {
             session_token: session_token.clone(),
+            user_id,
+            subscriptions: topics.into_iter().collect(),
+            replay_buffer: replaying.then(Vec::new),
             last_heartbeat: Instant::now(),
             sender: tx,
-            session,
+            closer,
         };
+        self.connections.insert(conn_id, connection);
 
-        self.connections.write().await.insert(conn_id, connection);
-        self.sessions
-            .write()
-            .await
-            .entry(session_token.clone())
-            .or_default()
-            .push(conn_id);
-
-        tokio::spawn({
-            let connections = Arc::clone(&self.connections);
-            async move {
-                while let Some(msg) = rx.recv().await {
-                    log::info!("Received message {:?} from connection: {:?}", msg, conn_id);
-                    if let Err(e) =
-                        Self::send_message_to_connection(&connections, conn_id, msg).await
-                    {
-                        log::error!("Failed to send message: {:?}", e);
-                        break;
-                    }
-                }
-                connections.write().await.remove(&conn_id);
-            }
-        });
+        tokio::spawn(write_messages(conn_id, session, rx, close_rx));
 
         Ok(conn_id)
     }
 
     pub async fn disconnect(&self, conn_id: ConnId) -> io::Result<()> {
-        if let Some(conn) = self.connections.write().await.remove(&conn_id) {
-            let mut sessions = self.sessions.write().await;
-            if let Some(conn_ids) = sessions.get_mut(&conn.session_token) {
-                conn_ids.retain(|&id| id != conn_id);
-                if conn_ids.is_empty() {
-                    sessions.remove(&conn.session_token);
-                }
-            }
+        self.connections.remove(&conn_id);
+        Ok(())
+    }
+
+    pub async fn update_heartbeat(&self, conn_id: ConnId) -> io::Result<()> {
+        if let Some(mut conn) = self.connections.get_mut(&conn_id) {
+            conn.last_heartbeat = Instant::now();
         }
         Ok(())
     }
 
-    pub async fn send_message_to_session(
-        &self,
-        session_token: &SessionToken,
-        message: Message,
-        sender_conn_id: ConnId,
-    ) -> io::Result<()> {
-        let sessions = self.sessions.read().await;
-        let connections = self.connections.read().await;
+    pub async fn subscribe(&self, conn_id: ConnId, topic: Topic) -> io::Result<()> {
+        if let Some(mut conn) = self.connections.get_mut(&conn_id) {
+            conn.subscriptions.insert(topic);
+        }
+        Ok(())
+    }
 
-        if let Some(conn_ids) = sessions.get(session_token) {
-            for &conn_id in conn_ids {
-                if conn_id == sender_conn_id {
-                    continue;
-                }
-                log::info!(
-                    "sender_conn_id: {:?}, conn_id: {:?}",
-                    sender_conn_id,
-                    conn_id
-                );
-                if let Some(conn) = connections.get(&conn_id) {
-                    log::info!("Sending message {:?} to connection: {:?}", message, conn_id);
-                    let mut session = conn.session.clone();
-                    match message {
-                        Message::Text(ref text) => {
-                            session.text(text.clone()).await.map_err(|e| {
-                                io::Error::new(
-                                    io::ErrorKind::Other,
-                                    format!("Failed to send message: {:?}", e),
-                                )
-                            })?;
-                        }
-                        Message::Binary(ref binary) => {
-                            session.binary(binary.clone()).await.map_err(|e| {
-                                io::Error::new(
-                                    io::ErrorKind::Other,
-                                    format!("Failed to send message: {:?}", e),
-                                )
-                            })?;
-                        }
-                        _ => {
-                            log::error!("Received unsupported message type: {:?}", message);
-                        }
-                    }
-                }
-            }
+    pub async fn unsubscribe(&self, conn_id: ConnId, topic: &Topic) -> io::Result<()> {
+        if let Some(mut conn) = self.connections.get_mut(&conn_id) {
+            conn.subscriptions.remove(topic);
         }
         Ok(())
     }
 
-    pub async fn update_heartbeat(&self, conn_id: ConnId) -> io::Result<()> {
-        if let Some(conn) = self.connections.write().await.get_mut(&conn_id) {
-            conn.last_heartbeat = Instant::now();
+    /// Subscribes every open connection of a user, e.g. to a repository they
+    /// just created.
+    pub async fn subscribe_user(&self, user_id: &Uuid, topic: &Topic) -> io::Result<()> {
+        for mut conn in self.connections.iter_mut() {
+            if conn.user_id == *user_id {
+                conn.subscriptions.insert(topic.clone());
+            }
         }
         Ok(())
     }
 
-    async fn send_message_to_connection(
-        connections: &RwLock<HashMap<ConnId, Connection>>,
+    pub async fn send_to_connection(
+        &self,
         conn_id: ConnId,
-        message: Message,
+        message: &ServerMessage,
     ) -> io::Result<()> {
-        let mut connections = connections.write().await;
-        if let Some(conn) = connections.get_mut(&conn_id) {
-            match message {
-                Message::Text(text) => {
-                    conn.session.text(text).await.map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Failed to send message: {:?}", e),
-                        )
-                    })?;
-                }
-                Message::Binary(binary) => {
-                    conn.session.binary(binary).await.map_err(|e| {
-                        io::Error::new(
-                            io::ErrorKind::Other,
-                            format!("Failed to send message: {:?}", e),
-                        )
-                    })?;
-                }
-                _ => {
-                    log::error!("Received unsupported message type: {:?}", message);
+        let sent = match self.connections.get(&conn_id) {
+            Some(conn) => conn.sender.try_send(message.to_text()),
+            None => return Ok(()),
+        };
+        self.handle_send_result(conn_id, sent);
+        Ok(())
+    }
+
+    /// Queues a message for every connection subscribed to its topic. A
+    /// connection whose queue is full is disconnected, the others are not
+    /// held up by it.
+    pub async fn publish(&self, topic: &Topic, message: &ServerMessage) -> io::Result<()> {
+        let text = message.to_text();
+        let mut failed = Vec::new();
+        for mut conn in self.connections.iter_mut() {
+            if !conn.subscriptions.contains(topic) {
+                continue;
+            }
+            let queue_capacity = self.queue_capacity;
+            let sent = match conn.replay_buffer.as_mut() {
+                Some(buffer) if buffer.len() < queue_capacity => {
+                    buffer.push((message.id, text.clone()));
+                    Ok(())
                 }
+                Some(_) => Err(TrySendError::Full(text.clone())),
+                None => conn.sender.try_send(text.clone()),
+            };
+            if let Err(e) = sent {
+                failed.push((*conn.key(), e));
             }
         }
+        // handled once the shards are no longer borrowed
+        for (conn_id, e) in failed {
+            self.handle_send_result(conn_id, Err(e));
+        }
         Ok(())
     }
 
-    pub async fn broadcast_to_session(
+    /// Sends a connection the events it missed, then the ones published
+    /// while they were looked up that it did not get that way, and from then
+    /// on delivers events to it as they are published. The replay waits for
+    /// room in the queue, up to a limit.
+    pub async fn finish_replay(
         &self,
-        session_token: &SessionToken,
-        message: Message,
+        conn_id: ConnId,
+        missed: Vec<ServerMessage>,
     ) -> io::Result<()> {
-        let sessions = self.sessions.read().await;
-        let connections = self.connections.read().await;
-
-        if let Some(conn_ids) = sessions.get(session_token) {
-            for &conn_id in conn_ids {
-                if let Some(conn) = connections.get(&conn_id) {
-                    let cloned_message = clone_websocket_message(&message);
-                    let mut session = conn.session.clone();
-
-                    match cloned_message {
-                        Message::Text(text) => {
-                            session.text(text).await.map_err(|e| {
-                                io::Error::new(
-                                    io::ErrorKind::Other,
-                                    format!("Failed to send message: {:?}", e),
-                                )
-                            })?;
-                        }
-                        Message::Binary(binary) => {
-                            session.binary(binary).await.map_err(|e| {
-                                io::Error::new(
-                                    io::ErrorKind::Other,
-                                    format!("Failed to send message: {:?}", e),
-                                )
-                            })?;
-                        }
-                        _ => {
-                            log::error!("Received unsupported message type: {:?}", cloned_message);
-                        }
+        let sender = match self.connections.get(&conn_id) {
+            Some(conn) => conn.sender.clone(),
+            None => return Ok(()),
+        };
+
+        let mut last_id = None;
+        for message in missed {
+            last_id = message.id.or(last_id);
+            self.send_replayed(conn_id, &sender, message.to_text())
+                .await?;
+        }
+        loop {
+            let buffered = {
+                let mut conn = match self.connections.get_mut(&conn_id) {
+                    Some(conn) => conn,
+                    None => return Ok(()),
+                };
+                match conn.replay_buffer.as_mut() {
+                    Some(buffer) if !buffer.is_empty() => std::mem::take(buffer),
+                    _ => {
+                        conn.replay_buffer = None;
+                        return Ok(());
                     }
                 }
+            };
+            for (id, text) in buffered {
+                // already sent as a missed event; ids are committed in
+                // order, so the lookup saw every event up to the last one
+                if id.is_some() && id <= last_id {
+                    continue;
+                }
+                self.send_replayed(conn_id, &sender, text).await?;
             }
         }
+    }
+
+    async fn send_replayed(
+        &self,
+        conn_id: ConnId,
+        sender: &mpsc::Sender<String>,
+        text: String,
+    ) -> io::Result<()> {
+        if sender
+            .send_timeout(text, REPLAY_SEND_TIMEOUT)
+            .await
+            .is_err()
+        {
+            self.close_slow_consumer(conn_id);
+            return Err(io::Error::other(
+                "Connection did not keep up with the replay",
+            ));
+        }
         Ok(())
     }
+
+    fn handle_send_result(&self, conn_id: ConnId, sent: Result<(), TrySendError<String>>) {
+        match sent {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => self.close_slow_consumer(conn_id),
+            // the writer task stopped, the session is gone
+            Err(TrySendError::Closed(_)) => {
+                self.connections.remove(&conn_id);
+            }
+        }
+    }
+
+    fn close_slow_consumer(&self, conn_id: ConnId) {
+        if let Some((_, conn)) = self.connections.remove(&conn_id) {
+            log::warn!(
+                "Disconnecting slow websocket consumer: Connection ID {:?}, session {}",
+                conn_id,
+                conn.session_token
+            );
+            self.slow_consumer_disconnects
+                .fetch_add(1, Ordering::Relaxed);
+            conn.closer
+                .send(CloseReason {
+                    code: CloseCode::Policy,
+                    description: Some("Slow consumer".to_string()),
+                })
+                .ok();
+        }
+    }
+
+    pub fn metrics(&self) -> WebSocketMetrics {
+        let depths: Vec<usize> = self
+            .connections
+            .iter()
+            .map(|conn| conn.sender.max_capacity() - conn.sender.capacity())
+            .collect();
+        WebSocketMetrics {
+            connections: depths.len(),
+            queue_capacity: self.queue_capacity,
+            queued_messages: depths.iter().sum(),
+            max_queue_depth: depths.iter().copied().max().unwrap_or(0),
+            slow_consumer_disconnects: self.slow_consumer_disconnects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Writes the queued messages of a connection to its session until the
+/// connection is removed, or closes the session when told to.
+async fn write_messages(
+    conn_id: ConnId,
+    mut session: Session,
+    mut messages: mpsc::Receiver<String>,
+    mut close: oneshot::Receiver<CloseReason>,
+) {
+    loop {
+        tokio::select! {
+            biased;
+            reason = &mut close => {
+                // without a reason the connection was removed normally
+                if let Ok(reason) = reason {
+                    session.close(Some(reason)).await.ok();
+                }
+                break;
+            }
+            message = messages.recv() => match message {
+                Some(text) => {
+                    if let Err(e) = session.text(text).await {
+                        log::error!("Failed to send message to connection {:?}: {:?}", conn_id, e);
+                        break;
+                    }
+                }
+                None => break,
+            },
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct WebSocketManagerHandle {
     manager: Arc<WebSocketManager>,
+    fanout: WebsocketFanout,
 }
 
 impl WebSocketManagerHandle {
-    pub fn new() -> Self {
+    pub fn new(fanout: WebsocketFanout) -> Self {
         WebSocketManagerHandle {
             manager: Arc::new(WebSocketManager::new()),
+            fanout,
         }
     }
 
     pub async fn connect(
         &self,
         session_token: &SessionToken,
+        user_id: Uuid,
         session: &Session,
+        topics: Vec<Topic>,
+        replaying: bool,
     ) -> io::Result<ConnId> {
-        self.manager.connect(session_token, session.clone()).await
+        self.manager
+            .connect(session_token, user_id, session.clone(), topics, replaying)
+            .await
     }
 
     pub async fn disconnect(&self, conn_id: ConnId) -> io::Result<()> {
         self.manager.disconnect(conn_id).await
     }
 
-    pub async fn send_message_to_session(
+    pub async fn update_heartbeat(&self, conn_id: ConnId) -> io::Result<()> {
+        self.manager.update_heartbeat(conn_id).await
+    }
+
+    pub async fn subscribe(&self, conn_id: ConnId, topic: Topic) -> io::Result<()> {
+        self.manager.subscribe(conn_id, topic).await
+    }
+
+    pub async fn unsubscribe(&self, conn_id: ConnId, topic: &Topic) -> io::Result<()> {
+        self.manager.unsubscribe(conn_id, topic).await
+    }
+
+    /// Subscribes the connections of a user on every instance.
+    pub async fn subscribe_user(&self, user_id: &Uuid, topic: &Topic) -> io::Result<()> {
+        match &self.fanout {
+            WebsocketFanout::InProcess => self.manager.subscribe_user(user_id, topic).await,
+            WebsocketFanout::Postgres(fanout) => fanout
+                .notify_subscribe(user_id, topic)
+                .await
+                .map_err(|e| io::Error::other(format!("{:#}", e))),
+        }
+    }
+
+    /// Subscribes the connections of a user on this instance only.
+    pub async fn subscribe_user_local(&self, user_id: &Uuid, topic: &Topic) -> io::Result<()> {
+        self.manager.subscribe_user(user_id, topic).await
+    }
+
+    pub async fn send_to_connection(
         &self,
-        session_token: &SessionToken,
-        message: Message,
-        sender_conn_id: ConnId,
+        conn_id: ConnId,
+        message: &ServerMessage,
     ) -> io::Result<()> {
-        self.manager
-            .send_message_to_session(session_token, message, sender_conn_id)
-            .await
+        self.manager.send_to_connection(conn_id, message).await
     }
 
-    pub async fn update_heartbeat(&self, conn_id: ConnId) -> io::Result<()> {
-        self.manager.update_heartbeat(conn_id).await
+    /// Publishes a message to the subscribers of its topic on every
+    /// instance. If the fan-out fails the ones on this instance still get it.
+    pub async fn publish(&self, topic: &Topic, message: &ServerMessage) -> io::Result<()> {
+        match &self.fanout {
+            WebsocketFanout::InProcess => self.manager.publish(topic, message).await,
+            WebsocketFanout::Postgres(fanout) => {
+                match fanout.notify_publish(topic, message).await {
+                    Ok(()) => Ok(()),
+                    Err(e) => {
+                        log::error!("Failed to fan out {}: {:?}", message.message_type, e);
+                        self.manager.publish(topic, message).await
+                    }
+                }
+            }
+        }
+    }
+
+    /// Publishes a message to the subscribers on this instance only.
+    pub async fn publish_local(&self, topic: &Topic, message: &ServerMessage) -> io::Result<()> {
+        self.manager.publish(topic, message).await
     }
 
-    pub async fn broadcast_to_session(
+    pub async fn finish_replay(
         &self,
-        session_token: &SessionToken,
-        message: Message,
+        conn_id: ConnId,
+        missed: Vec<ServerMessage>,
     ) -> io::Result<()> {
-        self.manager
-            .broadcast_to_session(session_token, message)
-            .await
+        self.manager.finish_replay(conn_id, missed).await
+    }
+
+    pub fn metrics(&self) -> WebSocketMetrics {
+        self.manager.metrics()
     }
 }
@@ -1,2 +1,5 @@
+pub mod delivery;
+pub mod fanout;
 pub mod handler;
 pub mod manager;
+pub mod protocol;
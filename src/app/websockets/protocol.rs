@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+use uuid::Uuid;
+
+use crate::service::database::models::WebsocketEvent;
+
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// A topic a connection can subscribe to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Topic {
+    /// Pushes, test runs, output and results of one repository.
+    Repo(Uuid),
+    Leaderboard,
+    Announcements,
+}
+
+impl Topic {
+    pub fn from_str(topic: &str) -> Result<Self, &'static str> {
+        match topic {
+            "leaderboard" => Ok(Topic::Leaderboard),
+            "announcements" => Ok(Topic::Announcements),
+            _ => topic
+                .strip_prefix("repo:")
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .map(Topic::Repo)
+                .ok_or("Invalid topic"),
+        }
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topic::Repo(id) => write!(f, "repo:{}", id),
+            Topic::Leaderboard => write!(f, "leaderboard"),
+            Topic::Announcements => write!(f, "announcements"),
+        }
+    }
+}
+
+/// Every message the server sends. Published events have an `id` that
+/// increases with every event in the order they are stored; replies to a client message have none and
+/// carry the id the client sent as `payload.requestId` instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerMessage {
+    pub version: u32,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+    pub payload: Value,
+}
+
+impl ServerMessage {
+    pub fn new(message_type: &str, topic: Option<&Topic>, payload: Value) -> Self {
+        ServerMessage {
+            version: PROTOCOL_VERSION,
+            message_type: message_type.to_string(),
+            id: None,
+            topic: topic.map(|topic| topic.to_string()),
+            payload,
+        }
+    }
+
+    pub fn from_event(event: &WebsocketEvent) -> Self {
+        ServerMessage {
+            version: PROTOCOL_VERSION,
+            message_type: event.message_type.clone(),
+            id: Some(event.id),
+            topic: Some(event.topic.clone()),
+            payload: event.payload.clone(),
+        }
+    }
+
+    pub fn error(error: &ProtocolError, request_id: Option<&str>) -> Self {
+        ServerMessage::new(
+            "error",
+            None,
+            json!({
+                "code": error.code,
+                "message": error.message,
+                "requestId": request_id,
+            }),
+        )
+    }
+
+    pub fn to_text(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// A message sent by a client, in the same envelope as server messages.
+#[derive(Debug, Deserialize)]
+pub struct ClientMessage {
+    pub version: u32,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub id: Option<String>,
+    pub topic: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    InvalidMessage,
+    UnsupportedVersion,
+    UnknownType,
+    InvalidTopic,
+    Forbidden,
+    InternalError,
+}
+
+#[derive(Debug)]
+pub struct ProtocolError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ProtocolError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ProtocolError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// What a client asked for, once its message is validated.
+#[derive(Debug)]
+pub enum ClientRequest {
+    Subscribe(Topic),
+    Unsubscribe(Topic),
+    Ping,
+}
+
+/// Parses a text frame into a request, with the client's message id when
+/// it could be read so that errors can point back at it.
+pub fn parse_client_message(text: &str) -> (Option<String>, Result<ClientRequest, ProtocolError>) {
+    let message: ClientMessage = match serde_json::from_str(text) {
+        Ok(message) => message,
+        Err(e) => {
+            return (
+                None,
+                Err(ProtocolError::new(
+                    ErrorCode::InvalidMessage,
+                    format!("Invalid message: {}", e),
+                )),
+            )
+        }
+    };
+    let request = validate_client_message(&message);
+    (message.id, request)
+}
+
+fn validate_client_message(message: &ClientMessage) -> Result<ClientRequest, ProtocolError> {
+    if message.version != PROTOCOL_VERSION {
+        return Err(ProtocolError::new(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "Unsupported protocol version {}, expected {}",
+                message.version, PROTOCOL_VERSION
+            ),
+        ));
+    }
+    let topic = || {
+        let topic = message
+            .topic
+            .as_deref()
+            .ok_or_else(|| ProtocolError::new(ErrorCode::InvalidTopic, "A topic is required"))?;
+        Topic::from_str(topic).map_err(|_| {
+            ProtocolError::new(ErrorCode::InvalidTopic, format!("Unknown topic {}", topic))
+        })
+    };
+    match message.message_type.as_str() {
+        "subscribe" => Ok(ClientRequest::Subscribe(topic()?)),
+        "unsubscribe" => Ok(ClientRequest::Unsubscribe(topic()?)),
+        "ping" => Ok(ClientRequest::Ping),
+        other => Err(ProtocolError::new(
+            ErrorCode::UnknownType,
+            format!("Unknown message type {}", other),
+        )),
+    }
+}
@@ -3,13 +3,29 @@ use actix_web::{middleware::Logger, web, App, HttpServer};
 use app::{
     auth::middleware::AuthMiddleware,
     init::initialize_leaderboards,
+    repo::reconcile::run_periodic_reconciliation,
     routes,
-    websockets::{handler::websocket_handler, manager::WebSocketManagerHandle},
+    submission::track_test_run::run_test_run_timeouts,
+    websockets::{
+        delivery::run_event_retention,
+        fanout::{run_fanout_listener, WebsocketFanout},
+        handler::websocket_handler,
+        manager::WebSocketManagerHandle,
+    },
 };
 use dotenvy::dotenv;
 use env_logger::Env;
-use log::error;
-use service::{database::conn::get_connection_pool, queue::consume_queue};
+use log::{error, warn};
+use service::{
+    database::conn::get_connection_pool,
+    event_bus::{
+        dev_routes_enabled, event_bus_from_env, routing::JobRoutes, spawn_event_bus,
+        ConfiguredEventBus,
+    },
+    event_publisher::EventPublisherHandle,
+    queue::MessageHandler,
+    queue_health::QueueHealthHandle,
+};
 
 mod app;
 mod schema;
@@ -26,15 +42,15 @@ async fn main() -> std::io::Result<()> {
     if std::env::var("GIT_SERVICE_URL").is_err() {
         panic!("GIT_SERVICE_URL is not set");
     }
-    if std::env::var("RABBITMQ_URL").is_err() {
-        panic!("RABBITMQ_URL is not set");
-    }
-    if std::env::var("WEBHOOK_HANDLER_RABBITMQ_QUEUE_NAME").is_err() {
-        panic!("WEBHOOK_HANDLER_RABBITMQ_QUEUE_NAME is not set");
-    }
-    if std::env::var("TEST_RUNNER_RABBITMQ_QUEUE_NAME").is_err() {
-        panic!("TEST_RUNNER_RABBITMQ_QUEUE_NAME is not set");
-    }
+    let event_bus = event_bus_from_env().map_err(|e| {
+        error!("Failed to configure event bus: {}", e);
+        std::io::Error::other(format!("Failed to configure event bus: {}", e))
+    })?;
+    let job_routes = JobRoutes::from_env().map_err(|e| {
+        error!("Failed to configure test run job routes: {:#}", e);
+        std::io::Error::other(format!("Failed to configure test run job routes: {:#}", e))
+    })?;
+    let queue_inspector = event_bus.queue_inspector();
 
     let connection_url =
         std::env::var("CONNECTION_URL").unwrap_or_else(|_| "127.0.0.1:4925".to_string());
@@ -53,14 +69,57 @@ async fn main() -> std::io::Result<()> {
         ));
     }
 
-    let manager_handle = WebSocketManagerHandle::new();
-    let manager_handle_clone = manager_handle.clone();
+    let fanout = WebsocketFanout::from_env(&pool).map_err(|e| {
+        std::io::Error::other(format!("Failed to configure websocket fan-out: {:#}", e))
+    })?;
+    let manager_handle = WebSocketManagerHandle::new(fanout.clone());
+    let queue_health_handle = QueueHealthHandle::new();
+    let (event_publisher_handle, event_receiver) = EventPublisherHandle::new();
+    let message_handler = MessageHandler::new(
+        pool.clone(),
+        manager_handle.clone(),
+        event_publisher_handle.clone(),
+    );
 
-    tokio::spawn(async move {
-        if let Err(e) = consume_queue(manager_handle_clone).await {
-            log::error!("RabbitMQ consumer encountered an error: {:?}", e);
+    // events can only be injected when there is no broker to deliver them,
+    // and only when the dev routes were asked for
+    let event_injector = match event_bus {
+        ConfiguredEventBus::RabbitMq(bus) => {
+            spawn_event_bus(
+                bus,
+                message_handler.clone(),
+                queue_health_handle.clone(),
+                event_receiver,
+            );
+            None
         }
-    });
+        ConfiguredEventBus::InMemory(bus) => {
+            let injector = bus.injector();
+            spawn_event_bus(
+                bus,
+                message_handler.clone(),
+                queue_health_handle.clone(),
+                event_receiver,
+            );
+            match dev_routes_enabled() {
+                true => {
+                    warn!("Dev routes are enabled, events can be injected by admins");
+                    Some(injector)
+                }
+                false => None,
+            }
+        }
+    };
+    if event_injector.is_none() && dev_routes_enabled() {
+        warn!("ENABLE_DEV_ROUTES is ignored, dev routes need EVENT_BUS=memory");
+    }
+
+    tokio::spawn(run_periodic_reconciliation(pool.clone()));
+    tokio::spawn(run_test_run_timeouts(pool.clone(), manager_handle.clone()));
+    tokio::spawn(run_event_retention(pool.clone()));
+    if let WebsocketFanout::Postgres(fanout) = fanout {
+        tokio::spawn(run_fanout_listener(fanout, manager_handle.clone()));
+    }
 
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -70,10 +129,20 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(manager_handle.clone()))
+            .app_data(web::Data::new(queue_health_handle.clone()))
+            .app_data(web::Data::new(event_publisher_handle.clone()))
+            .app_data(web::Data::new(message_handler.clone()))
+            .app_data(web::Data::new(job_routes.clone()))
+            .app_data(web::Data::new(queue_inspector.clone()))
             .wrap(Logger::default())
             .wrap(AuthMiddleware)
             .wrap(cors)
-            .service(web::scope("/api").configure(routes::init))
+            .service(web::scope("/api").configure(routes::init).configure(|cfg| {
+                if let Some(injector) = event_injector.clone() {
+                    cfg.app_data(web::Data::new(injector));
+                    cfg.service(routes::dev::init());
+                }
+            }))
             .service(web::resource("/ws").route(web::get().to(websocket_handler)))
     })
     .bind(&connection_url)?
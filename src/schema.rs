@@ -46,6 +46,40 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    idempotency_keys (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        #[max_length = 255]
+        idempotency_key -> Varchar,
+        #[max_length = 255]
+        request_path -> Varchar,
+        request_body -> Jsonb,
+        response_status -> Nullable<Int4>,
+        response_body -> Nullable<Jsonb>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    inbound_events (id) {
+        id -> Uuid,
+        #[max_length = 255]
+        queue -> Varchar,
+        payload -> Text,
+        #[max_length = 255]
+        status -> Varchar,
+        error -> Nullable<Text>,
+        attempts -> Int4,
+        received_at -> Timestamp,
+        processed_at -> Nullable<Timestamp>,
+        updated_at -> Timestamp,
+        #[max_length = 255]
+        message_id -> Varchar,
+    }
+}
+
 diesel::table! {
     leaderboard (id) {
         id -> Int4,
@@ -58,6 +92,14 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    orphaned_remotes (repo_name) {
+        #[max_length = 255]
+        repo_name -> Varchar,
+        first_seen_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     progress (id) {
         id -> Uuid,
@@ -71,6 +113,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    progress_archives (id) {
+        id -> Uuid,
+        progress_id -> Uuid,
+        user_id -> Uuid,
+        challenge_id -> Uuid,
+        repository_id -> Uuid,
+        #[max_length = 255]
+        language -> Varchar,
+        #[max_length = 255]
+        status -> Varchar,
+        progress_details -> Nullable<Jsonb>,
+        score_earned -> Int4,
+        #[max_length = 255]
+        reason -> Varchar,
+        started_at -> Timestamp,
+        archived_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     repositories (id) {
         id -> Uuid,
@@ -83,6 +145,35 @@ diesel::table! {
         soft_serve_url -> Text,
         #[max_length = 255]
         language -> Varchar,
+        last_pushed_at -> Nullable<Timestamp>,
+        #[max_length = 255]
+        last_commit_sha -> Nullable<Varchar>,
+        #[max_length = 255]
+        last_push_branch -> Nullable<Varchar>,
+        push_count -> Int4,
+        force_push_count -> Int4,
+        remote_deleted_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    repository_pushes (id) {
+        id -> Uuid,
+        repository_id -> Uuid,
+        #[max_length = 255]
+        commit_sha -> Varchar,
+        #[max_length = 255]
+        branch -> Nullable<Varchar>,
+        pushed_at -> Timestamp,
+        #[max_length = 255]
+        kind -> Varchar,
+        forced -> Bool,
+        #[max_length = 255]
+        before_sha -> Nullable<Varchar>,
+        #[max_length = 255]
+        tag -> Nullable<Varchar>,
+        #[max_length = 255]
+        message_id -> Nullable<Varchar>,
     }
 }
 
@@ -102,7 +193,7 @@ diesel::table! {
     submissions (id) {
         id -> Uuid,
         user_id -> Uuid,
-        exercise_id -> Uuid,
+        exercise_id -> Nullable<Uuid>,
         #[max_length = 255]
         commit_id -> Varchar,
         repository_id -> Uuid,
@@ -111,6 +202,71 @@ diesel::table! {
         feedback -> Nullable<Text>,
         submitted_at -> Timestamp,
         updated_at -> Timestamp,
+        step -> Nullable<Int4>,
+        flagged_reason -> Nullable<Text>,
+        test_run_id -> Nullable<Uuid>,
+        #[max_length = 255]
+        delivery_key -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    submission_test_cases (id) {
+        id -> Uuid,
+        submission_id -> Uuid,
+        position -> Int4,
+        name -> Text,
+        suite -> Nullable<Text>,
+        #[max_length = 255]
+        status -> Varchar,
+        duration_ms -> Nullable<Int4>,
+        failure_message -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    test_run_output_chunks (id) {
+        id -> Uuid,
+        test_run_id -> Uuid,
+        sequence -> Int4,
+        output -> Text,
+        received_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    test_runners (id) {
+        #[max_length = 255]
+        id -> Varchar,
+        #[max_length = 255]
+        queue -> Varchar,
+        languages -> Jsonb,
+        capacity -> Int4,
+        busy -> Int4,
+        #[max_length = 255]
+        version -> Nullable<Varchar>,
+        registered_at -> Timestamp,
+        last_heartbeat_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    test_runs (id) {
+        id -> Uuid,
+        repository_id -> Uuid,
+        #[max_length = 255]
+        commit_sha -> Nullable<Varchar>,
+        #[max_length = 255]
+        status -> Varchar,
+        success -> Nullable<Bool>,
+        submission_id -> Nullable<Uuid>,
+        requested_by -> Nullable<Uuid>,
+        queued_at -> Timestamp,
+        started_at -> Nullable<Timestamp>,
+        finished_at -> Nullable<Timestamp>,
+        updated_at -> Timestamp,
+        relayed_sequence -> Int4,
     }
 }
 
@@ -143,28 +299,59 @@ diesel::table! {
 }
 
 diesel::joinable!(exercises -> challenges (challenge_id));
+diesel::joinable!(idempotency_keys -> users (user_id));
 diesel::joinable!(leaderboard -> users (user_id));
 diesel::joinable!(progress -> challenges (challenge_id));
 diesel::joinable!(progress -> repositories (repository_id));
 diesel::joinable!(progress -> users (user_id));
+diesel::joinable!(progress_archives -> challenges (challenge_id));
+diesel::joinable!(progress_archives -> users (user_id));
 diesel::joinable!(repositories -> challenges (challenge_id));
 diesel::joinable!(repositories -> users (user_id));
+diesel::joinable!(repository_pushes -> repositories (repository_id));
 diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(submission_test_cases -> submissions (submission_id));
 diesel::joinable!(submissions -> exercises (exercise_id));
 diesel::joinable!(submissions -> repositories (repository_id));
 diesel::joinable!(submissions -> users (user_id));
+diesel::table! {
+    websocket_events (id) {
+        id -> Int8,
+        user_id -> Nullable<Uuid>,
+        topic -> Varchar,
+        message_type -> Varchar,
+        payload -> Jsonb,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(test_run_output_chunks -> test_runs (test_run_id));
+diesel::joinable!(test_runs -> repositories (repository_id));
+diesel::joinable!(test_runs -> submissions (submission_id));
+diesel::joinable!(test_runs -> users (requested_by));
 diesel::joinable!(user_badges -> badges (badge_id));
 diesel::joinable!(user_badges -> users (user_id));
+diesel::joinable!(websocket_events -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     badges,
     challenges,
     exercises,
+    idempotency_keys,
+    inbound_events,
     leaderboard,
+    orphaned_remotes,
     progress,
+    progress_archives,
     repositories,
+    repository_pushes,
     sessions,
+    submission_test_cases,
     submissions,
+    test_run_output_chunks,
+    test_runners,
+    test_runs,
     user_badges,
     users,
+    websocket_events,
 );
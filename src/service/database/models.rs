@@ -64,6 +64,24 @@ pub struct Progress {
     pub updated_at: NaiveDateTime,
 }
 
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::progress_archives)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ProgressArchive {
+    pub id: Uuid,
+    pub progress_id: Uuid,
+    pub user_id: Uuid,
+    pub challenge_id: Uuid,
+    pub repository_id: Uuid,
+    pub language: String,
+    pub status: String,
+    pub progress_details: Option<serde_json::Value>,
+    pub score_earned: i32,
+    pub reason: String,
+    pub started_at: NaiveDateTime,
+    pub archived_at: NaiveDateTime,
+}
+
 #[derive(Queryable, Insertable, Selectable, Debug, Clone, Serialize)]
 #[diesel(table_name = crate::schema::repositories)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -77,22 +95,177 @@ pub struct Repository {
     pub language: String,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub last_pushed_at: Option<NaiveDateTime>,
+    pub last_commit_sha: Option<String>,
+    pub last_push_branch: Option<String>,
+    pub push_count: i32,
+    pub force_push_count: i32,
+    pub remote_deleted_at: Option<NaiveDateTime>,
 }
 
-#[derive(Queryable, Insertable, Selectable, Debug)]
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize)]
+#[diesel(table_name = crate::schema::repository_pushes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RepositoryPush {
+    pub id: Uuid,
+    pub repository_id: Uuid,
+    pub commit_sha: String,
+    pub branch: Option<String>,
+    pub pushed_at: NaiveDateTime,
+    pub kind: String,
+    pub forced: bool,
+    pub before_sha: Option<String>,
+    pub tag: Option<String>,
+    /// Queue message the push was delivered in.
+    pub message_id: Option<String>,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize)]
 #[diesel(table_name = crate::schema::submissions)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
 #[allow(dead_code)]
 pub struct Submission {
     pub id: Uuid,
     pub user_id: Uuid,
-    pub exercise_id: Uuid,
+    pub exercise_id: Option<Uuid>,
     pub commit_id: String,
     pub repository_id: Uuid,
     pub status: String,
     pub feedback: Option<String>,
     pub submitted_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub step: Option<i32>,
+    pub flagged_reason: Option<String>,
+    pub test_run_id: Option<Uuid>,
+    pub delivery_key: Option<String>,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize)]
+#[diesel(table_name = crate::schema::submission_test_cases)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SubmissionTestCase {
+    pub id: Uuid,
+    pub submission_id: Uuid,
+    pub position: i32,
+    pub name: String,
+    pub suite: Option<String>,
+    pub status: String,
+    pub duration_ms: Option<i32>,
+    pub failure_message: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::test_runs)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TestRun {
+    pub id: Uuid,
+    pub repository_id: Uuid,
+    pub commit_sha: Option<String>,
+    pub status: String,
+    pub success: Option<bool>,
+    pub submission_id: Option<Uuid>,
+    pub requested_by: Option<Uuid>,
+    pub queued_at: NaiveDateTime,
+    pub started_at: Option<NaiveDateTime>,
+    pub finished_at: Option<NaiveDateTime>,
+    pub updated_at: NaiveDateTime,
+    pub relayed_sequence: i32,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::test_run_output_chunks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TestRunOutputChunk {
+    pub id: Uuid,
+    pub test_run_id: Uuid,
+    pub sequence: i32,
+    pub output: String,
+    pub received_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::test_runners)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct TestRunner {
+    pub id: String,
+    pub queue: String,
+    pub languages: serde_json::Value,
+    pub capacity: i32,
+    pub busy: i32,
+    pub version: Option<String>,
+    pub registered_at: NaiveDateTime,
+    pub last_heartbeat_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::orphaned_remotes)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct OrphanedRemote {
+    pub repo_name: String,
+    pub first_seen_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Selectable, Debug, Serialize, Clone)]
+#[diesel(table_name = crate::schema::websocket_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebsocketEvent {
+    pub id: i64,
+    pub user_id: Option<Uuid>,
+    pub topic: String,
+    pub message_type: String,
+    pub payload: serde_json::Value,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::websocket_events)]
+pub struct NewWebsocketEvent {
+    pub user_id: Option<Uuid>,
+    pub topic: String,
+    pub message_type: String,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct TestCaseFailureStats {
+    pub suite: Option<String>,
+    pub name: String,
+    pub runs: i64,
+    pub failures: i64,
+    pub last_failed_at: Option<NaiveDateTime>,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::schema::idempotency_keys)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct IdempotencyKey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub idempotency_key: String,
+    pub request_path: String,
+    pub request_body: serde_json::Value,
+    pub response_status: Option<i32>,
+    pub response_body: Option<serde_json::Value>,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable, Selectable, Debug, Serialize)]
+#[diesel(table_name = crate::schema::inbound_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct InboundEvent {
+    pub id: Uuid,
+    pub queue: String,
+    pub payload: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub received_at: NaiveDateTime,
+    pub processed_at: Option<NaiveDateTime>,
+    pub updated_at: NaiveDateTime,
+    /// Stays the same for every retry of the message.
+    pub message_id: String,
 }
 
 #[derive(Queryable, Insertable, Selectable, Debug)]
@@ -199,6 +372,7 @@ pub struct RepositoryWithRelations {
     pub updated_at: NaiveDateTime,
     pub challenge: ChallengeInfo,
     pub progress: ProgressInfo,
+    pub activity: ActivityInfo,
 }
 #[derive(Debug, Queryable, Serialize)]
 pub struct AttemptInfo {
@@ -207,6 +381,18 @@ pub struct AttemptInfo {
     pub total_score: i32,
     pub module_count: i32,
     pub language: String,
+    pub last_pushed_at: Option<NaiveDateTime>,
+    pub push_count: i32,
+}
+
+#[derive(Debug, Queryable, Serialize)]
+pub struct ActivityInfo {
+    pub last_pushed_at: Option<NaiveDateTime>,
+    pub last_commit_sha: Option<String>,
+    pub last_push_branch: Option<String>,
+    pub push_count: i32,
+    pub force_push_count: i32,
+    pub remote_deleted_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Queryable, Serialize)]
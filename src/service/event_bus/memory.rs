@@ -0,0 +1,157 @@
+use anyhow::{Error, Result};
+use log::{error, info};
+use serde_json::{json, Value};
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use uuid::Uuid;
+
+use crate::service::{
+    event_bus::{routing::JobRoutes, EventBus},
+    event_publisher::OutboundMessage,
+    queue::{InboundQueue, MessageHandler},
+    queue_health::QueueHealthHandle,
+};
+
+// Published messages kept around for inspection, oldest are dropped first.
+const MAX_PUBLISHED_MESSAGES: usize = 100;
+
+struct InjectedMessage {
+    queue: InboundQueue,
+    data: Vec<u8>,
+    processed: oneshot::Sender<Result<(), String>>,
+}
+
+/// Feeds messages into an `InMemoryEventBus` as if they had arrived on its
+/// queues, and shows what core published in return.
+#[derive(Clone)]
+pub struct EventInjector {
+    sender: mpsc::UnboundedSender<InjectedMessage>,
+    published: Arc<Mutex<VecDeque<Value>>>,
+}
+
+impl EventInjector {
+    /// Delivers `data` on `queue` and waits until it has been handled. An
+    /// error carries the reason the message would have been dead-lettered.
+    pub async fn inject(&self, queue: InboundQueue, data: Vec<u8>) -> Result<()> {
+        let (processed, result) = oneshot::channel();
+        self.sender
+            .send(InjectedMessage {
+                queue,
+                data,
+                processed,
+            })
+            .map_err(|_| anyhow::anyhow!("In-memory event bus is not consuming"))?;
+        result
+            .await
+            .map_err(|_| anyhow::anyhow!("In-memory event bus stopped"))?
+            .map_err(Error::msg)
+    }
+
+    pub async fn published(&self) -> Vec<Value> {
+        self.published.lock().await.iter().cloned().collect()
+    }
+}
+
+/// Event bus for tests and local development: inbound messages come from an
+/// `EventInjector` instead of a broker, and published messages are only
+/// logged and kept in memory.
+pub struct InMemoryEventBus {
+    injector: EventInjector,
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<InjectedMessage>>>,
+    job_routes: JobRoutes,
+}
+
+impl InMemoryEventBus {
+    pub fn new(job_routes: JobRoutes) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        InMemoryEventBus {
+            injector: EventInjector {
+                sender,
+                published: Arc::new(Mutex::new(VecDeque::new())),
+            },
+            receiver: Mutex::new(Some(receiver)),
+            job_routes,
+        }
+    }
+
+    pub fn injector(&self) -> EventInjector {
+        self.injector.clone()
+    }
+}
+
+impl Default for InMemoryEventBus {
+    fn default() -> Self {
+        Self::new(JobRoutes::default())
+    }
+}
+
+impl EventBus for InMemoryEventBus {
+    async fn consume(
+        &self,
+        handler: MessageHandler,
+        health_handle: QueueHealthHandle,
+    ) -> Result<()> {
+        let mut receiver = self
+            .receiver
+            .lock()
+            .await
+            .take()
+            .ok_or_else(|| anyhow::anyhow!("In-memory event bus is already consuming"))?;
+        for queue in InboundQueue::all() {
+            health_handle.connected(queue.consumer_name()).await;
+        }
+        info!("Consuming events from the in-memory event bus");
+
+        while let Some(message) = receiver.recv().await {
+            let consumer_name = message.queue.consumer_name();
+            let message_id = Uuid::new_v4().to_string();
+            let result = match handler
+                .handle(message.queue, &message_id, message.data)
+                .await
+            {
+                Ok(()) => {
+                    health_handle.message_processed(consumer_name).await;
+                    Ok(())
+                }
+                Err(e) => {
+                    let reason = format!("{:#}", e);
+                    error!("Failed to process injected message: {}", reason);
+                    health_handle
+                        .message_dead_lettered(consumer_name, &reason)
+                        .await;
+                    Err(reason)
+                }
+            };
+            // the injector may have given up waiting
+            let _ = message.processed.send(result);
+        }
+        Ok(())
+    }
+
+    async fn publish(&self, mut receiver: mpsc::Receiver<OutboundMessage>) -> Result<()> {
+        while let Some(message) = receiver.recv().await {
+            let published = match &message {
+                OutboundMessage::Event(envelope) => json!({
+                    "routing_key": envelope.event_type,
+                    "body": envelope,
+                }),
+                OutboundMessage::TestRunJob(job) => json!({
+                    "routing_key": job.event_type,
+                    "queue": self.job_routes.queue_for(&job.language, &job.challenge_id),
+                    "body": job,
+                }),
+            };
+            info!(
+                "Published {} on the in-memory event bus",
+                published["routing_key"]
+            );
+
+            let mut messages = self.injector.published.lock().await;
+            if messages.len() == MAX_PUBLISHED_MESSAGES {
+                messages.pop_front();
+            }
+            messages.push_back(published);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use log::error;
+use serde::Serialize;
+use std::{future::Future, sync::Arc};
+use tokio::sync::mpsc;
+
+use crate::service::{
+    event_publisher::OutboundMessage, queue::MessageHandler, queue_health::QueueHealthHandle,
+};
+
+pub mod memory;
+pub mod rabbitmq;
+pub mod routing;
+
+use memory::InMemoryEventBus;
+use rabbitmq::{RabbitMqConfig, RabbitMqEventBus};
+use routing::JobRoutes;
+
+/// Carries messages between core and the other services: webhook and test
+/// runner events in, domain events and test run jobs out.
+pub trait EventBus: Send + Sync + 'static {
+    /// Hands inbound messages to `handler` for the lifetime of the process.
+    fn consume(
+        &self,
+        handler: MessageHandler,
+        health_handle: QueueHealthHandle,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Publishes the messages queued through an `EventPublisherHandle`.
+    fn publish(
+        &self,
+        receiver: mpsc::Receiver<OutboundMessage>,
+    ) -> impl Future<Output = Result<()>> + Send;
+}
+
+pub enum ConfiguredEventBus {
+    RabbitMq(RabbitMqEventBus),
+    InMemory(InMemoryEventBus),
+}
+
+impl ConfiguredEventBus {
+    pub fn queue_inspector(&self) -> QueueInspector {
+        match self {
+            ConfiguredEventBus::RabbitMq(bus) => QueueInspector::RabbitMq(bus.config().clone()),
+            ConfiguredEventBus::InMemory(_) => QueueInspector::InMemory,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct QueueDepth {
+    pub queue: String,
+    /// `None` when the bus can't tell, e.g. the in-memory one.
+    pub messages: Option<u32>,
+    pub consumers: Option<u32>,
+}
+
+/// Looks up how many messages wait on the queues of the configured bus.
+#[derive(Clone)]
+pub enum QueueInspector {
+    RabbitMq(RabbitMqConfig),
+    InMemory,
+}
+
+impl QueueInspector {
+    pub async fn queue_depths(&self, queues: &[&str]) -> Vec<QueueDepth> {
+        match self {
+            QueueInspector::RabbitMq(config) => rabbitmq::queue_depths(config, queues).await,
+            QueueInspector::InMemory => queues
+                .iter()
+                .map(|queue| QueueDepth {
+                    queue: queue.to_string(),
+                    messages: None,
+                    consumers: None,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Picks the event bus from `EVENT_BUS` (`rabbitmq` or `memory`), RabbitMQ
+/// by default. The in-memory bus has to be asked for, so that a missing
+/// `RABBITMQ_URL` fails startup instead of quietly running without a broker.
+pub fn event_bus_from_env() -> Result<ConfiguredEventBus> {
+    let kind = std::env::var("EVENT_BUS")
+        .map(|kind| kind.to_lowercase())
+        .unwrap_or_else(|_| "rabbitmq".to_string());
+
+    match kind.as_str() {
+        "rabbitmq" => Ok(ConfiguredEventBus::RabbitMq(RabbitMqEventBus::new(
+            RabbitMqConfig::from_env()?,
+        ))),
+        "memory" => Ok(ConfiguredEventBus::InMemory(InMemoryEventBus::new(
+            JobRoutes::from_env()?,
+        ))),
+        other => Err(anyhow::anyhow!(
+            "Unknown EVENT_BUS {}, expected rabbitmq or memory",
+            other
+        )),
+    }
+}
+
+/// Whether the dev routes are mounted, `ENABLE_DEV_ROUTES`. They only exist
+/// on the in-memory bus.
+pub fn dev_routes_enabled() -> bool {
+    std::env::var("ENABLE_DEV_ROUTES")
+        .map(|enabled| enabled.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Runs the consumers and the publisher of `bus` in the background.
+pub fn spawn_event_bus<B: EventBus>(
+    bus: B,
+    handler: MessageHandler,
+    health_handle: QueueHealthHandle,
+    receiver: mpsc::Receiver<OutboundMessage>,
+) {
+    let bus = Arc::new(bus);
+    let consumer = bus.clone();
+    tokio::spawn(async move {
+        if let Err(e) = consumer.consume(handler, health_handle).await {
+            error!("Event bus consumer encountered an error: {:?}", e);
+        }
+    });
+    tokio::spawn(async move {
+        if let Err(e) = bus.publish(receiver).await {
+            error!("Event bus publisher encountered an error: {:?}", e);
+        }
+    });
+}
@@ -0,0 +1,219 @@
+use anyhow::Result;
+use lapin::{
+    options::{ExchangeDeclareOptions, QueueBindOptions, QueueDeclareOptions},
+    types::FieldTable,
+    Channel, Connection, ConnectionProperties, ExchangeKind,
+};
+use log::{error, warn};
+use std::future::Future;
+use tokio::sync::mpsc;
+
+use crate::service::{
+    event_bus::{routing::JobRoutes, EventBus, QueueDepth},
+    event_publisher::{publish_events, OutboundMessage},
+    queue::{consume_queue, MessageHandler},
+    queue_health::QueueHealthHandle,
+};
+
+const DEFAULT_DOMAIN_EVENTS_EXCHANGE: &str = "hxckr.events";
+const DEFAULT_DEAD_LETTER_EXCHANGE: &str = "hxckr.dead_letter";
+
+#[derive(Clone)]
+pub struct RabbitMqConfig {
+    pub rabbitmq_url: String,
+    pub webhook_handler_queue_name: String,
+    pub test_runner_queue_name: String,
+    pub job_routes: JobRoutes,
+    pub domain_events_exchange: String,
+    pub dead_letter_exchange: String,
+}
+
+impl RabbitMqConfig {
+    pub fn from_env() -> Result<Self> {
+        Ok(RabbitMqConfig {
+            rabbitmq_url: required_var("RABBITMQ_URL")?,
+            webhook_handler_queue_name: required_var("WEBHOOK_HANDLER_RABBITMQ_QUEUE_NAME")?,
+            test_runner_queue_name: required_var("TEST_RUNNER_RABBITMQ_QUEUE_NAME")?,
+            job_routes: JobRoutes::from_env()?,
+            domain_events_exchange: std::env::var("DOMAIN_EVENTS_EXCHANGE")
+                .unwrap_or_else(|_| DEFAULT_DOMAIN_EVENTS_EXCHANGE.to_string()),
+            dead_letter_exchange: std::env::var("DEAD_LETTER_EXCHANGE")
+                .unwrap_or_else(|_| DEFAULT_DEAD_LETTER_EXCHANGE.to_string()),
+        })
+    }
+}
+
+/// Messages that could not be processed are routed here through the dead
+/// letter exchange, together with the reason.
+fn dead_letter_queue_name(queue_name: &str) -> String {
+    format!("{}.dead_letter", queue_name)
+}
+
+/// Declares the exchanges and queues core owns: the dead letter exchange,
+/// a dead letter queue per inbound queue and the domain events exchange.
+/// Declaring is idempotent, so the consumers and the publisher both do it
+/// when they connect.
+/// The inbound queues belong to the services publishing to them and the job
+/// queues to the runners, which may declare them with arguments of their
+/// own, so they are left alone here, see `check_queue`.
+pub async fn declare_topology(channel: &Channel, config: &RabbitMqConfig) -> Result<()> {
+    declare_exchange(channel, &config.dead_letter_exchange, ExchangeKind::Direct).await?;
+    for queue_name in [
+        &config.webhook_handler_queue_name,
+        &config.test_runner_queue_name,
+    ] {
+        let dead_letter_queue = dead_letter_queue_name(queue_name);
+        declare_queue(channel, &dead_letter_queue).await?;
+        channel
+            .queue_bind(
+                &dead_letter_queue,
+                &config.dead_letter_exchange,
+                queue_name,
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await?;
+    }
+    declare_exchange(channel, &config.domain_events_exchange, ExchangeKind::Topic).await?;
+    Ok(())
+}
+
+/// Checks that a queue declared by another service exists, whatever
+/// arguments it was declared with. Fails, and closes the channel, if it
+/// doesn't exist yet.
+pub async fn check_queue(channel: &Channel, queue_name: &str) -> Result<()> {
+    channel
+        .queue_declare(
+            queue_name,
+            QueueDeclareOptions {
+                passive: true,
+                ..QueueDeclareOptions::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn declare_queue(channel: &Channel, queue_name: &str) -> Result<()> {
+    channel
+        .queue_declare(
+            queue_name,
+            QueueDeclareOptions {
+                durable: true,
+                ..QueueDeclareOptions::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn declare_exchange(channel: &Channel, exchange: &str, kind: ExchangeKind) -> Result<()> {
+    channel
+        .exchange_declare(
+            exchange,
+            kind,
+            ExchangeDeclareOptions {
+                durable: true,
+                ..ExchangeDeclareOptions::default()
+            },
+            FieldTable::default(),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Reads the depth of each queue without declaring it. A queue that can't be
+/// read, e.g. because no runner declared it yet, is reported without counts.
+pub async fn queue_depths(config: &RabbitMqConfig, queues: &[&str]) -> Vec<QueueDepth> {
+    let unknown = |queue: &str| QueueDepth {
+        queue: queue.to_string(),
+        messages: None,
+        consumers: None,
+    };
+    let conn =
+        match Connection::connect(&config.rabbitmq_url, ConnectionProperties::default()).await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!("Failed to connect to read queue depths: {:?}", e);
+                return queues.iter().map(|queue| unknown(queue)).collect();
+            }
+        };
+
+    let mut depths = Vec::new();
+    for queue in queues {
+        // a failed passive declare closes the channel, so each gets its own
+        let declared = match conn.create_channel().await {
+            Ok(channel) => {
+                channel
+                    .queue_declare(
+                        queue,
+                        QueueDeclareOptions {
+                            passive: true,
+                            ..QueueDeclareOptions::default()
+                        },
+                        FieldTable::default(),
+                    )
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match declared {
+            Ok(declared) => depths.push(QueueDepth {
+                queue: queue.to_string(),
+                messages: Some(declared.message_count()),
+                consumers: Some(declared.consumer_count()),
+            }),
+            Err(e) => {
+                warn!("Failed to read depth of queue {}: {:?}", queue, e);
+                depths.push(unknown(queue));
+            }
+        }
+    }
+    if let Err(e) = conn.close(0, "").await {
+        warn!(
+            "Failed to close connection after reading queue depths: {:?}",
+            e
+        );
+    }
+    depths
+}
+
+fn required_var(name: &str) -> Result<String> {
+    std::env::var(name).map_err(|_| {
+        error!("{} is not set", name);
+        anyhow::anyhow!("{} is not set", name)
+    })
+}
+
+pub struct RabbitMqEventBus {
+    config: RabbitMqConfig,
+}
+
+impl RabbitMqEventBus {
+    pub fn new(config: RabbitMqConfig) -> Self {
+        RabbitMqEventBus { config }
+    }
+
+    pub fn config(&self) -> &RabbitMqConfig {
+        &self.config
+    }
+}
+
+impl EventBus for RabbitMqEventBus {
+    fn consume(
+        &self,
+        handler: MessageHandler,
+        health_handle: QueueHealthHandle,
+    ) -> impl Future<Output = Result<()>> + Send {
+        consume_queue(&self.config, handler, health_handle)
+    }
+
+    fn publish(
+        &self,
+        receiver: mpsc::Receiver<OutboundMessage>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        publish_events(&self.config, receiver)
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use uuid::Uuid;
+
+const DEFAULT_TEST_RUNNER_JOBS_QUEUE_NAME: &str = "test_runner_jobs";
+
+/// What a routing rule matches a test run job on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "match", content = "value", rename_all = "snake_case")]
+pub enum JobRouteMatch {
+    Language(String),
+    Challenge(Uuid),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobRoute {
+    #[serde(flatten)]
+    pub matches: JobRouteMatch,
+    pub queue: String,
+}
+
+/// Decides which queue a test run job is published to, so that the runners
+/// of each language, or of a single challenge, scale on their own. A rule
+/// for the challenge wins over one for the language, and jobs no rule
+/// matches go to the default queue.
+#[derive(Debug, Clone)]
+pub struct JobRoutes {
+    pub default_queue: String,
+    pub rules: Vec<JobRoute>,
+}
+
+impl Default for JobRoutes {
+    fn default() -> Self {
+        JobRoutes {
+            default_queue: DEFAULT_TEST_RUNNER_JOBS_QUEUE_NAME.to_string(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl JobRoutes {
+    /// Reads the default queue from `TEST_RUNNER_JOBS_RABBITMQ_QUEUE_NAME`
+    /// and the rules from `TEST_RUNNER_JOB_ROUTES`, see `parse`.
+    pub fn from_env() -> Result<Self> {
+        let default_queue = std::env::var("TEST_RUNNER_JOBS_RABBITMQ_QUEUE_NAME")
+            .unwrap_or_else(|_| DEFAULT_TEST_RUNNER_JOBS_QUEUE_NAME.to_string());
+        let rules = std::env::var("TEST_RUNNER_JOB_ROUTES").unwrap_or_default();
+        JobRoutes::parse(&default_queue, &rules).context("Invalid TEST_RUNNER_JOB_ROUTES")
+    }
+
+    /// Parses comma separated rules such as
+    /// `language:rust=test_runner_jobs.rust,challenge:<id>=test_runner_jobs.bitcoin`.
+    pub fn parse(default_queue: &str, rules: &str) -> Result<Self> {
+        let rules = rules
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| {
+                let (matcher, queue) = rule
+                    .split_once('=')
+                    .ok_or_else(|| anyhow::anyhow!("Rule {} has no queue", rule))?;
+                let matches = match matcher.trim().split_once(':') {
+                    Some(("language", language)) if !language.trim().is_empty() => {
+                        JobRouteMatch::Language(language.trim().to_lowercase())
+                    }
+                    Some(("challenge", challenge_id)) => JobRouteMatch::Challenge(
+                        Uuid::parse_str(challenge_id.trim())
+                            .context(format!("Rule {} has an invalid challenge id", rule))?,
+                    ),
+                    _ => {
+                        return Err(anyhow::anyhow!(
+                            "Rule {} must match on language:<name> or challenge:<id>",
+                            rule
+                        ))
+                    }
+                };
+                let queue = queue.trim();
+                if queue.is_empty() {
+                    return Err(anyhow::anyhow!("Rule {} has no queue", rule));
+                }
+                Ok(JobRoute {
+                    matches,
+                    queue: queue.to_string(),
+                })
+            })
+            .collect::<Result<Vec<JobRoute>>>()?;
+
+        Ok(JobRoutes {
+            default_queue: default_queue.to_string(),
+            rules,
+        })
+    }
+
+    pub fn queue_for(&self, language: &str, challenge_id: &Uuid) -> &str {
+        let challenge_rule = self
+            .rules
+            .iter()
+            .find(|rule| rule.matches == JobRouteMatch::Challenge(*challenge_id));
+        let language_rule = || {
+            self.rules.iter().find(|rule| {
+                matches!(&rule.matches, JobRouteMatch::Language(rule_language)
+                    if rule_language.eq_ignore_ascii_case(language))
+            })
+        };
+        challenge_rule
+            .or_else(language_rule)
+            .map(|rule| rule.queue.as_str())
+            .unwrap_or(&self.default_queue)
+    }
+
+    /// Every queue jobs can be routed to, the default one first.
+    pub fn queues(&self) -> Vec<&str> {
+        let mut queues = vec![self.default_queue.as_str()];
+        for rule in &self.rules {
+            if !queues.contains(&rule.queue.as_str()) {
+                queues.push(&rule.queue);
+            }
+        }
+        queues
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::{Error, Result};
+use lapin::{
+    options::{BasicPublishOptions, ConfirmSelectOptions},
+    types::{AMQPValue, FieldTable},
+    BasicProperties, Channel, Connection, ConnectionProperties,
+};
+use log::{error, info};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::service::{
+    event_bus::rabbitmq::{declare_topology, RabbitMqConfig},
+    events::{DomainEvent, EventEnvelope},
+    queue::{reconnect_delay, TestRunJob},
+};
+
+// Messages waiting to be published; once it is full, e.g. while RabbitMQ is
+// unreachable, further messages are dropped instead of piling up in memory
+const OUTBOUND_BUFFER_SIZE: usize = 1024;
+
+/// Messages core sends out through RabbitMQ.
+pub enum OutboundMessage {
+    Event(EventEnvelope),
+    TestRunJob(TestRunJob),
+}
+
+/// Queues domain events and test run jobs for publishing. Sending never
+/// blocks, so messages can be published from request handlers and consumers
+/// alike; the actual publishing is up to the event bus. Up to
+/// `OUTBOUND_BUFFER_SIZE` messages are buffered in memory, so messages are
+/// lost when the buffer is full or the process exits before they went out.
+#[derive(Clone)]
+pub struct EventPublisherHandle {
+    sender: mpsc::Sender<OutboundMessage>,
+}
+
+impl EventPublisherHandle {
+    pub fn new() -> (Self, mpsc::Receiver<OutboundMessage>) {
+        let (sender, receiver) = mpsc::channel(OUTBOUND_BUFFER_SIZE);
+        (EventPublisherHandle { sender }, receiver)
+    }
+
+    pub fn publish(&self, event: DomainEvent) {
+        let envelope = EventEnvelope::new(event);
+        let event_type = envelope.event_type;
+        match self.sender.try_send(OutboundMessage::Event(envelope)) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => error!(
+                "Event publisher is falling behind, dropping {} event",
+                event_type
+            ),
+            Err(TrySendError::Closed(_)) => error!(
+                "Event publisher is not running, dropping {} event",
+                event_type
+            ),
+        }
+    }
+
+    /// Queues a job for the test runner. Fails when the publisher is gone
+    /// or falling behind, so that the caller doesn't hand out a test run id
+    /// for a job that won't be sent.
+    pub fn queue_test_run(&self, job: TestRunJob) -> Result<()> {
+        let test_run_id = job.test_run_id;
+        match self.sender.try_send(OutboundMessage::TestRunJob(job)) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                error!(
+                    "Event publisher is falling behind, dropping test run {}",
+                    test_run_id
+                );
+                Err(anyhow::anyhow!("Event publisher is falling behind"))
+            }
+            Err(TrySendError::Closed(_)) => {
+                error!(
+                    "Event publisher is not running, dropping test run {}",
+                    test_run_id
+                );
+                Err(anyhow::anyhow!("Event publisher is not running"))
+            }
+        }
+    }
+}
+
+/// Publishes queued events to the `DOMAIN_EVENTS_EXCHANGE` topic exchange,
+/// routed by event type, and test run jobs to
+/// `TEST_RUNNER_JOBS_RABBITMQ_QUEUE_NAME`. A message that can't be published
+/// is retried after reconnecting, so the messages taken off the buffer are
+/// published in order and at least once as long as the process keeps
+/// running; see `EventPublisherHandle` for those that never get this far.
+pub async fn publish_events(
+    config: &RabbitMqConfig,
+    mut receiver: mpsc::Receiver<OutboundMessage>,
+) -> Result<()> {
+    let mut pending: Option<OutboundMessage> = None;
+    let mut reconnect_attempts: u32 = 0;
+    loop {
+        match connect(config).await {
+            Ok(channel) => {
+                reconnect_attempts = 0;
+                info!(
+                    "Publishing domain events to exchange {}",
+                    config.domain_events_exchange
+                );
+                loop {
+                    let message = match pending.take() {
+                        Some(message) => message,
+                        None => match receiver.recv().await {
+                            Some(message) => message,
+                            None => return Ok(()),
+                        },
+                    };
+                    let published = match &message {
+                        OutboundMessage::Event(envelope) => {
+                            publish(&channel, &config.domain_events_exchange, envelope)
+                                .await
+                                .map_err(|e| {
+                                    error!(
+                                        "Failed to publish {} event: {:?}",
+                                        envelope.event_type, e
+                                    )
+                                })
+                        }
+                        OutboundMessage::TestRunJob(job) => {
+                            let queue_name = config
+                                .job_routes
+                                .queue_for(&job.language, &job.challenge_id);
+                            publish_test_run_job(&channel, queue_name, job)
+                                .await
+                                .map_err(|e| {
+                                    error!(
+                                        "Failed to publish test run {}: {:?}",
+                                        job.test_run_id, e
+                                    )
+                                })
+                        }
+                    };
+                    if published.is_err() {
+                        pending = Some(message);
+                        break;
+                    }
+                }
+            }
+            Err(e) => error!("Failed to connect event publisher: {:?}", e),
+        }
+
+        reconnect_attempts = reconnect_attempts.saturating_add(1);
+        tokio::time::sleep(reconnect_delay(reconnect_attempts)).await;
+    }
+}
+
+async fn connect(config: &RabbitMqConfig) -> Result<Channel, Error> {
+    let conn = Connection::connect(&config.rabbitmq_url, ConnectionProperties::default()).await?;
+    let channel = conn.create_channel().await?;
+    declare_topology(&channel, config).await?;
+    channel
+        .confirm_select(ConfirmSelectOptions::default())
+        .await?;
+    Ok(channel)
+}
+
+async fn publish(channel: &Channel, exchange: &str, envelope: &EventEnvelope) -> Result<(), Error> {
+    let payload = serde_json::to_vec(envelope)?;
+    let mut headers = FieldTable::default();
+    headers.insert(
+        "schema_version".into(),
+        AMQPValue::LongUInt(envelope.schema_version),
+    );
+
+    let confirmation = channel
+        .basic_publish(
+            exchange,
+            envelope.event_type,
+            BasicPublishOptions::default(),
+            &payload,
+            BasicProperties::default()
+                .with_message_id(envelope.id.to_string().into())
+                .with_type(envelope.event_type.into())
+                .with_content_type("application/json".into())
+                .with_headers(headers)
+                .with_delivery_mode(2),
+        )
+        .await?
+        .await?;
+    if confirmation.is_nack() {
+        return Err(anyhow::anyhow!("Broker rejected the event"));
+    }
+    Ok(())
+}
+
+async fn publish_test_run_job(
+    channel: &Channel,
+    queue_name: &str,
+    job: &TestRunJob,
+) -> Result<(), Error> {
+    let payload = serde_json::to_vec(job)?;
+    let confirmation = channel
+        .basic_publish(
+            "",
+            queue_name,
+            BasicPublishOptions::default(),
+            &payload,
+            BasicProperties::default()
+                .with_message_id(job.test_run_id.to_string().into())
+                .with_content_type("application/json".into())
+                .with_delivery_mode(2),
+        )
+        .await?
+        .await?;
+    if confirmation.is_nack() {
+        return Err(anyhow::anyhow!("Broker rejected the test run job"));
+    }
+    Ok(())
+}
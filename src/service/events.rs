@@ -0,0 +1,86 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Version of the envelope and payload schemas in `schemas/events/`.
+/// Bump it together with the schema whenever a field is removed or changes
+/// meaning; adding an optional field doesn't need a new version.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UserSignedUp {
+    pub user_id: Uuid,
+    pub username: String,
+    pub github_username: String,
+    pub email: String,
+    pub role: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepoCreated {
+    pub repository_id: Uuid,
+    pub user_id: Uuid,
+    pub challenge_id: Uuid,
+    pub language: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forked_from: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepCompleted {
+    pub user_id: Uuid,
+    pub challenge_id: Uuid,
+    pub repository_id: Uuid,
+    pub step: i64,
+    pub commit_sha: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChallengeCompleted {
+    pub user_id: Uuid,
+    pub challenge_id: Uuid,
+    pub repository_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum DomainEvent {
+    UserSignedUp(UserSignedUp),
+    RepoCreated(RepoCreated),
+    StepCompleted(StepCompleted),
+    ChallengeCompleted(ChallengeCompleted),
+}
+
+impl DomainEvent {
+    /// Name of the event, also used as the routing key on the exchange.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            DomainEvent::UserSignedUp(_) => "user.signed_up",
+            DomainEvent::RepoCreated(_) => "repo.created",
+            DomainEvent::StepCompleted(_) => "progress.step_completed",
+            DomainEvent::ChallengeCompleted(_) => "challenge.completed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EventEnvelope {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub event_type: &'static str,
+    pub schema_version: u32,
+    pub occurred_at: NaiveDateTime,
+    pub data: DomainEvent,
+}
+
+impl EventEnvelope {
+    pub fn new(event: DomainEvent) -> Self {
+        EventEnvelope {
+            id: Uuid::new_v4(),
+            event_type: event.event_type(),
+            schema_version: EVENT_SCHEMA_VERSION,
+            occurred_at: chrono::Utc::now().naive_utc(),
+            data: event,
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::shared::errors::RepositoryError;
+use log::error;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateRepoResponse {
+    pub repo_name: String,
+    pub repo_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteRepoResponse {
+    pub repo_name: String,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RotateCredentialsResponse {
+    pub repo_name: String,
+    pub repo_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListReposResponse {
+    repositories: Vec<String>,
+}
+
+fn git_service_url() -> Result<String, RepositoryError> {
+    std::env::var("GIT_SERVICE_URL").map_err(|_| {
+        error!("GIT_SERVICE_URL environment variable not set");
+        RepositoryError::ServerConfigurationError(
+            "GIT_SERVICE_URL environment variable not set".to_string(),
+        )
+    })
+}
+
+/// Creates a repository named `repo_name` on the git service, seeded from
+/// the starter code at `repo_url`.
+pub async fn create_repo(
+    repo_name: &str,
+    repo_url: &str,
+) -> Result<CreateRepoResponse, RepositoryError> {
+    let git_service_url = git_service_url()?;
+    let client = reqwest::Client::new();
+    let request_body = json!({
+        "repo_name": repo_name,
+        "repo_url": repo_url,
+    });
+    let response = client
+        .post(format!("{}/create_repo", git_service_url))
+        .header("Content-Type", "application/json")
+        .body(request_body.to_string())
+        .send()
+        .await
+        .map_err(|e| {
+            error!("Error creating repository in git service: {:#?}", e);
+            RepositoryError::BadRequest("Error creating repository".to_string())
+        })?;
+
+    match response.status() {
+        StatusCode::OK => response.json::<CreateRepoResponse>().await.map_err(|e| {
+            error!("Error in git service response: {:#?}", e);
+            RepositoryError::BadRequest("Error decoding repository response".to_string())
+        }),
+        StatusCode::CONFLICT => Err(RepositoryError::RepositoryAlreadyExists),
+        _ => Err(RepositoryError::BadRequest(
+            "Error creating repository".to_string(),
+        )),
+    }
+}
+
+pub async fn delete_repo(repo_name: &str) -> Result<DeleteRepoResponse, RepositoryError> {
+    let git_service_url = git_service_url()?;
+    let client = reqwest::Client::new();
+    let response = client
+        .delete(format!("{}/delete_repo", git_service_url))
+        .json(&json!({
+            "repo_name": repo_name
+        }))
+        .send()
+        .await
+        .map_err(|e| {
+            error!("Error deleting repository from git service: {:#?}", e);
+            RepositoryError::BadRequest("Error deleting repository".to_string())
+        })?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(RepositoryError::NotFound(format!(
+            "Repository {} not found on git service",
+            repo_name
+        )));
+    }
+    if !response.status().is_success() {
+        return Err(RepositoryError::BadRequest(
+            "Failed to delete repository".to_string(),
+        ));
+    }
+
+    response.json::<DeleteRepoResponse>().await.map_err(|e| {
+        error!("Error parsing delete response: {:#?}", e);
+        RepositoryError::BadRequest("Error parsing delete response".to_string())
+    })
+}
+
+/// Revokes the access token of `repo_name` and returns the clone url with
+/// a freshly issued one.
+pub async fn rotate_credentials(
+    repo_name: &str,
+) -> Result<RotateCredentialsResponse, RepositoryError> {
+    let git_service_url = git_service_url()?;
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/rotate_token", git_service_url))
+        .json(&json!({
+            "repo_name": repo_name
+        }))
+        .send()
+        .await
+        .map_err(|e| {
+            error!("Error rotating repository token in git service: {:#?}", e);
+            RepositoryError::BadRequest("Error rotating repository credentials".to_string())
+        })?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(RepositoryError::NotFound(format!(
+            "Repository {} not found on git service",
+            repo_name
+        )));
+    }
+    if !response.status().is_success() {
+        return Err(RepositoryError::BadRequest(
+            "Failed to rotate repository credentials".to_string(),
+        ));
+    }
+
+    response
+        .json::<RotateCredentialsResponse>()
+        .await
+        .map_err(|e| {
+            error!("Error parsing rotate token response: {:#?}", e);
+            RepositoryError::BadRequest("Error parsing rotate token response".to_string())
+        })
+}
+
+pub async fn list_repos() -> Result<Vec<String>, RepositoryError> {
+    let git_service_url = git_service_url()?;
+    let client = reqwest::Client::new();
+    let response = client
+        .get(format!("{}/list_repos", git_service_url))
+        .send()
+        .await
+        .map_err(|e| {
+            error!("Error listing repositories from git service: {:#?}", e);
+            RepositoryError::BadRequest("Error listing repositories".to_string())
+        })?;
+
+    if !response.status().is_success() {
+        return Err(RepositoryError::BadRequest(
+            "Failed to list repositories".to_string(),
+        ));
+    }
+
+    let list_repos_response = response.json::<ListReposResponse>().await.map_err(|e| {
+        error!("Error parsing repository list response: {:#?}", e);
+        RepositoryError::BadRequest("Error parsing repository list".to_string())
+    })?;
+    Ok(list_repos_response.repositories)
+}
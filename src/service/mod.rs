@@ -8,11 +8,26 @@ pub mod repository {
     pub mod challenge;
     pub mod exercise;
     pub mod progress;
+    pub mod progress_archive;
     pub mod repo;
+    pub mod repository_push;
     pub mod submission;
+    pub mod submission_test_case;
     pub mod session;
     pub mod leaderboard;
     pub mod badge;
+    pub mod idempotency_key;
+    pub mod inbound_event;
+    pub mod orphaned_remote;
+    pub mod test_run;
+    pub mod test_run_output_chunk;
+    pub mod test_runner;
+    pub mod websocket_event;
 }
 
+pub mod event_bus;
+pub mod event_publisher;
+pub mod events;
+pub mod git;
 pub mod queue;
+pub mod queue_health;
@@ -1,16 +1,76 @@
-use actix_ws::Message;
-use anyhow::{Error, Result};
+use anyhow::{Context, Error, Result};
 use futures_util::StreamExt;
-use lapin::{options::*, types::FieldTable, Connection, ConnectionProperties};
-use log::error;
+use lapin::{
+    options::*,
+    types::{AMQPValue, FieldTable},
+    BasicProperties, Channel, Connection, ConnectionProperties, Consumer,
+};
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
+use std::time::Duration;
+use uuid::Uuid;
 
-use crate::app::{
-    progress::update_progress::update_progress, repo::match_repo::match_repo_for_webhook,
-    websockets::manager::WebSocketManagerHandle,
+use crate::{
+    app::{
+        repo::activity::{record_push, record_ref_created, record_remote_deleted},
+        runner::registry::{record_heartbeat, Heartbeat},
+        submission::{
+            process_test_result::{process_test_result, TestResult},
+            test_output::{
+                assemble_output, broadcast_output_chunk, flush_output, record_output_chunk,
+            },
+            test_report::{parse_test_report, TestCaseResult, TestReportFormat},
+            track_test_run::{
+                broadcast_test_run, finish_test_run, get_repo_by_url, queue_test_run_for_push,
+                start_test_run,
+            },
+        },
+        websockets::{delivery::record_event, manager::WebSocketManagerHandle, protocol::Topic},
+    },
+    service::{
+        database::{
+            conn::DbPool,
+            models::{InboundEvent, Leaderboard},
+        },
+        event_bus::rabbitmq::{check_queue, declare_topology, RabbitMqConfig},
+        event_publisher::EventPublisherHandle,
+        events::{ChallengeCompleted, DomainEvent, StepCompleted},
+        queue_health::QueueHealthHandle,
+    },
+    shared::primitives::{RepositoryEventKind, Status, TestRunStatus},
 };
 
+const WEBHOOK_CONSUMER: &str = "webhook_handler";
+const TEST_RUNNER_CONSUMER: &str = "test_runner";
+// Messages without `schema_version` predate versioning and are read as this
+const UNVERSIONED_SCHEMA_VERSION: u64 = 1;
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+// A message failing for another reason than itself is retried this often
+// before it is dead-lettered after all
+const MAX_MESSAGE_RETRIES: u32 = 5;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+const RETRY_COUNT_HEADER: &str = "x-retry-count";
+
+/// A message that can never be processed because it is not valid JSON, has
+/// an unsupported schema version, fails validation or is about a repository
+/// core doesn't know. Any other failure, such as the database being
+/// unavailable, is treated as transient.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct InvalidMessage(pub String);
+
+pub fn invalid_message(message: impl Into<String>) -> Error {
+    Error::new(InvalidMessage(message.into()))
+}
+
+/// Whether retrying the message can't help.
+pub fn is_permanent_failure(error: &Error) -> bool {
+    error.chain().any(|cause| cause.is::<InvalidMessage>())
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 struct WebhookHandlerConsumerEvent {
     event_type: String,
@@ -19,6 +79,14 @@ struct WebhookHandlerConsumerEvent {
     branch: Option<String>,
     #[serde(rename = "commitSha")]
     commit_sha: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+    /// Set on a push that rewrote history.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    forced: bool,
+    /// Head of the branch before the push.
+    #[serde(rename = "beforeSha", default, skip_serializing_if = "Option::is_none")]
+    before_sha: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -29,179 +97,1055 @@ struct TestRunnerConsumerEvent {
     #[serde(rename = "repoUrl")]
     repo_url: String,
     success: bool,
+    // runners streaming their output may leave it out, see `chunkCount`
+    #[serde(default)]
     output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stage: Option<i64>,
+    #[serde(
+        rename = "exerciseId",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    exercise_id: Option<Uuid>,
+    #[serde(rename = "testRunId", default, skip_serializing_if = "Option::is_none")]
+    test_run_id: Option<Uuid>,
+    /// One of `json`, `tap` or `junit`. Without `results` the report is read
+    /// from `output`.
+    #[serde(
+        rename = "resultsFormat",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    results_format: Option<String>,
+    // forwarded to the client parsed, as `tests`
+    #[serde(default, skip_serializing)]
+    results: Option<Value>,
+    /// Number of output chunks streamed for the run, to detect lost ones.
+    #[serde(rename = "chunkCount", default, skip_serializing)]
+    chunk_count: Option<i32>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Sent by the test runner when it picks up a run, before the result.
+#[derive(Debug, Deserialize)]
+struct TestRunnerStatusEvent {
+    #[serde(rename = "commitSha")]
+    commit_sha: String,
+    #[serde(rename = "repoUrl")]
+    repo_url: String,
+    status: String,
+    #[serde(rename = "testRunId", default)]
+    test_run_id: Option<Uuid>,
+}
+
+/// A piece of output streamed while a run is in progress. Sequence numbers
+/// start at 1 and have no gaps.
+#[derive(Debug, Deserialize)]
+struct TestRunnerOutputChunk {
+    #[serde(rename = "commitSha")]
+    commit_sha: String,
+    #[serde(rename = "repoUrl")]
+    repo_url: String,
+    sequence: i32,
+    output: String,
+    #[serde(rename = "testRunId", default)]
+    test_run_id: Option<Uuid>,
+}
+
+/// Sent by every test runner periodically, see `RUNNER_HEARTBEAT_TIMEOUT_SECS`.
+#[derive(Debug, Deserialize)]
+struct TestRunnerHeartbeat {
+    #[serde(rename = "runnerId")]
+    runner_id: String,
+    /// Jobs queue the runner consumes.
+    queue: String,
+    #[serde(default)]
+    languages: Vec<String>,
+    capacity: i32,
+    #[serde(default)]
+    busy: i32,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+/// Carries either the result of a run, a status update on it, a chunk of
+/// its output or a runner heartbeat.
+#[derive(Debug, Deserialize)]
 struct TestRunnerWrapper {
-    result: TestRunnerConsumerEvent,
-}
-
-pub async fn consume_queue(manager_handle: WebSocketManagerHandle) -> Result<(), Error> {
-    let rabbitmq_url = std::env::var("RABBITMQ_URL").map_err(|_| {
-        error!("RABBITMQ_URL is not set");
-        anyhow::anyhow!("RabbitMQ URL is not set")
-    })?;
-    let webhook_handler_rabbitmq_queue_name = std::env::var("WEBHOOK_HANDLER_RABBITMQ_QUEUE_NAME")
-        .map_err(|_| {
-            error!("WEBHOOK_HANDLER_RABBITMQ_QUEUE_NAME is not set");
-            anyhow::anyhow!("Webhook handler RabbitMQ queue name is not set")
-        })?;
-    let test_runner_rabbitmq_queue_name = std::env::var("TEST_RUNNER_RABBITMQ_QUEUE_NAME")
-        .map_err(|_| {
-            error!("TEST_RUNNER_RABBITMQ_QUEUE_NAME is not set");
-            anyhow::anyhow!("Test runner RabbitMQ queue name is not set")
-        })?;
-
-    let conn = Connection::connect(&rabbitmq_url, ConnectionProperties::default()).await?;
-    let channel = conn.create_channel().await?;
+    #[serde(default)]
+    result: Option<TestRunnerConsumerEvent>,
+    #[serde(default)]
+    status: Option<TestRunnerStatusEvent>,
+    #[serde(default)]
+    chunk: Option<TestRunnerOutputChunk>,
+    #[serde(default)]
+    heartbeat: Option<TestRunnerHeartbeat>,
+}
 
-    let webhook_handle = manager_handle.clone();
-    let webhook_channel = channel.clone();
-    let test_handle = manager_handle.clone();
-    let test_channel = channel;
-
-    // Spawn two separate tasks for concurrent queue processing
-    let webhook_consumer = tokio::spawn(async move {
-        consume_webhook_queue(
-            webhook_channel,
-            webhook_handle,
-            &webhook_handler_rabbitmq_queue_name,
-        )
-        .await
-    });
+/// Job asking the test runner to test a repository. The runner reports back
+/// on the test runner queue like it does for pushes, echoing `testRunId`.
+#[derive(Debug, Serialize)]
+pub struct TestRunJob {
+    pub event_type: &'static str,
+    #[serde(rename = "testRunId")]
+    pub test_run_id: Uuid,
+    #[serde(rename = "repoUrl")]
+    pub repo_url: String,
+    /// `None` tests the head of the default branch.
+    #[serde(rename = "commitSha")]
+    pub commit_sha: Option<String>,
+    pub language: String,
+    #[serde(rename = "challengeId")]
+    pub challenge_id: Uuid,
+    pub stage: i64,
+    #[serde(rename = "requestedBy")]
+    pub requested_by: Uuid,
+}
 
-    let test_consumer = tokio::spawn(async move {
-        consume_test_queue(test_channel, test_handle, &test_runner_rabbitmq_queue_name).await
-    });
+impl TestRunJob {
+    pub fn new(
+        repo_url: &str,
+        commit_sha: Option<String>,
+        language: &str,
+        challenge_id: &Uuid,
+        stage: i64,
+        requested_by: &Uuid,
+    ) -> Self {
+        TestRunJob {
+            event_type: "test_run",
+            test_run_id: Uuid::new_v4(),
+            repo_url: repo_url.to_string(),
+            commit_sha,
+            language: language.to_string(),
+            challenge_id: challenge_id.to_owned(),
+            stage,
+            requested_by: requested_by.to_owned(),
+        }
+    }
+}
 
-    // Wait for both consumers
-    tokio::try_join!(webhook_consumer, test_consumer)?;
+/// The queues core consumes, whatever event bus delivers them.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InboundQueue {
+    Webhook,
+    TestRunner,
+}
 
-    Ok(())
+impl InboundQueue {
+    pub fn all() -> [InboundQueue; 2] {
+        [InboundQueue::Webhook, InboundQueue::TestRunner]
+    }
+
+    /// Schema versions accepted on the queue. Producers bump
+    /// `schema_version` on breaking changes, and a version not listed here
+    /// is dead-lettered instead of being read with the wrong shape.
+    pub fn accepted_schema_versions(&self) -> &'static [u64] {
+        match self {
+            InboundQueue::Webhook => &[1],
+            InboundQueue::TestRunner => &[1],
+        }
+    }
+
+    pub fn from_str(queue: &str) -> Result<InboundQueue, &'static str> {
+        match queue {
+            "webhook" => Ok(InboundQueue::Webhook),
+            "test-runner" => Ok(InboundQueue::TestRunner),
+            _ => Err("Invalid inbound queue"),
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            InboundQueue::Webhook => "webhook",
+            InboundQueue::TestRunner => "test-runner",
+        }
+    }
+
+    /// Name the consumer is reported under by the health endpoint.
+    pub fn consumer_name(&self) -> &'static str {
+        match self {
+            InboundQueue::Webhook => WEBHOOK_CONSUMER,
+            InboundQueue::TestRunner => TEST_RUNNER_CONSUMER,
+        }
+    }
 }
 
-async fn consume_webhook_queue(
-    channel: lapin::Channel,
+/// Processes inbound messages. An error means the message could not be
+/// processed; the event bus dead-letters it when `is_permanent_failure`
+/// and retries it otherwise.
+#[derive(Clone)]
+pub struct MessageHandler {
+    pool: DbPool,
     manager_handle: WebSocketManagerHandle,
-    queue_name: &str,
+    events: EventPublisherHandle,
+}
+
+impl MessageHandler {
+    pub fn new(
+        pool: DbPool,
+        manager_handle: WebSocketManagerHandle,
+        events: EventPublisherHandle,
+    ) -> Self {
+        MessageHandler {
+            pool,
+            manager_handle,
+            events,
+        }
+    }
+
+    /// Stores the message as an inbound event before processing it and
+    /// records the outcome, so that a failed message can be replayed later.
+    /// `message_id` is the same for every retry of the message, which is
+    /// stored once and not processed again once it was processed.
+    pub async fn handle(
+        &self,
+        queue: InboundQueue,
+        message_id: &str,
+        data: Vec<u8>,
+    ) -> Result<(), Error> {
+        if is_heartbeat(queue, &data) {
+            return self.process(queue, message_id, data).await;
+        }
+        let event = {
+            let mut conn = self
+                .pool
+                .get()
+                .context("Failed to get connection from pool")?;
+            InboundEvent::record_delivery(
+                &mut conn,
+                InboundEvent::new(queue.to_str(), message_id, &String::from_utf8_lossy(&data)),
+            )
+            .context("Failed to store inbound event")?
+        };
+        let event = match event {
+            Some(event) => event,
+            None => {
+                info!(
+                    "Message {} from {} was handled already",
+                    message_id,
+                    queue.consumer_name()
+                );
+                return Ok(());
+            }
+        };
+
+        let result = self.process(queue, message_id, data).await;
+        let failure = result.as_ref().err().map(|e| format!("{:#}", e));
+        match self.pool.get() {
+            Ok(mut conn) => {
+                if let Err(e) = InboundEvent::finish(&mut conn, &event.id, failure.as_deref()) {
+                    error!(
+                        "Failed to record outcome of inbound event {}: {}",
+                        event.id, e
+                    );
+                }
+            }
+            Err(e) => error!(
+                "Failed to record outcome of inbound event {}: {}",
+                event.id, e
+            ),
+        }
+        result
+    }
+
+    /// Processes a message without storing it, as done when replaying.
+    pub async fn process(
+        &self,
+        queue: InboundQueue,
+        message_id: &str,
+        data: Vec<u8>,
+    ) -> Result<(), Error> {
+        let message: Value = serde_json::from_slice(&data)
+            .map_err(|e| invalid_message(format!("Message is not valid JSON: {}", e)))?;
+        check_schema_version(queue, &message)?;
+        match queue {
+            InboundQueue::Webhook => {
+                handle_webhook_message(&self.pool, self.manager_handle.clone(), message_id, message)
+                    .await
+            }
+            InboundQueue::TestRunner => {
+                handle_test_message(
+                    &self.pool,
+                    self.manager_handle.clone(),
+                    self.events.clone(),
+                    message,
+                )
+                .await
+            }
+        }
+    }
+}
+
+fn check_schema_version(queue: InboundQueue, message: &Value) -> Result<(), Error> {
+    let version = match message.get("schema_version") {
+        None | Some(Value::Null) => UNVERSIONED_SCHEMA_VERSION,
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| invalid_message("schema_version must be a positive integer"))?,
+    };
+    let accepted = queue.accepted_schema_versions();
+    if !accepted.contains(&version) {
+        return Err(invalid_message(format!(
+            "Unsupported schema_version {} for {} messages, accepted versions are {:?}",
+            version,
+            queue.consumer_name(),
+            accepted
+        )));
+    }
+    Ok(())
+}
+
+/// Time to wait before reconnecting, doubling with every failed attempt up
+/// to `MAX_RECONNECT_DELAY`.
+pub fn reconnect_delay(reconnect_attempts: u32) -> Duration {
+    INITIAL_RECONNECT_DELAY
+        .saturating_mul(2u32.saturating_pow(reconnect_attempts.saturating_sub(1)))
+        .min(MAX_RECONNECT_DELAY)
+}
+
+/// Runs the RabbitMQ consumers for the lifetime of the process, starting
+/// them again with `reconnect_delay` whenever the connection drops.
+pub async fn consume_queue(
+    config: &RabbitMqConfig,
+    handler: MessageHandler,
+    health_handle: QueueHealthHandle,
+) -> Result<(), Error> {
+    let mut reconnect_attempts: u32 = 0;
+    loop {
+        let reason =
+            match run_consumers(config, &handler, &health_handle, &mut reconnect_attempts).await {
+                Ok(()) => "RabbitMQ consumers stopped".to_string(),
+                Err(e) => format!("{:#}", e),
+            };
+
+        reconnect_attempts = reconnect_attempts.saturating_add(1);
+        let delay = reconnect_delay(reconnect_attempts);
+        error!(
+            "RabbitMQ consumers disconnected: {}. Reconnecting in {:?} (attempt {})",
+            reason, delay, reconnect_attempts
+        );
+        for queue in InboundQueue::all() {
+            health_handle
+                .disconnected(queue.consumer_name(), &reason, reconnect_attempts)
+                .await;
+        }
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn run_consumers(
+    config: &RabbitMqConfig,
+    handler: &MessageHandler,
+    health_handle: &QueueHealthHandle,
+    reconnect_attempts: &mut u32,
 ) -> Result<(), Error> {
-    let mut consumer = channel
+    let conn = Connection::connect(&config.rabbitmq_url, ConnectionProperties::default()).await?;
+    let channel = conn.create_channel().await?;
+    declare_topology(&channel, config).await?;
+    check_queue(&channel, &config.webhook_handler_queue_name).await?;
+    check_queue(&channel, &config.test_runner_queue_name).await?;
+
+    let webhook_consumer = start_consumer(
+        &channel,
+        &config.webhook_handler_queue_name,
+        "backend_consumer_webhook_handler",
+    )
+    .await?;
+    let test_consumer = start_consumer(
+        &channel,
+        &config.test_runner_queue_name,
+        "backend_consumer_test_runner",
+    )
+    .await?;
+
+    *reconnect_attempts = 0;
+    for queue in InboundQueue::all() {
+        health_handle.connected(queue.consumer_name()).await;
+    }
+    info!("RabbitMQ consumers connected");
+
+    // Both consumers share the connection, so once either of them stops the
+    // other one is dropped as well and both are started again together.
+    tokio::select! {
+        result = consume_messages(
+            &channel,
+            &config.dead_letter_exchange,
+            webhook_consumer,
+            &config.webhook_handler_queue_name,
+            InboundQueue::Webhook,
+            handler,
+            health_handle,
+        ) => result,
+        result = consume_messages(
+            &channel,
+            &config.dead_letter_exchange,
+            test_consumer,
+            &config.test_runner_queue_name,
+            InboundQueue::TestRunner,
+            handler,
+            health_handle,
+        ) => result,
+    }
+}
+
+async fn start_consumer(
+    channel: &Channel,
+    queue_name: &str,
+    consumer_tag: &str,
+) -> Result<Consumer, Error> {
+    let consumer = channel
         .basic_consume(
             queue_name,
-            "backend_consumer_webhook_handler",
+            consumer_tag,
             BasicConsumeOptions::default(),
             FieldTable::default(),
         )
         .await?;
+    Ok(consumer)
+}
 
+/// Hands every delivery to `handler`. A message that is invalid is
+/// dead-lettered and nacked so that it can't stop the messages behind it.
+/// One that failed otherwise is published to the back of the queue again
+/// after `retry_delay`, waited out in a task of its own so the consumer
+/// moves on meanwhile, and dead-lettered once it failed
+/// `MAX_MESSAGE_RETRIES` times. Only errors of the channel itself end the
+/// consumer.
+async fn consume_messages(
+    channel: &Channel,
+    dead_letter_exchange: &str,
+    mut consumer: Consumer,
+    queue_name: &str,
+    queue: InboundQueue,
+    handler: &MessageHandler,
+    health_handle: &QueueHealthHandle,
+) -> Result<(), Error> {
+    let consumer_name = queue.consumer_name();
     while let Some(delivery) = consumer.next().await {
-        if let Ok(delivery) = delivery {
-            let webhook_handler_payload: WebhookHandlerConsumerEvent =
-                serde_json::from_slice(&delivery.data)?;
-            let message = serde_json::to_string(&webhook_handler_payload)?;
-            match webhook_handler_payload.event_type.to_lowercase().as_str() {
-                "push" => {
-                    let repo_url = match webhook_handler_payload.repo_url.as_ref() {
-                        Some(url) if !url.is_empty() => url,
-                        _ => {
-                            error!("Repository URL is missing or empty");
-                            return Err(anyhow::anyhow!("Repository URL is missing or empty"));
-                        }
-                    };
-                    let session = match match_repo_for_webhook(repo_url).await {
-                        Ok(session) => session,
-                        Err(e) => {
-                            error!("Failed to match repository: {:?}", e);
-                            return Err(anyhow::anyhow!("Repository not found"));
-                        }
-                    };
-                    // send the event through the websocket to the client
-                    if let Err(e) = manager_handle
-                        .broadcast_to_session(&session.token, Message::Text(message.into()))
-                        .await
-                    {
-                        error!("Failed to broadcast webhook event: {:?}", e);
-                        return Err(anyhow::anyhow!("Failed to broadcast webhook event"));
-                    }
+        let delivery = delivery?;
+        let message_id = message_id(&delivery);
+        let e = match handler
+            .handle(queue, &message_id, delivery.data.clone())
+            .await
+        {
+            Ok(()) => {
+                delivery.ack(BasicAckOptions::default()).await?;
+                health_handle.message_processed(consumer_name).await;
+                continue;
+            }
+            Err(e) => e,
+        };
+
+        let reason = format!("{:#}", e);
+        let retries = retry_count(&delivery);
+        if !is_permanent_failure(&e) && retries < MAX_MESSAGE_RETRIES {
+            let delay = retry_delay(retries + 1);
+            warn!(
+                "Failed to process message from {}, retrying in {:?} (retry {}): {}",
+                queue_name,
+                delay,
+                retries + 1,
+                reason
+            );
+            health_handle.message_requeued(consumer_name, &reason).await;
+            let channel = channel.clone();
+            let queue_name = queue_name.to_string();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                // if the copy can't be published the message stays unacked
+                // and is delivered again after reconnecting
+                let result = async {
+                    requeue(&channel, &queue_name, &delivery, &message_id, retries + 1).await?;
+                    delivery.ack(BasicAckOptions::default()).await?;
+                    Ok::<(), Error>(())
                 }
-                _ => {
-                    error!("Invalid event type");
+                .await;
+                if let Err(e) = result {
+                    error!(
+                        "Failed to retry message from {}, leaving it unacked: {:#}",
+                        queue_name, e
+                    );
                 }
-            }
-            delivery.ack(BasicAckOptions::default()).await?;
+            });
+            continue;
         }
+
+        error!("Failed to process message from {}: {}", queue_name, reason);
+        dead_letter(
+            channel,
+            dead_letter_exchange,
+            queue_name,
+            &delivery,
+            &message_id,
+            &reason,
+        )
+        .await?;
+        delivery
+            .nack(BasicNackOptions {
+                requeue: false,
+                ..BasicNackOptions::default()
+            })
+            .await?;
+        health_handle
+            .message_dead_lettered(consumer_name, &reason)
+            .await;
     }
+    Err(anyhow::anyhow!("Consumer for {} was cancelled", queue_name))
+}
+
+/// Time to wait before retrying a message, doubling with every retry up to
+/// `MAX_RETRY_DELAY`.
+fn retry_delay(retries: u32) -> Duration {
+    INITIAL_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(retries.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Id the message is stored under as an inbound event. Messages the
+/// producer didn't give an id get one derived from their routing key and
+/// body, so that a redelivery of the same message is recognized as such.
+/// Two messages with the same body on the same queue are therefore taken as
+/// one; producers that can send those have to set a message id.
+fn message_id(delivery: &lapin::message::Delivery) -> String {
+    delivery
+        .properties
+        .message_id()
+        .as_ref()
+        .map(|message_id| message_id.to_string())
+        .unwrap_or_else(|| {
+            let mut name = delivery.routing_key.as_str().as_bytes().to_vec();
+            name.push(0);
+            name.extend_from_slice(&delivery.data);
+            Uuid::new_v5(&Uuid::NAMESPACE_OID, &name).to_string()
+        })
+}
+
+fn retry_count(delivery: &lapin::message::Delivery) -> u32 {
+    delivery
+        .properties
+        .headers()
+        .as_ref()
+        .and_then(|headers| headers.inner().get(RETRY_COUNT_HEADER))
+        .and_then(|retries| match retries {
+            AMQPValue::LongUInt(retries) => Some(*retries),
+            AMQPValue::LongInt(retries) => u32::try_from(*retries).ok(),
+            AMQPValue::LongLongInt(retries) => u32::try_from(*retries).ok(),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// Publishes a copy of the message to the back of its queue, so that the
+/// messages behind it are not held up while it is retried.
+async fn requeue(
+    channel: &Channel,
+    queue_name: &str,
+    delivery: &lapin::message::Delivery,
+    message_id: &str,
+    retries: u32,
+) -> Result<(), Error> {
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(RETRY_COUNT_HEADER.into(), AMQPValue::LongUInt(retries));
+
+    channel
+        .basic_publish(
+            "",
+            queue_name,
+            BasicPublishOptions::default(),
+            &delivery.data,
+            BasicProperties::default()
+                .with_headers(headers)
+                .with_message_id(message_id.into())
+                .with_content_type("application/json".into())
+                .with_delivery_mode(2),
+        )
+        .await?
+        .await?;
     Ok(())
 }
 
-async fn consume_test_queue(
-    channel: lapin::Channel,
-    manager_handle: WebSocketManagerHandle,
+async fn dead_letter(
+    channel: &Channel,
+    dead_letter_exchange: &str,
     queue_name: &str,
+    delivery: &lapin::message::Delivery,
+    message_id: &str,
+    reason: &str,
 ) -> Result<(), Error> {
-    let mut consumer = channel
-        .basic_consume(
+    let mut headers = delivery.properties.headers().clone().unwrap_or_default();
+    headers.insert(
+        "x-failure-reason".into(),
+        AMQPValue::LongString(reason.into()),
+    );
+    headers.insert(
+        "x-original-queue".into(),
+        AMQPValue::LongString(queue_name.into()),
+    );
+
+    channel
+        .basic_publish(
+            dead_letter_exchange,
             queue_name,
-            "backend_consumer_test_runner",
-            BasicConsumeOptions::default(),
-            FieldTable::default(),
+            BasicPublishOptions::default(),
+            &delivery.data,
+            BasicProperties::default()
+                .with_headers(headers)
+                .with_message_id(message_id.into())
+                .with_content_type("application/json".into())
+                .with_delivery_mode(2),
         )
+        .await?
         .await?;
+    Ok(())
+}
 
-    while let Some(delivery) = consumer.next().await {
-        if let Ok(delivery) = delivery {
-            let wrapper: TestRunnerWrapper = serde_json::from_slice(&delivery.data)?;
-            let test_runner_payload = wrapper.result;
+async fn handle_webhook_message(
+    pool: &DbPool,
+    manager_handle: WebSocketManagerHandle,
+    message_id: &str,
+    message: Value,
+) -> Result<(), Error> {
+    let mut webhook_handler_payload: WebhookHandlerConsumerEvent = serde_json::from_value(message)
+        .map_err(|e| invalid_message(format!("Invalid webhook message: {}", e)))?;
+    let event_type = webhook_handler_payload.event_type.to_lowercase();
+    if !matches!(
+        event_type.as_str(),
+        "push" | "force_push" | "branch_created" | "tag_created" | "repository_deleted"
+    ) {
+        warn!("Ignoring webhook event of type {}", event_type);
+        return Ok(());
+    }
 
-            if test_runner_payload.repo_url.is_empty() {
-                error!("Repository URL is missing or empty");
-                return Err(anyhow::anyhow!("Repository URL is missing or empty"));
-            }
-            let session = match match_repo_for_webhook(&test_runner_payload.repo_url).await {
-                Ok(session) => session,
-                Err(e) => {
-                    error!("Failed to match repository: {:?}", e);
-                    return Err(anyhow::anyhow!("Repository not found"));
-                }
-            };
-            if test_runner_payload.success {
-                let updated_progress =
-                    match update_progress(&test_runner_payload.repo_url, &session.user_id).await {
-                        Ok(progress) => progress,
-                        Err(e) => {
-                            error!("Failed to update progress: {:?}", e);
-                            return Err(anyhow::anyhow!("Failed to update progress"));
-                        }
-                    };
-                let combined_payload = json!({
-                    "event_type": test_runner_payload.event_type,
-                    "repoUrl": test_runner_payload.repo_url,
-                    "commitSha": test_runner_payload.commit_sha,
-                    "success": test_runner_payload.success,
-                    "output": test_runner_payload.output,
-                    "progress": updated_progress
-                });
-                let combined_message = serde_json::to_string(&combined_payload)?;
-                if let Err(e) = manager_handle
-                    .broadcast_to_session(&session.token, Message::Text(combined_message.into()))
-                    .await
-                {
-                    error!("Failed to broadcast test runner event: {:?}", e);
-                    return Err(anyhow::anyhow!("Failed to broadcast test runner event"));
+    let repo_url = match webhook_handler_payload.repo_url.as_deref() {
+        Some(url) if !url.is_empty() => url,
+        _ => {
+            error!("Repository URL is missing or empty");
+            return Err(invalid_message("Repository URL is missing or empty"));
+        }
+    };
+    let commit_sha = webhook_handler_payload
+        .commit_sha
+        .as_deref()
+        .filter(|commit_sha| !commit_sha.is_empty());
+    let branch = webhook_handler_payload.branch.as_deref();
+
+    let mut queued_test_run = None;
+    match event_type.as_str() {
+        "push" | "force_push" => {
+            // older producers only send the flag, newer ones the event type
+            webhook_handler_payload.forced |= event_type == "force_push";
+            match commit_sha {
+                Some(commit_sha) => {
+                    record_push(
+                        pool,
+                        repo_url,
+                        message_id,
+                        commit_sha,
+                        branch,
+                        webhook_handler_payload.forced,
+                        webhook_handler_payload.before_sha.as_deref(),
+                    )
+                    .await?;
+                    queued_test_run =
+                        Some(queue_test_run_for_push(pool, repo_url, commit_sha).await?);
                 }
-            } else {
-                let message = serde_json::to_string(&test_runner_payload)?;
-                if let Err(e) = manager_handle
-                    .broadcast_to_session(&session.token, Message::Text(message.into()))
-                    .await
-                {
-                    error!("Failed to broadcast test runner event: {:?}", e);
-                    return Err(anyhow::anyhow!("Failed to broadcast test runner event"));
+                None => warn!("Commit SHA is missing, push activity not recorded"),
+            }
+        }
+        "branch_created" | "tag_created" => {
+            let kind = RepositoryEventKind::from_str(&event_type).map_err(invalid_message)?;
+            match commit_sha {
+                Some(commit_sha) => {
+                    record_ref_created(
+                        pool,
+                        repo_url,
+                        message_id,
+                        kind,
+                        commit_sha,
+                        branch,
+                        webhook_handler_payload.tag.as_deref(),
+                    )
+                    .await?;
                 }
+                None => warn!("Commit SHA is missing, {} not recorded", event_type),
             }
-            delivery.ack(BasicAckOptions::default()).await?;
         }
+        _ => {
+            if record_remote_deleted(pool, repo_url).await?.is_none() {
+                // nobody to tell, or the repository is back already
+                return Ok(());
+            }
+        }
+    }
+
+    // send the event through the websocket to the client
+    let payload = serde_json::to_value(&webhook_handler_payload)?;
+    publish_repo_event(pool, &manager_handle, repo_url, &event_type, payload).await;
+    if let Some(test_run) = queued_test_run {
+        broadcast_test_run(pool, &manager_handle, repo_url, &test_run).await;
+    }
+    Ok(())
+}
+
+async fn handle_test_message(
+    pool: &DbPool,
+    manager_handle: WebSocketManagerHandle,
+    events: EventPublisherHandle,
+    message: Value,
+) -> Result<(), Error> {
+    let wrapper: TestRunnerWrapper = serde_json::from_value(message)
+        .map_err(|e| invalid_message(format!("Invalid test runner message: {}", e)))?;
+    if let Some(heartbeat) = wrapper.heartbeat {
+        return handle_runner_heartbeat(pool, heartbeat).await;
+    }
+    match (wrapper.result, wrapper.status, wrapper.chunk) {
+        (Some(result), _, _) => handle_test_result(pool, manager_handle, events, result).await,
+        (None, Some(status), _) => handle_test_status(pool, manager_handle, status).await,
+        (None, None, Some(chunk)) => handle_test_output(pool, manager_handle, chunk).await,
+        (None, None, None) => Err(invalid_message(
+            "Test runner message has neither a result, a status, a chunk nor a heartbeat",
+        )),
+    }
+}
+
+async fn handle_runner_heartbeat(
+    pool: &DbPool,
+    heartbeat: TestRunnerHeartbeat,
+) -> Result<(), Error> {
+    if heartbeat.runner_id.is_empty() || heartbeat.queue.is_empty() {
+        return Err(invalid_message(
+            "Runner heartbeat is missing runnerId or queue",
+        ));
+    }
+    if heartbeat.capacity < 0 || heartbeat.busy < 0 {
+        return Err(invalid_message(format!(
+            "Runner {} reported a negative capacity or busy count",
+            heartbeat.runner_id
+        )));
     }
+    record_heartbeat(
+        pool,
+        &Heartbeat {
+            runner_id: &heartbeat.runner_id,
+            queue: &heartbeat.queue,
+            languages: &heartbeat.languages,
+            capacity: heartbeat.capacity,
+            busy: heartbeat.busy,
+            version: heartbeat.version.as_deref(),
+        },
+    )
+    .await?;
     Ok(())
 }
+
+/// Heartbeats only matter until the next one, so they are not stored as
+/// inbound events.
+fn is_heartbeat(queue: InboundQueue, data: &[u8]) -> bool {
+    matches!(queue, InboundQueue::TestRunner)
+        && serde_json::from_slice::<Value>(data)
+            .is_ok_and(|message| message.get("heartbeat").is_some())
+}
+
+async fn handle_test_output(
+    pool: &DbPool,
+    manager_handle: WebSocketManagerHandle,
+    chunk: TestRunnerOutputChunk,
+) -> Result<(), Error> {
+    if chunk.repo_url.is_empty() {
+        error!("Repository URL is missing or empty");
+        return Err(invalid_message("Repository URL is missing or empty"));
+    }
+    if chunk.sequence < 1 {
+        return Err(invalid_message(format!(
+            "Invalid output chunk sequence {}",
+            chunk.sequence
+        )));
+    }
+
+    let recorded = match record_output_chunk(
+        pool,
+        &chunk.repo_url,
+        &chunk.commit_sha,
+        chunk.test_run_id,
+        chunk.sequence,
+        &chunk.output,
+    )
+    .await?
+    {
+        Some(recorded) => recorded,
+        None => {
+            info!(
+                "No running test run for commit {} to stream output to",
+                chunk.commit_sha
+            );
+            return Ok(());
+        }
+    };
+    if recorded.started {
+        broadcast_test_run(pool, &manager_handle, &chunk.repo_url, &recorded.test_run).await;
+    }
+    for relayed in &recorded.relay {
+        broadcast_output_chunk(
+            pool,
+            &manager_handle,
+            &chunk.repo_url,
+            &recorded.test_run,
+            relayed,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+async fn handle_test_status(
+    pool: &DbPool,
+    manager_handle: WebSocketManagerHandle,
+    status_event: TestRunnerStatusEvent,
+) -> Result<(), Error> {
+    if status_event.repo_url.is_empty() {
+        error!("Repository URL is missing or empty");
+        return Err(invalid_message("Repository URL is missing or empty"));
+    }
+    let status = TestRunStatus::from_str(&status_event.status).map_err(invalid_message)?;
+    if status != TestRunStatus::Running {
+        // queued runs are tracked from the push, results finish them
+        info!(
+            "Ignoring {} status of test run for commit {}",
+            status_event.status, status_event.commit_sha
+        );
+        return Ok(());
+    }
+
+    match start_test_run(
+        pool,
+        &status_event.repo_url,
+        &status_event.commit_sha,
+        status_event.test_run_id,
+    )
+    .await?
+    {
+        Some(test_run) => {
+            broadcast_test_run(pool, &manager_handle, &status_event.repo_url, &test_run).await;
+        }
+        None => info!(
+            "No queued test run for commit {} to start",
+            status_event.commit_sha
+        ),
+    }
+    Ok(())
+}
+
+async fn handle_test_result(
+    pool: &DbPool,
+    manager_handle: WebSocketManagerHandle,
+    events: EventPublisherHandle,
+    mut test_runner_payload: TestRunnerConsumerEvent,
+) -> Result<(), Error> {
+    if test_runner_payload.repo_url.is_empty() {
+        error!("Repository URL is missing or empty");
+        return Err(invalid_message("Repository URL is missing or empty"));
+    }
+    if let Some(assembled) = assemble_output(
+        pool,
+        &test_runner_payload.repo_url,
+        &test_runner_payload.commit_sha,
+        test_runner_payload.test_run_id,
+        test_runner_payload.chunk_count,
+    )
+    .await?
+    {
+        flush_output(
+            pool,
+            &manager_handle,
+            &test_runner_payload.repo_url,
+            &assembled.test_run,
+            &assembled.unrelayed,
+        )
+        .await;
+        test_runner_payload.output = assembled.output;
+    }
+    let test_cases = reported_test_cases(&test_runner_payload);
+    let processed = process_test_result(
+        pool,
+        &test_runner_payload.repo_url,
+        &TestResult {
+            commit_sha: &test_runner_payload.commit_sha,
+            success: test_runner_payload.success,
+            output: &test_runner_payload.output,
+            stage: test_runner_payload.stage,
+            exercise_id: test_runner_payload.exercise_id,
+            test_run_id: test_runner_payload.test_run_id,
+            test_cases: &test_cases,
+        },
+    )
+    .await?;
+    if let Some(reason) = processed.flagged_reason.as_ref() {
+        warn!(
+            "Test result for commit {} not applied: {}",
+            test_runner_payload.commit_sha, reason
+        );
+    }
+    if processed.duplicate {
+        // still forwarded below, the client may not have seen it yet
+        info!(
+            "Test result for commit {} was already processed",
+            test_runner_payload.commit_sha
+        );
+    }
+
+    if let Some(step) = processed.completed_step {
+        let progress = &processed.progress;
+        events.publish(DomainEvent::StepCompleted(StepCompleted {
+            user_id: progress.user_id,
+            challenge_id: progress.challenge_id,
+            repository_id: progress.repository_id,
+            step,
+            commit_sha: test_runner_payload.commit_sha.clone(),
+        }));
+        if progress.status == Status::Completed.to_str() {
+            events.publish(DomainEvent::ChallengeCompleted(ChallengeCompleted {
+                user_id: progress.user_id,
+                challenge_id: progress.challenge_id,
+                repository_id: progress.repository_id,
+            }));
+        }
+    }
+
+    let payload = json!({
+        "repoUrl": test_runner_payload.repo_url,
+        "commitSha": test_runner_payload.commit_sha,
+        "testRunId": test_runner_payload.test_run_id,
+        "success": test_runner_payload.success,
+        "output": test_runner_payload.output,
+        "stage": test_runner_payload.stage,
+        "exerciseId": test_runner_payload.exercise_id,
+        "flaggedReason": processed.flagged_reason,
+        "tests": test_cases,
+        "progress": processed.progress
+    });
+    publish_repo_event(
+        pool,
+        &manager_handle,
+        &test_runner_payload.repo_url,
+        "test_result",
+        payload,
+    )
+    .await;
+    if processed.completed_step.is_some() {
+        publish_leaderboard_update(pool, &manager_handle, &processed.progress.user_id).await;
+    }
+
+    if let Some(test_run) = finish_test_run(
+        pool,
+        &test_runner_payload.repo_url,
+        &test_runner_payload.commit_sha,
+        test_runner_payload.test_run_id,
+        test_runner_payload.success,
+        processed.submission_id,
+    )
+    .await?
+    {
+        broadcast_test_run(
+            pool,
+            &manager_handle,
+            &test_runner_payload.repo_url,
+            &test_run,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Test cases of the structured results a run reported. A report that can't
+/// be read is logged and left out, the run itself is still recorded.
+fn reported_test_cases(payload: &TestRunnerConsumerEvent) -> Vec<TestCaseResult> {
+    let format = match payload.results_format.as_deref() {
+        Some(format) => format,
+        None => {
+            if payload.results.is_some() {
+                warn!(
+                    "Test results for commit {} do not say which format they are in",
+                    payload.commit_sha
+                );
+            }
+            return Vec::new();
+        }
+    };
+    let format = match TestReportFormat::from_str(format) {
+        Ok(format) => format,
+        Err(e) => {
+            warn!("{} {} for commit {}", e, format, payload.commit_sha);
+            return Vec::new();
+        }
+    };
+    let output = Value::String(payload.output.clone());
+    let report = payload.results.as_ref().unwrap_or(&output);
+    parse_test_report(format, report).unwrap_or_else(|e| {
+        warn!(
+            "Failed to parse test results for commit {}: {:#}",
+            payload.commit_sha, e
+        );
+        Vec::new()
+    })
+}
+
+/// Publishes an event on the websocket topic of the repository it is about,
+/// stored for its owner in case they are not connected. The event has
+/// already been recorded by then, so a failure is only logged.
+pub async fn publish_repo_event(
+    pool: &DbPool,
+    manager_handle: &WebSocketManagerHandle,
+    repo_url: &str,
+    message_type: &str,
+    payload: Value,
+) {
+    let message = pool.get().map_err(Error::from).and_then(|mut conn| {
+        let repo = get_repo_by_url(&mut conn, repo_url)?;
+        let topic = Topic::Repo(repo.id);
+        let message = record_event(
+            &mut conn,
+            Some(&repo.user_id),
+            &topic,
+            message_type,
+            payload,
+        );
+        Ok((topic, message))
+    });
+    let (topic, message) = match message {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("No repository to publish event for {}: {:?}", repo_url, e);
+            return;
+        }
+    };
+    if let Err(e) = manager_handle.publish(&topic, &message).await {
+        error!("Failed to publish event: {:?}", e);
+    }
+}
+
+/// Publishes the new leaderboard entry of a user who completed a step.
+async fn publish_leaderboard_update(
+    pool: &DbPool,
+    manager_handle: &WebSocketManagerHandle,
+    user_id: &Uuid,
+) {
+    let topic = Topic::Leaderboard;
+    let message = pool.get().map_err(Error::from).and_then(|mut conn| {
+        let entry = Leaderboard::get_leaderboard(&mut conn, Some(user_id))?;
+        Ok(entry.into_iter().next().map(|entry| {
+            record_event(&mut conn, None, &topic, "leaderboard_updated", json!(entry))
+        }))
+    });
+    let message = match message {
+        Ok(Some(message)) => message,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Failed to get leaderboard entry of {}: {:?}", user_id, e);
+            return;
+        }
+    };
+    if let Err(e) = manager_handle.publish(&topic, &message).await {
+        error!("Failed to publish leaderboard update: {:?}", e);
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::{collections::BTreeMap, sync::Arc};
+use tokio::sync::RwLock;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConsumerStatus {
+    pub connected: bool,
+    pub last_connected_at: Option<NaiveDateTime>,
+    pub last_message_at: Option<NaiveDateTime>,
+    pub processed_messages: u64,
+    pub dead_lettered_messages: u64,
+    pub requeued_messages: u64,
+    pub reconnect_attempts: u32,
+    pub last_error: Option<String>,
+}
+
+/// Shared view of the queue consumers, written by the consumer tasks and
+/// read by the health endpoint.
+#[derive(Clone, Default)]
+pub struct QueueHealthHandle {
+    consumers: Arc<RwLock<BTreeMap<String, ConsumerStatus>>>,
+}
+
+impl QueueHealthHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn snapshot(&self) -> BTreeMap<String, ConsumerStatus> {
+        self.consumers.read().await.clone()
+    }
+
+    pub async fn is_healthy(&self) -> bool {
+        let consumers = self.consumers.read().await;
+        !consumers.is_empty() && consumers.values().all(|status| status.connected)
+    }
+
+    pub async fn connected(&self, consumer: &str) {
+        let mut consumers = self.consumers.write().await;
+        let status = consumers.entry(consumer.to_string()).or_default();
+        status.connected = true;
+        status.last_connected_at = Some(chrono::Utc::now().naive_utc());
+        status.reconnect_attempts = 0;
+    }
+
+    pub async fn disconnected(&self, consumer: &str, reason: &str, reconnect_attempts: u32) {
+        let mut consumers = self.consumers.write().await;
+        let status = consumers.entry(consumer.to_string()).or_default();
+        status.connected = false;
+        status.reconnect_attempts = reconnect_attempts;
+        status.last_error = Some(reason.to_string());
+    }
+
+    pub async fn message_processed(&self, consumer: &str) {
+        let mut consumers = self.consumers.write().await;
+        let status = consumers.entry(consumer.to_string()).or_default();
+        status.processed_messages += 1;
+        status.last_message_at = Some(chrono::Utc::now().naive_utc());
+    }
+
+    pub async fn message_requeued(&self, consumer: &str, reason: &str) {
+        let mut consumers = self.consumers.write().await;
+        let status = consumers.entry(consumer.to_string()).or_default();
+        status.requeued_messages += 1;
+        status.last_message_at = Some(chrono::Utc::now().naive_utc());
+        status.last_error = Some(reason.to_string());
+    }
+
+    pub async fn message_dead_lettered(&self, consumer: &str, reason: &str) {
+        let mut consumers = self.consumers.write().await;
+        let status = consumers.entry(consumer.to_string()).or_default();
+        status.dead_lettered_messages += 1;
+        status.last_message_at = Some(chrono::Utc::now().naive_utc());
+        status.last_error = Some(reason.to_string());
+    }
+}
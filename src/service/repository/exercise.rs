@@ -93,4 +93,56 @@ impl Exercise {
             (None, None) => Err(anyhow::anyhow!("No input provided")),
         }
     }
+
+    /// Exercises of a challenge are worked through in the order they were
+    /// created, so the exercise for `step` is the `step`-th one.
+    pub fn get_for_step(
+        connection: &mut PgConnection,
+        challenge_id: &Uuid,
+        step: i64,
+    ) -> Result<Option<Exercise>> {
+        use crate::schema::exercises::dsl::{challenge_id as challenge_id_col, created_at};
+
+        if step < 1 {
+            return Ok(None);
+        }
+
+        let exercise = exercises_table
+            .filter(challenge_id_col.eq(challenge_id))
+            .order(created_at.asc())
+            .offset(step - 1)
+            .first::<Exercise>(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error getting exercise: {}", e);
+                FailedToGetExercise(GetExerciseError(e))
+            })?;
+
+        Ok(exercise)
+    }
+
+    /// The step `exercise_id` stands for within its challenge, or `None` if
+    /// it isn't one of the challenge's exercises.
+    pub fn get_step(
+        connection: &mut PgConnection,
+        challenge_id: &Uuid,
+        exercise_id: &Uuid,
+    ) -> Result<Option<i64>> {
+        use crate::schema::exercises::dsl::{challenge_id as challenge_id_col, created_at, id};
+
+        let exercise_ids = exercises_table
+            .filter(challenge_id_col.eq(challenge_id))
+            .order(created_at.asc())
+            .select(id)
+            .load::<Uuid>(connection)
+            .map_err(|e| {
+                error!("Error getting exercises: {}", e);
+                FailedToGetExercise(GetExerciseError(e))
+            })?;
+
+        Ok(exercise_ids
+            .iter()
+            .position(|exercise| exercise == exercise_id)
+            .map(|index| index as i64 + 1))
+    }
 }
@@ -0,0 +1,140 @@
+use crate::schema::idempotency_keys::table as idempotency_keys_table;
+use crate::service::database::models::IdempotencyKey;
+use crate::shared::errors::{
+    CreateIdempotencyKeyError, DeleteIdempotencyKeyError, GetIdempotencyKeyError,
+    RepositoryError::{
+        FailedToCreateIdempotencyKey, FailedToDeleteIdempotencyKey, FailedToGetIdempotencyKey,
+        FailedToUpdateIdempotencyKey,
+    },
+    UpdateIdempotencyKeyError,
+};
+use anyhow::Result;
+use diesel::prelude::*;
+use log::error;
+use serde_json::Value;
+use uuid::Uuid;
+
+// Keys older than this are treated as expired and can be claimed again.
+pub const IDEMPOTENCY_KEY_TTL_HOURS: i64 = 24;
+// A claim without a response after this long was left behind by a request
+// that crashed or lost its connection, and can be claimed again.
+pub const IDEMPOTENCY_CLAIM_LEASE_MINUTES: i64 = 5;
+
+impl IdempotencyKey {
+    pub fn new(
+        user_id: &Uuid,
+        idempotency_key: &str,
+        request_path: &str,
+        request_body: Value,
+    ) -> Self {
+        IdempotencyKey {
+            id: Uuid::new_v4(),
+            user_id: user_id.to_owned(),
+            idempotency_key: idempotency_key.to_string(),
+            request_path: request_path.to_string(),
+            request_body,
+            response_status: None,
+            response_body: None,
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.created_at
+            < chrono::Utc::now().naive_utc() - chrono::Duration::hours(IDEMPOTENCY_KEY_TTL_HOURS)
+    }
+
+    /// Whether the key is still in flight past its lease.
+    pub fn is_abandoned(&self) -> bool {
+        self.response_status.is_none()
+            && self.created_at
+                < chrono::Utc::now().naive_utc()
+                    - chrono::Duration::minutes(IDEMPOTENCY_CLAIM_LEASE_MINUTES)
+    }
+
+    /// Inserts the key if no other request holds it yet.
+    /// Returns `None` when the key is already taken for this user.
+    pub fn claim(
+        connection: &mut PgConnection,
+        idempotency_key: IdempotencyKey,
+    ) -> Result<Option<IdempotencyKey>> {
+        let claimed = diesel::insert_into(idempotency_keys_table)
+            .values(idempotency_key)
+            .on_conflict_do_nothing()
+            .returning(IdempotencyKey::as_returning())
+            .get_result(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error creating idempotency key: {}", e);
+                FailedToCreateIdempotencyKey(CreateIdempotencyKeyError(e))
+            })?;
+
+        Ok(claimed)
+    }
+
+    pub fn get(
+        connection: &mut PgConnection,
+        user_id: &Uuid,
+        idempotency_key: &str,
+    ) -> Result<IdempotencyKey> {
+        use crate::schema::idempotency_keys::dsl::{
+            idempotency_key as idempotency_key_col, user_id as user_id_col,
+        };
+
+        let key = idempotency_keys_table
+            .filter(user_id_col.eq(user_id))
+            .filter(idempotency_key_col.eq(idempotency_key))
+            .select(IdempotencyKey::as_select())
+            .first::<IdempotencyKey>(connection)
+            .map_err(|e| {
+                error!("Error getting idempotency key: {}", e);
+                FailedToGetIdempotencyKey(GetIdempotencyKeyError(e))
+            })?;
+
+        Ok(key)
+    }
+
+    /// Stores the response of the request holding `claimed`. Returns `None`
+    /// when that claim lapsed and was released, or taken over by another
+    /// request, in the meantime.
+    pub fn complete(
+        connection: &mut PgConnection,
+        claimed: &IdempotencyKey,
+        response_status: i32,
+        response_body: &Value,
+    ) -> Result<Option<IdempotencyKey>> {
+        use crate::schema::idempotency_keys::dsl::{
+            created_at, response_body as response_body_col, response_status as response_status_col,
+        };
+
+        let key = diesel::update(
+            idempotency_keys_table
+                .find(claimed.id)
+                .filter(created_at.eq(claimed.created_at))
+                .filter(response_status_col.is_null()),
+        )
+        .set((
+            response_status_col.eq(response_status),
+            response_body_col.eq(response_body),
+        ))
+        .returning(IdempotencyKey::as_returning())
+        .get_result(connection)
+        .optional()
+        .map_err(|e| {
+            error!("Error updating idempotency key: {}", e);
+            FailedToUpdateIdempotencyKey(UpdateIdempotencyKeyError(e))
+        })?;
+
+        Ok(key)
+    }
+
+    pub fn release(connection: &mut PgConnection, id: &Uuid) -> Result<usize> {
+        diesel::delete(idempotency_keys_table.find(id))
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error deleting idempotency key: {}", e);
+                FailedToDeleteIdempotencyKey(DeleteIdempotencyKeyError(e)).into()
+            })
+    }
+}
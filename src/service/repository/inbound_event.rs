@@ -0,0 +1,264 @@
+use crate::schema::inbound_events::table as inbound_events_table;
+use crate::service::database::models::InboundEvent;
+use crate::shared::errors::{
+    CreateInboundEventError, GetInboundEventError,
+    RepositoryError::{
+        FailedToCreateInboundEvent, FailedToGetInboundEvent, FailedToUpdateInboundEvent,
+    },
+    UpdateInboundEventError,
+};
+use crate::shared::primitives::{InboundEventStatus, PaginatedResponse, PaginationParams};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{pg::Pg, prelude::*, sql_types::Bool};
+use log::error;
+use uuid::Uuid;
+
+// An event still `received` or `replaying` this long after it was last
+// updated was left behind by a process that crashed while handling it, and
+// can be replayed like a failed one.
+pub const INBOUND_EVENT_LEASE_MINUTES: i64 = 10;
+
+/// Narrows down inbound events, every field that is set has to match.
+#[derive(Default)]
+pub struct InboundEventFilter<'a> {
+    pub status: Option<&'a InboundEventStatus>,
+    /// Only events that can be replayed, see `claim_for_replay`.
+    pub replayable: bool,
+    pub queue: Option<&'a str>,
+    pub from: Option<NaiveDateTime>,
+    pub to: Option<NaiveDateTime>,
+}
+
+impl InboundEventFilter<'_> {
+    fn apply<'q>(
+        &self,
+        query: crate::schema::inbound_events::BoxedQuery<'q, Pg>,
+    ) -> crate::schema::inbound_events::BoxedQuery<'q, Pg> {
+        use crate::schema::inbound_events::dsl::{queue, received_at, status};
+
+        let mut query = query;
+        if let Some(event_status) = self.status {
+            query = query.filter(status.eq(event_status.to_str()));
+        }
+        if self.replayable {
+            query = query.filter(is_replayable());
+        }
+        if let Some(event_queue) = self.queue {
+            query = query.filter(queue.eq(event_queue.to_string()));
+        }
+        if let Some(from) = self.from {
+            query = query.filter(received_at.ge(from));
+        }
+        if let Some(to) = self.to {
+            query = query.filter(received_at.lt(to));
+        }
+        query
+    }
+}
+
+/// Failed events, and those stuck in `received` or `replaying` past
+/// `INBOUND_EVENT_LEASE_MINUTES`.
+fn is_replayable(
+) -> Box<dyn BoxableExpression<crate::schema::inbound_events::table, Pg, SqlType = Bool>> {
+    use crate::schema::inbound_events::dsl::{status, updated_at};
+
+    let lease_cutoff =
+        chrono::Utc::now().naive_utc() - chrono::Duration::minutes(INBOUND_EVENT_LEASE_MINUTES);
+    Box::new(
+        status.eq(InboundEventStatus::Failed.to_str()).or(status
+            .eq_any([
+                InboundEventStatus::Received.to_str(),
+                InboundEventStatus::Replaying.to_str(),
+            ])
+            .and(updated_at.lt(lease_cutoff))),
+    )
+}
+
+impl InboundEvent {
+    pub fn new(queue: &str, message_id: &str, payload: &str) -> Self {
+        InboundEvent {
+            id: Uuid::new_v4(),
+            message_id: message_id.to_string(),
+            queue: queue.to_string(),
+            payload: payload.to_string(),
+            status: InboundEventStatus::Received.to_str().to_string(),
+            error: None,
+            attempts: 1,
+            received_at: chrono::Utc::now().naive_utc(),
+            processed_at: None,
+            updated_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    /// Stores a delivered message, or counts another attempt on the event
+    /// stored for an earlier delivery of it. Returns `None` when that event
+    /// doesn't need processing again: it was processed already, or a replay
+    /// is working on it.
+    pub fn record_delivery(
+        connection: &mut PgConnection,
+        event: InboundEvent,
+    ) -> Result<Option<InboundEvent>> {
+        use crate::schema::inbound_events::dsl::{
+            attempts, error as error_col, message_id, status,
+        };
+        // filters the update of a conflicting event rather than a query
+        use diesel::query_dsl::methods::FilterDsl;
+
+        let event = diesel::insert_into(inbound_events_table)
+            .values(event)
+            .on_conflict(message_id)
+            .do_update()
+            .set((
+                status.eq(InboundEventStatus::Received.to_str()),
+                error_col.eq(None::<String>),
+                attempts.eq(attempts + 1),
+            ))
+            .filter(status.eq_any([
+                InboundEventStatus::Received.to_str(),
+                InboundEventStatus::Failed.to_str(),
+            ]))
+            .returning(InboundEvent::as_returning())
+            .get_result(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error creating inbound event: {}", e);
+                FailedToCreateInboundEvent(CreateInboundEventError(e))
+            })?;
+
+        Ok(event)
+    }
+
+    pub fn get(connection: &mut PgConnection, id: &Uuid) -> Result<InboundEvent> {
+        let event = inbound_events_table
+            .find(id)
+            .select(InboundEvent::as_select())
+            .first::<InboundEvent>(connection)
+            .map_err(|e| {
+                error!("Error getting inbound event: {}", e);
+                FailedToGetInboundEvent(GetInboundEventError(e))
+            })?;
+
+        Ok(event)
+    }
+
+    /// Events matching `filter`, newest first.
+    pub fn list(
+        connection: &mut PgConnection,
+        filter: &InboundEventFilter,
+        pagination: &PaginationParams,
+    ) -> Result<PaginatedResponse<InboundEvent>> {
+        use crate::schema::inbound_events::dsl::received_at;
+
+        let page = pagination.page.unwrap_or(1);
+        let per_page = pagination.per_page.unwrap_or(10);
+        let offset = (page - 1) * per_page;
+
+        let total: i64 = filter
+            .apply(inbound_events_table.into_boxed())
+            .count()
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error counting inbound events: {}", e);
+                FailedToGetInboundEvent(GetInboundEventError(e))
+            })?;
+        let total_pages = (total as f64 / per_page as f64).ceil() as i64;
+
+        let events = filter
+            .apply(inbound_events_table.into_boxed())
+            .order(received_at.desc())
+            .offset(offset)
+            .limit(per_page)
+            .select(InboundEvent::as_select())
+            .load::<InboundEvent>(connection)
+            .map_err(|e| {
+                error!("Error getting inbound events: {}", e);
+                FailedToGetInboundEvent(GetInboundEventError(e))
+            })?;
+
+        Ok(PaginatedResponse {
+            data: events,
+            total,
+            page,
+            per_page,
+            total_pages,
+        })
+    }
+
+    /// Ids of the events matching `filter`, oldest first.
+    pub fn get_ids(
+        connection: &mut PgConnection,
+        filter: &InboundEventFilter,
+        limit: i64,
+    ) -> Result<Vec<Uuid>> {
+        use crate::schema::inbound_events::dsl::{id, received_at};
+
+        let ids = filter
+            .apply(inbound_events_table.into_boxed())
+            .order(received_at.asc())
+            .limit(limit)
+            .select(id)
+            .load::<Uuid>(connection)
+            .map_err(|e| {
+                error!("Error getting inbound events: {}", e);
+                FailedToGetInboundEvent(GetInboundEventError(e))
+            })?;
+
+        Ok(ids)
+    }
+
+    /// Moves a failed or stalled event to `replaying`. Returns `None` when
+    /// the event can't be replayed, e.g. because another replay already
+    /// picked it up, so that an event is never processed twice by
+    /// concurrent replays.
+    pub fn claim_for_replay(
+        connection: &mut PgConnection,
+        event_id: &Uuid,
+    ) -> Result<Option<InboundEvent>> {
+        use crate::schema::inbound_events::dsl::{attempts, status};
+
+        let event = diesel::update(inbound_events_table.find(event_id).filter(is_replayable()))
+            .set((
+                status.eq(InboundEventStatus::Replaying.to_str()),
+                attempts.eq(attempts + 1),
+            ))
+            .returning(InboundEvent::as_returning())
+            .get_result(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error updating inbound event: {}", e);
+                FailedToUpdateInboundEvent(UpdateInboundEventError(e))
+            })?;
+
+        Ok(event)
+    }
+
+    /// Records the outcome of processing an event, `error` is set when it
+    /// failed.
+    pub fn finish(
+        connection: &mut PgConnection,
+        event_id: &Uuid,
+        failure: Option<&str>,
+    ) -> Result<InboundEvent> {
+        use crate::schema::inbound_events::dsl::{error as error_col, processed_at, status};
+
+        let outcome = match failure {
+            Some(_) => InboundEventStatus::Failed,
+            None => InboundEventStatus::Processed,
+        };
+        let event = diesel::update(inbound_events_table.find(event_id))
+            .set((
+                status.eq(outcome.to_str()),
+                error_col.eq(failure),
+                processed_at.eq(chrono::Utc::now().naive_utc()),
+            ))
+            .returning(InboundEvent::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error updating inbound event: {}", e);
+                FailedToUpdateInboundEvent(UpdateInboundEventError(e))
+            })?;
+
+        Ok(event)
+    }
+}
@@ -0,0 +1,48 @@
+use crate::schema::orphaned_remotes::table as orphaned_remotes_table;
+use crate::service::database::models::OrphanedRemote;
+use crate::shared::errors::{
+    RepositoryError::FailedToUpdateOrphanedRemotes, UpdateOrphanedRemoteError,
+};
+use anyhow::Result;
+use diesel::prelude::*;
+use log::error;
+
+impl OrphanedRemote {
+    /// Records the remotes orphaned now and forgets those that no longer
+    /// are. Returns every orphaned remote with when it was first seen.
+    pub fn record_sightings(
+        connection: &mut PgConnection,
+        repo_names: &[String],
+    ) -> Result<Vec<OrphanedRemote>> {
+        use crate::schema::orphaned_remotes::dsl::repo_name;
+
+        let now = chrono::Utc::now().naive_utc();
+        let sightings: Vec<OrphanedRemote> = repo_names
+            .iter()
+            .map(|name| OrphanedRemote {
+                repo_name: name.clone(),
+                first_seen_at: now,
+            })
+            .collect();
+
+        let remotes = connection
+            .transaction(|conn| {
+                diesel::delete(orphaned_remotes_table.filter(repo_name.ne_all(repo_names)))
+                    .execute(conn)?;
+                diesel::insert_into(orphaned_remotes_table)
+                    .values(&sightings)
+                    .on_conflict_do_nothing()
+                    .execute(conn)?;
+                orphaned_remotes_table
+                    .order(repo_name.asc())
+                    .select(OrphanedRemote::as_select())
+                    .load::<OrphanedRemote>(conn)
+            })
+            .map_err(|e| {
+                error!("Error recording orphaned remotes: {}", e);
+                FailedToUpdateOrphanedRemotes(UpdateOrphanedRemoteError(e))
+            })?;
+
+        Ok(remotes)
+    }
+}
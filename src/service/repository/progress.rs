@@ -1,8 +1,10 @@
 use crate::schema::progress::table as progress_table;
 use crate::service::database::models::Progress;
 use crate::shared::errors::{
-    CreateProgressError, GetProgressError,
-    RepositoryError::{FailedToCreateProgress, FailedToGetProgress, FailedToUpdateProgress},
+    CreateProgressError, DeleteProgressError, GetProgressError,
+    RepositoryError::{
+        FailedToCreateProgress, FailedToDeleteProgress, FailedToGetProgress, FailedToUpdateProgress,
+    },
     UpdateProgressError,
 };
 use crate::shared::primitives::Status;
@@ -151,4 +153,30 @@ impl Progress {
             _ => Err(anyhow::anyhow!("Invalid input")),
         }
     }
+
+    /// Gets the progress of a repository and locks the row until the
+    /// surrounding transaction ends.
+    pub fn get_for_update(connection: &mut PgConnection, repository_id: &Uuid) -> Result<Progress> {
+        use crate::schema::progress::dsl::repository_id as repository_id_col;
+
+        let progress = progress_table
+            .filter(repository_id_col.eq(repository_id))
+            .select(Progress::as_select())
+            .for_update()
+            .first::<Progress>(connection)
+            .map_err(|e| {
+                error!("Error getting progress: {}", e);
+                FailedToGetProgress(GetProgressError(e))
+            })?;
+        Ok(progress)
+    }
+
+    pub fn delete(connection: &mut PgConnection, id: &Uuid) -> Result<usize> {
+        diesel::delete(progress_table.find(id))
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error deleting progress: {}", e);
+                FailedToDeleteProgress(DeleteProgressError(e)).into()
+            })
+    }
 }
@@ -0,0 +1,50 @@
+use crate::schema::progress_archives::table as progress_archives_table;
+use crate::service::database::models::{Progress, ProgressArchive};
+use crate::shared::errors::{
+    CreateProgressArchiveError, RepositoryError::FailedToCreateProgressArchive,
+};
+use crate::shared::primitives::ArchiveReason;
+use anyhow::Result;
+use diesel::prelude::*;
+use log::error;
+use uuid::Uuid;
+
+impl ProgressArchive {
+    pub fn new(
+        progress: &Progress,
+        language: &str,
+        reason: ArchiveReason,
+        score_earned: i32,
+    ) -> Self {
+        ProgressArchive {
+            id: Uuid::new_v4(),
+            progress_id: progress.id,
+            user_id: progress.user_id,
+            challenge_id: progress.challenge_id,
+            repository_id: progress.repository_id,
+            language: language.to_string(),
+            status: progress.status.clone(),
+            progress_details: progress.progress_details.clone(),
+            score_earned,
+            reason: reason.to_str().to_string(),
+            started_at: progress.created_at,
+            archived_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn create(
+        connection: &mut PgConnection,
+        archive: ProgressArchive,
+    ) -> Result<ProgressArchive> {
+        let archive = archive
+            .insert_into(progress_archives_table)
+            .returning(ProgressArchive::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error creating progress archive: {}", e);
+                FailedToCreateProgressArchive(CreateProgressArchiveError(e))
+            })?;
+
+        Ok(archive)
+    }
+}
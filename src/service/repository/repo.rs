@@ -1,12 +1,17 @@
 use crate::schema::repositories::table as repositories;
 use crate::service::database::models::{
-    AttemptInfo, ChallengeInfo, ProgressInfo, Repository, RepositoryWithRelations,
+    ActivityInfo, AttemptInfo, ChallengeInfo, Progress, ProgressInfo, Repository, RepositoryPush,
+    RepositoryWithRelations,
 };
 use crate::shared::errors::{
-    CreateRepositoryError, GetRepositoryError,
-    RepositoryError::{FailedToCreateRepository, FailedToGetRepository},
+    CreateRepositoryError, DeleteRepositoryError, GetRepositoryError,
+    RepositoryError::{
+        FailedToCreateRepository, FailedToDeleteRepository, FailedToGetRepository,
+        FailedToUpdateRepository,
+    },
+    UpdateRepositoryError,
 };
-use crate::shared::primitives::{PaginatedResponse, PaginationParams, Period, Status};
+use crate::shared::primitives::{AttemptSort, PaginatedResponse, PaginationParams, Period, Status};
 use anyhow::Result;
 use chrono::NaiveDateTime;
 use diesel::prelude::*;
@@ -30,6 +35,12 @@ impl Repository {
             language: language.to_string(),
             created_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
+            last_pushed_at: None,
+            last_commit_sha: None,
+            last_push_branch: None,
+            push_count: 0,
+            force_push_count: 0,
+            remote_deleted_at: None,
         }
     }
 
@@ -46,6 +57,82 @@ impl Repository {
         Ok(repo)
     }
 
+    /// Points the repository at its remote, e.g. after the remote was
+    /// recreated, which also clears `remote_deleted_at`.
+    pub fn update_urls(
+        connection: &mut PgConnection,
+        id: &Uuid,
+        repo_url: &str,
+        soft_serve_url: &str,
+    ) -> Result<Repository> {
+        use crate::schema::repositories::dsl::{
+            remote_deleted_at, repo_url as repo_url_col, soft_serve_url as soft_serve_url_col,
+        };
+
+        let repo = diesel::update(repositories.find(id))
+            .set((
+                repo_url_col.eq(repo_url),
+                soft_serve_url_col.eq(soft_serve_url),
+                remote_deleted_at.eq(None::<NaiveDateTime>),
+            ))
+            .returning(Repository::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error updating repository: {}", e);
+                FailedToUpdateRepository(UpdateRepositoryError(e))
+            })?;
+
+        Ok(repo)
+    }
+
+    /// Moves the activity summary of a repository forward to `push`.
+    pub fn record_push(connection: &mut PgConnection, push: &RepositoryPush) -> Result<Repository> {
+        use crate::schema::repositories::dsl::{
+            force_push_count, last_commit_sha, last_push_branch, last_pushed_at, push_count,
+        };
+
+        let repo = diesel::update(repositories.find(&push.repository_id))
+            .set((
+                last_pushed_at.eq(push.pushed_at),
+                last_commit_sha.eq(&push.commit_sha),
+                last_push_branch.eq(&push.branch),
+                push_count.eq(push_count + 1),
+                force_push_count.eq(force_push_count + i32::from(push.forced)),
+            ))
+            .returning(Repository::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error updating repository activity: {}", e);
+                FailedToUpdateRepository(UpdateRepositoryError(e))
+            })?;
+
+        Ok(repo)
+    }
+
+    pub fn mark_remote_deleted(connection: &mut PgConnection, id: &Uuid) -> Result<Repository> {
+        use crate::schema::repositories::dsl::remote_deleted_at;
+
+        let repo = diesel::update(repositories.find(id))
+            .set(remote_deleted_at.eq(chrono::Utc::now().naive_utc()))
+            .returning(Repository::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error updating repository: {}", e);
+                FailedToUpdateRepository(UpdateRepositoryError(e))
+            })?;
+
+        Ok(repo)
+    }
+
+    pub fn delete(connection: &mut PgConnection, id: &Uuid) -> Result<usize> {
+        diesel::delete(repositories.find(id))
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error deleting repository: {}", e);
+                FailedToDeleteRepository(DeleteRepositoryError(e)).into()
+            })
+    }
+
     pub fn get_repo(
         connection: &mut PgConnection,
         id: Option<&Uuid>,
@@ -137,6 +224,41 @@ impl Repository {
         }
     }
 
+    pub fn get_all_with_owner(connection: &mut PgConnection) -> Result<Vec<(Repository, String)>> {
+        use crate::schema::{repositories, users};
+
+        let repos = repositories::table
+            .inner_join(users::table)
+            .select((Repository::as_select(), users::username))
+            .load::<(Repository, String)>(connection)
+            .map_err(|e| {
+                error!("Error getting repositories: {}", e);
+                FailedToGetRepository(GetRepositoryError(e))
+            })?;
+
+        Ok(repos)
+    }
+
+    /// Every attempt at a challenge together with its progress.
+    pub fn get_by_challenge_with_progress(
+        connection: &mut PgConnection,
+        challenge_id: &Uuid,
+    ) -> Result<Vec<(Repository, Progress)>> {
+        use crate::schema::{progress, repositories::dsl::challenge_id as challenge_id_col};
+
+        let repos = repositories
+            .inner_join(progress::table)
+            .filter(challenge_id_col.eq(challenge_id))
+            .select((Repository::as_select(), Progress::as_select()))
+            .load::<(Repository, Progress)>(connection)
+            .map_err(|e| {
+                error!("Error getting repositories: {}", e);
+                FailedToGetRepository(GetRepositoryError(e))
+            })?;
+
+        Ok(repos)
+    }
+
     pub fn get_repo_with_relations(
         connection: &mut PgConnection,
         user_id: &Uuid,
@@ -232,6 +354,14 @@ impl Repository {
                 NaiveDateTime,
                 NaiveDateTime,
             )>,
+            (
+                Option<NaiveDateTime>,
+                Option<String>,
+                Option<String>,
+                i32,
+                i32,
+                Option<NaiveDateTime>,
+            ),
         )> = results_query
             .offset(offset)
             .limit(per_page)
@@ -264,41 +394,59 @@ impl Repository {
                     progress::updated_at,
                 )
                     .nullable(),
+                (
+                    repositories::last_pushed_at,
+                    repositories::last_commit_sha,
+                    repositories::last_push_branch,
+                    repositories::push_count,
+                    repositories::force_push_count,
+                    repositories::remote_deleted_at,
+                ),
             ))
             .load(connection)?;
 
         Ok(PaginatedResponse {
             data: results
                 .into_iter()
-                .map(|(repo, challenge, progress)| RepositoryWithRelations {
-                    id: repo.0,
-                    user_id: repo.1,
-                    challenge_id: repo.2,
-                    repo_url: repo.3,
-                    soft_serve_url: repo.4,
-                    language: repo.5,
-                    created_at: repo.6,
-                    updated_at: repo.7,
-                    challenge: ChallengeInfo {
-                        title: challenge.0,
-                        description: challenge.1,
-                        repo_urls: challenge.2,
-                        difficulty: challenge.3,
-                        module_count: challenge.4,
-                        mode: challenge.5,
-                        created_at: challenge.6,
-                        updated_at: challenge.7,
+                .map(
+                    |(repo, challenge, progress, activity)| RepositoryWithRelations {
+                        id: repo.0,
+                        user_id: repo.1,
+                        challenge_id: repo.2,
+                        repo_url: repo.3,
+                        soft_serve_url: repo.4,
+                        language: repo.5,
+                        created_at: repo.6,
+                        updated_at: repo.7,
+                        challenge: ChallengeInfo {
+                            title: challenge.0,
+                            description: challenge.1,
+                            repo_urls: challenge.2,
+                            difficulty: challenge.3,
+                            module_count: challenge.4,
+                            mode: challenge.5,
+                            created_at: challenge.6,
+                            updated_at: challenge.7,
+                        },
+                        progress: progress
+                            .map(|p| ProgressInfo {
+                                id: p.0,
+                                status: p.1,
+                                progress_details: p.2,
+                                created_at: p.3,
+                                updated_at: p.4,
+                            })
+                            .unwrap_or_default(),
+                        activity: ActivityInfo {
+                            last_pushed_at: activity.0,
+                            last_commit_sha: activity.1,
+                            last_push_branch: activity.2,
+                            push_count: activity.3,
+                            force_push_count: activity.4,
+                            remote_deleted_at: activity.5,
+                        },
                     },
-                    progress: progress
-                        .map(|p| ProgressInfo {
-                            id: p.0,
-                            status: p.1,
-                            progress_details: p.2,
-                            created_at: p.3,
-                            updated_at: p.4,
-                        })
-                        .unwrap_or_default(),
-                })
+                )
                 .collect(),
             total,
             page,
@@ -340,6 +488,14 @@ impl Repository {
                 NaiveDateTime,
                 NaiveDateTime,
             )>,
+            (
+                Option<NaiveDateTime>,
+                Option<String>,
+                Option<String>,
+                i32,
+                i32,
+                Option<NaiveDateTime>,
+            ),
         ) = repositories::table
             .inner_join(challenges::table)
             .left_join(progress::table.on(progress::repository_id.eq(repositories::id)))
@@ -372,6 +528,14 @@ impl Repository {
                     progress::updated_at,
                 )
                     .nullable(),
+                (
+                    repositories::last_pushed_at,
+                    repositories::last_commit_sha,
+                    repositories::last_push_branch,
+                    repositories::push_count,
+                    repositories::force_push_count,
+                    repositories::remote_deleted_at,
+                ),
             ))
             .first(connection)?;
 
@@ -405,6 +569,14 @@ impl Repository {
                     updated_at: p.4,
                 })
                 .unwrap_or_default(),
+            activity: ActivityInfo {
+                last_pushed_at: result.10 .0,
+                last_commit_sha: result.10 .1,
+                last_push_branch: result.10 .2,
+                push_count: result.10 .3,
+                force_push_count: result.10 .4,
+                remote_deleted_at: result.10 .5,
+            },
         })
     }
 
@@ -412,6 +584,7 @@ impl Repository {
         connection: &mut PgConnection,
         period: &Period,
         challenge_id: &Uuid,
+        sort: Option<&AttemptSort>,
     ) -> Result<Vec<AttemptInfo>> {
         use crate::schema::{challenges, progress, repositories, users};
         use chrono::{Datelike, Duration, Utc};
@@ -440,7 +613,7 @@ impl Repository {
                 .naive_utc(),
         };
 
-        let results = repositories::table
+        let mut query = repositories::table
             .inner_join(challenges::table)
             .inner_join(users::table)
             .left_join(progress::table.on(progress::repository_id.eq(repositories::id)))
@@ -452,13 +625,40 @@ impl Repository {
                 progress::progress_details.nullable(),
                 challenges::module_count,
                 repositories::language,
+                repositories::last_pushed_at,
+                repositories::push_count,
             ))
-            .load::<(Uuid, String, Option<serde_json::Value>, i32, String)>(connection)?;
+            .into_boxed();
+        query = match sort {
+            Some(AttemptSort::RecentActivity) => {
+                query.order(repositories::last_pushed_at.desc().nulls_last())
+            }
+            Some(AttemptSort::PushCount) => query.order(repositories::push_count.desc()),
+            None => query,
+        };
+
+        let results = query.load::<(
+            Uuid,
+            String,
+            Option<serde_json::Value>,
+            i32,
+            String,
+            Option<NaiveDateTime>,
+            i32,
+        )>(connection)?;
 
         Ok(results
             .into_iter()
             .map(
-                |(challenge_id, username, progress_details, module_count, language)| {
+                |(
+                    challenge_id,
+                    username,
+                    progress_details,
+                    module_count,
+                    language,
+                    last_pushed_at,
+                    push_count,
+                )| {
                     let total_score = progress_details
                         .and_then(|details| details.get("current_step").cloned())
                         .and_then(|step| step.as_i64())
@@ -470,6 +670,8 @@ impl Repository {
                         total_score,
                         module_count,
                         language,
+                        last_pushed_at,
+                        push_count,
                     }
                 },
             )
@@ -0,0 +1,109 @@
+use crate::schema::repository_pushes::table as repository_pushes_table;
+use crate::service::database::models::RepositoryPush;
+use crate::shared::errors::{
+    CreateRepositoryPushError, DeleteRepositoryPushError, GetRepositoryPushError,
+    RepositoryError::{
+        FailedToCreateRepositoryPush, FailedToDeleteRepositoryPush, FailedToGetRepositoryPush,
+    },
+};
+use crate::shared::primitives::{PaginatedResponse, PaginationParams, RepositoryEventKind};
+use anyhow::Result;
+use diesel::prelude::*;
+use log::error;
+use uuid::Uuid;
+
+impl RepositoryPush {
+    pub fn new(repository_id: &Uuid, commit_sha: &str, branch: Option<&str>) -> Self {
+        RepositoryPush {
+            id: Uuid::new_v4(),
+            repository_id: repository_id.to_owned(),
+            commit_sha: commit_sha.to_string(),
+            branch: branch.map(|branch| branch.to_string()),
+            pushed_at: chrono::Utc::now().naive_utc(),
+            kind: RepositoryEventKind::Push.to_str().to_string(),
+            forced: false,
+            before_sha: None,
+            tag: None,
+            message_id: None,
+        }
+    }
+
+    /// Inserts the push unless one was recorded for the same repository from
+    /// the same queue message already. Returns `None` for such a duplicate.
+    pub fn create(
+        connection: &mut PgConnection,
+        push: RepositoryPush,
+    ) -> Result<Option<RepositoryPush>> {
+        let push = push
+            .insert_into(repository_pushes_table)
+            .on_conflict_do_nothing()
+            .returning(RepositoryPush::as_returning())
+            .get_result(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error creating repository push: {}", e);
+                FailedToCreateRepositoryPush(CreateRepositoryPushError(e))
+            })?;
+
+        Ok(push)
+    }
+
+    /// Pushes of a repository, newest first.
+    pub fn get_by_repository(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+        pagination: &PaginationParams,
+    ) -> Result<PaginatedResponse<RepositoryPush>> {
+        use crate::schema::repository_pushes::dsl::{
+            pushed_at, repository_id as repository_id_col,
+        };
+
+        let page = pagination.page.unwrap_or(1);
+        let per_page = pagination.per_page.unwrap_or(10);
+        let offset = (page - 1) * per_page;
+
+        let total: i64 = repository_pushes_table
+            .filter(repository_id_col.eq(repository_id))
+            .count()
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error counting repository pushes: {}", e);
+                FailedToGetRepositoryPush(GetRepositoryPushError(e))
+            })?;
+        let total_pages = (total as f64 / per_page as f64).ceil() as i64;
+
+        let pushes = repository_pushes_table
+            .filter(repository_id_col.eq(repository_id))
+            .order(pushed_at.desc())
+            .offset(offset)
+            .limit(per_page)
+            .select(RepositoryPush::as_select())
+            .load::<RepositoryPush>(connection)
+            .map_err(|e| {
+                error!("Error getting repository pushes: {}", e);
+                FailedToGetRepositoryPush(GetRepositoryPushError(e))
+            })?;
+
+        Ok(PaginatedResponse {
+            data: pushes,
+            total,
+            page,
+            per_page,
+            total_pages,
+        })
+    }
+
+    pub fn delete_by_repository(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+    ) -> Result<usize> {
+        use crate::schema::repository_pushes::dsl::repository_id as repository_id_col;
+
+        diesel::delete(repository_pushes_table.filter(repository_id_col.eq(repository_id)))
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error deleting repository pushes: {}", e);
+                FailedToDeleteRepositoryPush(DeleteRepositoryPushError(e)).into()
+            })
+    }
+}
@@ -1,10 +1,14 @@
 use crate::schema::submissions::table as submissions_table;
 use crate::service::database::models::Submission;
 use crate::shared::errors::{
-    CreateSubmissionError, GetSubmissionError,
-    RepositoryError::{FailedToCreateSubmission, FailedToGetSubmission},
+    CreateSubmissionError, DeleteSubmissionError, GetSubmissionError,
+    RepositoryError::{
+        FailedToCreateSubmission, FailedToDeleteSubmission, FailedToGetSubmission,
+        FailedToUpdateSubmission,
+    },
+    UpdateSubmissionError,
 };
-use crate::shared::primitives::SubmissionStatus;
+use crate::shared::primitives::{PaginatedResponse, PaginationParams, SubmissionStatus};
 use crate::shared::utils::string_to_uuid;
 use anyhow::Result;
 use diesel::prelude::*;
@@ -13,22 +17,28 @@ use uuid::Uuid;
 
 impl Submission {
     pub fn new(
-        exercise_id: &str,
-        user_id: &str,
+        exercise_id: Option<&Uuid>,
+        user_id: &Uuid,
         status: SubmissionStatus,
-        repository_id: &str,
+        repository_id: &Uuid,
         commit_id: &str,
+        step: Option<i32>,
+        feedback: Option<&str>,
     ) -> Self {
         Submission {
             id: Uuid::new_v4(),
-            exercise_id: string_to_uuid(exercise_id).unwrap(),
-            user_id: string_to_uuid(user_id).unwrap(),
+            exercise_id: exercise_id.copied(),
+            user_id: user_id.to_owned(),
             status: status.to_str().to_string(),
-            repository_id: string_to_uuid(repository_id).unwrap(),
+            repository_id: repository_id.to_owned(),
             commit_id: commit_id.to_string(),
-            feedback: None,
+            feedback: feedback.map(|feedback| feedback.to_string()),
             submitted_at: chrono::Utc::now().naive_utc(),
             updated_at: chrono::Utc::now().naive_utc(),
+            step,
+            flagged_reason: None,
+            test_run_id: None,
+            delivery_key: None,
         }
     }
 
@@ -47,6 +57,55 @@ impl Submission {
         Ok(submission)
     }
 
+    /// Inserts the submission unless a passed one that was applied already
+    /// exists for the same repository, commit and delivery key. Returns
+    /// `None` for such a duplicate.
+    pub fn create_if_absent(
+        connection: &mut PgConnection,
+        submission: Submission,
+    ) -> Result<Option<Submission>> {
+        // the unique index is on an expression, so the conflict target
+        // can't be spelled out here
+        let submission = submission
+            .insert_into(submissions_table)
+            .on_conflict_do_nothing()
+            .returning(Submission::as_returning())
+            .get_result(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error creating submission: {}", e);
+                FailedToCreateSubmission(CreateSubmissionError(e))
+            })?;
+        Ok(submission)
+    }
+
+    /// Flags the passed submissions of a repository that were applied, so
+    /// that they no longer count as applied and the same results can move a
+    /// new run of the attempt forward. Returns the number of submissions
+    /// flagged.
+    pub fn flag_applied_passes(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+        reason: &str,
+    ) -> Result<usize> {
+        use crate::schema::submissions::dsl::{
+            flagged_reason, repository_id as repository_id_col, status,
+        };
+
+        diesel::update(
+            submissions_table
+                .filter(repository_id_col.eq(repository_id))
+                .filter(status.eq(SubmissionStatus::Passed.to_str()))
+                .filter(flagged_reason.is_null()),
+        )
+        .set(flagged_reason.eq(reason))
+        .execute(connection)
+        .map_err(|e| {
+            error!("Error flagging submissions: {}", e);
+            FailedToUpdateSubmission(UpdateSubmissionError(e)).into()
+        })
+    }
+
     pub fn get_submission(
         connection: &mut PgConnection,
         id: Option<String>,
@@ -94,4 +153,61 @@ impl Submission {
             _ => Err(anyhow::anyhow!("Invalid input")),
         }
     }
+
+    /// Submissions of a repository, newest first.
+    pub fn get_by_repository(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+        pagination: &PaginationParams,
+    ) -> Result<PaginatedResponse<Submission>> {
+        use crate::schema::submissions::dsl::{repository_id as repository_id_col, submitted_at};
+
+        let page = pagination.page.unwrap_or(1);
+        let per_page = pagination.per_page.unwrap_or(10);
+        let offset = (page - 1) * per_page;
+
+        let total: i64 = submissions_table
+            .filter(repository_id_col.eq(repository_id))
+            .count()
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error counting submissions: {}", e);
+                FailedToGetSubmission(GetSubmissionError(e))
+            })?;
+        let total_pages = (total as f64 / per_page as f64).ceil() as i64;
+
+        let submissions = submissions_table
+            .filter(repository_id_col.eq(repository_id))
+            .order(submitted_at.desc())
+            .offset(offset)
+            .limit(per_page)
+            .select(Submission::as_select())
+            .load::<Submission>(connection)
+            .map_err(|e| {
+                error!("Error getting submissions: {}", e);
+                FailedToGetSubmission(GetSubmissionError(e))
+            })?;
+
+        Ok(PaginatedResponse {
+            data: submissions,
+            total,
+            page,
+            per_page,
+            total_pages,
+        })
+    }
+
+    pub fn delete_by_repository(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+    ) -> Result<usize> {
+        use crate::schema::submissions::dsl::repository_id as repository_id_col;
+
+        diesel::delete(submissions_table.filter(repository_id_col.eq(repository_id)))
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error deleting submissions: {}", e);
+                FailedToDeleteSubmission(DeleteSubmissionError(e)).into()
+            })
+    }
 }
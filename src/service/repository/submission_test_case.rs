@@ -0,0 +1,114 @@
+use crate::schema::submission_test_cases::table as submission_test_cases_table;
+use crate::service::database::models::{SubmissionTestCase, TestCaseFailureStats};
+use crate::shared::errors::{
+    CreateSubmissionTestCaseError, GetSubmissionTestCaseError,
+    RepositoryError::{FailedToCreateSubmissionTestCases, FailedToGetSubmissionTestCases},
+};
+use crate::shared::primitives::TestCaseStatus;
+use anyhow::Result;
+use diesel::prelude::*;
+use log::error;
+use uuid::Uuid;
+
+impl SubmissionTestCase {
+    pub fn new(
+        submission_id: &Uuid,
+        position: i32,
+        name: &str,
+        suite: Option<&str>,
+        status: TestCaseStatus,
+        duration_ms: Option<i32>,
+        failure_message: Option<&str>,
+    ) -> Self {
+        SubmissionTestCase {
+            id: Uuid::new_v4(),
+            submission_id: submission_id.to_owned(),
+            position,
+            name: name.to_string(),
+            suite: suite.map(|suite| suite.to_string()),
+            status: status.to_str().to_string(),
+            duration_ms,
+            failure_message: failure_message.map(|message| message.to_string()),
+            created_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    pub fn create_many(
+        connection: &mut PgConnection,
+        test_cases: Vec<SubmissionTestCase>,
+    ) -> Result<usize> {
+        diesel::insert_into(submission_test_cases_table)
+            .values(test_cases)
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error creating submission test cases: {}", e);
+                FailedToCreateSubmissionTestCases(CreateSubmissionTestCaseError(e)).into()
+            })
+    }
+
+    /// Test cases of a submission in the order the runner reported them.
+    pub fn get_by_submission(
+        connection: &mut PgConnection,
+        submission_id: &Uuid,
+    ) -> Result<Vec<SubmissionTestCase>> {
+        use crate::schema::submission_test_cases::dsl::{
+            position, submission_id as submission_id_col,
+        };
+
+        let test_cases = submission_test_cases_table
+            .filter(submission_id_col.eq(submission_id))
+            .order(position.asc())
+            .select(SubmissionTestCase::as_select())
+            .load::<SubmissionTestCase>(connection)
+            .map_err(|e| {
+                error!("Error getting submission test cases: {}", e);
+                FailedToGetSubmissionTestCases(GetSubmissionTestCaseError(e))
+            })?;
+
+        Ok(test_cases)
+    }
+
+    /// Test cases of a challenge that failed at least once, those failing
+    /// most often first. `step` narrows it down to a single step.
+    pub fn get_failure_stats(
+        connection: &mut PgConnection,
+        challenge_id: &Uuid,
+        step: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<TestCaseFailureStats>> {
+        use crate::schema::{repositories, submission_test_cases, submissions};
+        use diesel::dsl::{count_star, sql};
+        use diesel::sql_types::{BigInt, Bool, Nullable, Timestamp};
+
+        let failures = "COUNT(*) FILTER (WHERE submission_test_cases.status = 'failed')";
+        let mut query = submission_test_cases::table
+            .inner_join(submissions::table.inner_join(repositories::table))
+            .filter(repositories::challenge_id.eq(challenge_id))
+            .group_by((submission_test_cases::suite, submission_test_cases::name))
+            .select((
+                submission_test_cases::suite,
+                submission_test_cases::name,
+                count_star(),
+                sql::<BigInt>(failures),
+                sql::<Nullable<Timestamp>>(
+                    "MAX(submission_test_cases.created_at) FILTER (WHERE submission_test_cases.status = 'failed')",
+                ),
+            ))
+            .having(sql::<Bool>(&format!("{} > 0", failures)))
+            .order(sql::<BigInt>(&format!("{} DESC", failures)))
+            .limit(limit)
+            .into_boxed();
+        if let Some(step) = step {
+            query = query.filter(submissions::step.eq(step));
+        }
+
+        let stats = query
+            .load::<TestCaseFailureStats>(connection)
+            .map_err(|e| {
+                error!("Error getting test case failure stats: {}", e);
+                FailedToGetSubmissionTestCases(GetSubmissionTestCaseError(e))
+            })?;
+
+        Ok(stats)
+    }
+}
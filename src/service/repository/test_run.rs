@@ -0,0 +1,316 @@
+use crate::schema::test_runs::table as test_runs_table;
+use crate::service::database::models::TestRun;
+use crate::shared::errors::{
+    CreateTestRunError, DeleteTestRunError, GetTestRunError,
+    RepositoryError::{
+        FailedToCreateTestRun, FailedToDeleteTestRun, FailedToGetTestRun, FailedToUpdateTestRun,
+    },
+    UpdateTestRunError,
+};
+use crate::shared::primitives::{PaginatedResponse, PaginationParams, TestRunStatus};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{
+    dsl::sql,
+    prelude::*,
+    sql_types::{Bool, Timestamp},
+};
+use log::error;
+use uuid::Uuid;
+
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::test_runs)]
+struct TestRunTransition {
+    status: String,
+    success: Option<bool>,
+    submission_id: Option<Uuid>,
+    started_at: Option<NaiveDateTime>,
+    finished_at: Option<NaiveDateTime>,
+}
+
+impl TestRun {
+    pub fn new(
+        id: &Uuid,
+        repository_id: &Uuid,
+        commit_sha: Option<&str>,
+        requested_by: Option<&Uuid>,
+    ) -> Self {
+        TestRun {
+            id: id.to_owned(),
+            repository_id: repository_id.to_owned(),
+            commit_sha: commit_sha.map(|sha| sha.to_string()),
+            status: TestRunStatus::Queued.to_str().to_string(),
+            success: None,
+            submission_id: None,
+            requested_by: requested_by.copied(),
+            queued_at: chrono::Utc::now().naive_utc(),
+            started_at: None,
+            finished_at: None,
+            updated_at: chrono::Utc::now().naive_utc(),
+            relayed_sequence: 0,
+        }
+    }
+
+    pub fn create(connection: &mut PgConnection, test_run: TestRun) -> Result<TestRun> {
+        let test_run = diesel::insert_into(test_runs_table)
+            .values(test_run)
+            .returning(TestRun::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error creating test run: {}", e);
+                FailedToCreateTestRun(CreateTestRunError(e))
+            })?;
+
+        Ok(test_run)
+    }
+
+    pub fn delete(connection: &mut PgConnection, test_run_id: &Uuid) -> Result<usize> {
+        diesel::delete(test_runs_table.find(test_run_id))
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error deleting test run: {}", e);
+                FailedToDeleteTestRun(DeleteTestRunError(e)).into()
+            })
+    }
+
+    pub fn get(connection: &mut PgConnection, test_run_id: &Uuid) -> Result<Option<TestRun>> {
+        let test_run = test_runs_table
+            .find(test_run_id)
+            .select(TestRun::as_select())
+            .first::<TestRun>(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error getting test run: {}", e);
+                FailedToGetTestRun(GetTestRunError(e))
+            })?;
+
+        Ok(test_run)
+    }
+
+    /// Locks the run until the end of the transaction, so that chunks of its
+    /// output are relayed by one consumer at a time.
+    pub fn get_for_update(
+        connection: &mut PgConnection,
+        test_run_id: &Uuid,
+    ) -> Result<TestRun> {
+        let test_run = test_runs_table
+            .find(test_run_id)
+            .for_update()
+            .select(TestRun::as_select())
+            .first::<TestRun>(connection)
+            .map_err(|e| {
+                error!("Error getting test run: {}", e);
+                FailedToGetTestRun(GetTestRunError(e))
+            })?;
+
+        Ok(test_run)
+    }
+
+    pub fn set_relayed_sequence(
+        connection: &mut PgConnection,
+        test_run_id: &Uuid,
+        sequence: i32,
+    ) -> Result<TestRun> {
+        use crate::schema::test_runs::dsl::relayed_sequence;
+
+        let test_run = diesel::update(test_runs_table.find(test_run_id))
+            .set(relayed_sequence.eq(sequence))
+            .returning(TestRun::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error updating test run: {}", e);
+                FailedToUpdateTestRun(UpdateTestRunError(e))
+            })?;
+
+        Ok(test_run)
+    }
+
+    /// The latest run of `commit_sha` that hasn't finished yet, for runner
+    /// events that don't carry the id of the run. Includes runs that timed
+    /// out, so that a late result still lands.
+    pub fn get_unfinished(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+        commit_sha: &str,
+    ) -> Result<Option<TestRun>> {
+        use crate::schema::test_runs::dsl::{
+            commit_sha as commit_sha_col, queued_at, repository_id as repository_id_col, status,
+        };
+
+        let test_run = test_runs_table
+            .filter(repository_id_col.eq(repository_id))
+            .filter(commit_sha_col.eq(commit_sha))
+            .filter(status.ne(TestRunStatus::Finished.to_str()))
+            .order(queued_at.desc())
+            .select(TestRun::as_select())
+            .first::<TestRun>(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error getting test run: {}", e);
+                FailedToGetTestRun(GetTestRunError(e))
+            })?;
+
+        Ok(test_run)
+    }
+
+    /// The latest run of `commit_sha` that is still queued or running. Unlike
+    /// `get_unfinished` it leaves out runs that timed out, which a new push
+    /// of the commit doesn't pick up again.
+    pub fn get_pending(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+        commit_sha: &str,
+    ) -> Result<Option<TestRun>> {
+        use crate::schema::test_runs::dsl::{
+            commit_sha as commit_sha_col, queued_at, repository_id as repository_id_col, status,
+        };
+
+        let test_run = test_runs_table
+            .filter(repository_id_col.eq(repository_id))
+            .filter(commit_sha_col.eq(commit_sha))
+            .filter(status.eq_any([
+                TestRunStatus::Queued.to_str(),
+                TestRunStatus::Running.to_str(),
+            ]))
+            .order(queued_at.desc())
+            .select(TestRun::as_select())
+            .first::<TestRun>(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error getting test run: {}", e);
+                FailedToGetTestRun(GetTestRunError(e))
+            })?;
+
+        Ok(test_run)
+    }
+
+    /// Test runs of a repository, newest first.
+    pub fn get_by_repository(
+        connection: &mut PgConnection,
+        repository_id: &Uuid,
+        pagination: &PaginationParams,
+    ) -> Result<PaginatedResponse<TestRun>> {
+        use crate::schema::test_runs::dsl::{queued_at, repository_id as repository_id_col};
+
+        let page = pagination.page.unwrap_or(1);
+        let per_page = pagination.per_page.unwrap_or(10);
+        let offset = (page - 1) * per_page;
+
+        let total: i64 = test_runs_table
+            .filter(repository_id_col.eq(repository_id))
+            .count()
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error counting test runs: {}", e);
+                FailedToGetTestRun(GetTestRunError(e))
+            })?;
+        let total_pages = (total as f64 / per_page as f64).ceil() as i64;
+
+        let test_runs = test_runs_table
+            .filter(repository_id_col.eq(repository_id))
+            .order(queued_at.desc())
+            .offset(offset)
+            .limit(per_page)
+            .select(TestRun::as_select())
+            .load::<TestRun>(connection)
+            .map_err(|e| {
+                error!("Error getting test runs: {}", e);
+                FailedToGetTestRun(GetTestRunError(e))
+            })?;
+
+        Ok(PaginatedResponse {
+            data: test_runs,
+            total,
+            page,
+            per_page,
+            total_pages,
+        })
+    }
+
+    /// Moves a run to `next` if its current status allows it, see
+    /// `TestRunStatus::reachable_from`. Returns `None` when it doesn't, so a
+    /// redelivered or late event leaves the run alone.
+    pub fn transition(
+        connection: &mut PgConnection,
+        test_run_id: &Uuid,
+        next: TestRunStatus,
+        success: Option<bool>,
+        submission_id: Option<Uuid>,
+    ) -> Result<Option<TestRun>> {
+        use crate::schema::test_runs::dsl::status;
+
+        let now = chrono::Utc::now().naive_utc();
+        let changes = TestRunTransition {
+            status: next.to_str().to_string(),
+            success,
+            submission_id,
+            started_at: (next == TestRunStatus::Running).then_some(now),
+            finished_at: matches!(next, TestRunStatus::Finished | TestRunStatus::TimedOut)
+                .then_some(now),
+        };
+        let allowed: Vec<&str> = next
+            .reachable_from()
+            .iter()
+            .map(|from| from.to_str())
+            .collect();
+
+        let test_run = diesel::update(
+            test_runs_table
+                .find(test_run_id)
+                .filter(status.eq_any(allowed)),
+        )
+        .set(changes)
+        .returning(TestRun::as_returning())
+        .get_result(connection)
+        .optional()
+        .map_err(|e| {
+            error!("Error updating test run: {}", e);
+            FailedToUpdateTestRun(UpdateTestRunError(e))
+        })?;
+
+        Ok(test_run)
+    }
+
+    /// Times out every run still queued or running that saw no activity
+    /// since `cutoff`: it wasn't queued, started or updated, and streamed no
+    /// output since then.
+    pub fn time_out_stale(
+        connection: &mut PgConnection,
+        cutoff: NaiveDateTime,
+    ) -> Result<Vec<TestRun>> {
+        use crate::schema::test_runs::dsl::{finished_at, status, updated_at};
+
+        let allowed: Vec<&str> = TestRunStatus::TimedOut
+            .reachable_from()
+            .iter()
+            .map(|from| from.to_str())
+            .collect();
+        let test_runs = diesel::update(
+            test_runs_table
+                .filter(status.eq_any(allowed))
+                // starting a run or relaying its output updates it as well
+                .filter(updated_at.lt(cutoff))
+                .filter(
+                    sql::<Bool>(
+                        "NOT EXISTS (SELECT 1 FROM test_run_output_chunks \
+                         WHERE test_run_output_chunks.test_run_id = test_runs.id \
+                         AND test_run_output_chunks.received_at >= ",
+                    )
+                    .bind::<Timestamp, _>(cutoff)
+                    .sql(")"),
+                ),
+        )
+        .set((
+            status.eq(TestRunStatus::TimedOut.to_str()),
+            finished_at.eq(chrono::Utc::now().naive_utc()),
+        ))
+        .returning(TestRun::as_returning())
+        .get_results(connection)
+        .map_err(|e| {
+            error!("Error timing out test runs: {}", e);
+            FailedToUpdateTestRun(UpdateTestRunError(e))
+        })?;
+
+        Ok(test_runs)
+    }
+}
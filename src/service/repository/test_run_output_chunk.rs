@@ -0,0 +1,66 @@
+use crate::schema::test_run_output_chunks::table as test_run_output_chunks_table;
+use crate::service::database::models::TestRunOutputChunk;
+use crate::shared::errors::{
+    CreateTestRunOutputChunkError, GetTestRunOutputChunkError,
+    RepositoryError::{FailedToCreateTestRunOutputChunk, FailedToGetTestRunOutputChunks},
+};
+use anyhow::Result;
+use diesel::prelude::*;
+use log::error;
+use uuid::Uuid;
+
+impl TestRunOutputChunk {
+    pub fn new(test_run_id: &Uuid, sequence: i32, output: &str) -> Self {
+        TestRunOutputChunk {
+            id: Uuid::new_v4(),
+            test_run_id: test_run_id.to_owned(),
+            sequence,
+            output: output.to_string(),
+            received_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    /// Stores a chunk unless one with the same sequence number was already
+    /// received, in which case `None` is returned.
+    pub fn create_if_absent(
+        connection: &mut PgConnection,
+        chunk: TestRunOutputChunk,
+    ) -> Result<Option<TestRunOutputChunk>> {
+        let chunk = diesel::insert_into(test_run_output_chunks_table)
+            .values(chunk)
+            .on_conflict_do_nothing()
+            .returning(TestRunOutputChunk::as_returning())
+            .get_result(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error creating test run output chunk: {}", e);
+                FailedToCreateTestRunOutputChunk(CreateTestRunOutputChunkError(e))
+            })?;
+
+        Ok(chunk)
+    }
+
+    /// Chunks of a run after `sequence`, in order.
+    pub fn get_after(
+        connection: &mut PgConnection,
+        test_run_id: &Uuid,
+        sequence: i32,
+    ) -> Result<Vec<TestRunOutputChunk>> {
+        use crate::schema::test_run_output_chunks::dsl::{
+            sequence as sequence_col, test_run_id as test_run_id_col,
+        };
+
+        let chunks = test_run_output_chunks_table
+            .filter(test_run_id_col.eq(test_run_id))
+            .filter(sequence_col.gt(sequence))
+            .order(sequence_col.asc())
+            .select(TestRunOutputChunk::as_select())
+            .load::<TestRunOutputChunk>(connection)
+            .map_err(|e| {
+                error!("Error getting test run output chunks: {}", e);
+                FailedToGetTestRunOutputChunks(GetTestRunOutputChunkError(e))
+            })?;
+
+        Ok(chunks)
+    }
+}
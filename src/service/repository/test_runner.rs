@@ -0,0 +1,80 @@
+use crate::schema::test_runners::table as test_runners_table;
+use crate::service::database::models::TestRunner;
+use crate::shared::errors::{
+    GetTestRunnerError,
+    RepositoryError::{FailedToGetTestRunners, FailedToUpdateTestRunner},
+    UpdateTestRunnerError,
+};
+use anyhow::Result;
+use diesel::prelude::*;
+use diesel::upsert::excluded;
+use log::error;
+
+impl TestRunner {
+    pub fn new(
+        id: &str,
+        queue: &str,
+        languages: &[String],
+        capacity: i32,
+        busy: i32,
+        version: Option<&str>,
+    ) -> Self {
+        TestRunner {
+            id: id.to_string(),
+            queue: queue.to_string(),
+            languages: serde_json::json!(languages),
+            capacity,
+            busy,
+            version: version.map(|version| version.to_string()),
+            registered_at: chrono::Utc::now().naive_utc(),
+            last_heartbeat_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    /// Registers a runner on its first heartbeat and refreshes what it
+    /// reported on every later one.
+    pub fn record_heartbeat(
+        connection: &mut PgConnection,
+        runner: TestRunner,
+    ) -> Result<TestRunner> {
+        use crate::schema::test_runners::dsl::{
+            busy, capacity, id, languages, last_heartbeat_at, queue, version,
+        };
+
+        let runner = diesel::insert_into(test_runners_table)
+            .values(runner)
+            .on_conflict(id)
+            .do_update()
+            .set((
+                queue.eq(excluded(queue)),
+                languages.eq(excluded(languages)),
+                capacity.eq(excluded(capacity)),
+                busy.eq(excluded(busy)),
+                version.eq(excluded(version)),
+                last_heartbeat_at.eq(excluded(last_heartbeat_at)),
+            ))
+            .returning(TestRunner::as_returning())
+            .get_result(connection)
+            .map_err(|e| {
+                error!("Error recording test runner heartbeat: {}", e);
+                FailedToUpdateTestRunner(UpdateTestRunnerError(e))
+            })?;
+
+        Ok(runner)
+    }
+
+    pub fn get_all(connection: &mut PgConnection) -> Result<Vec<TestRunner>> {
+        use crate::schema::test_runners::dsl::{id, queue};
+
+        let runners = test_runners_table
+            .order((queue.asc(), id.asc()))
+            .select(TestRunner::as_select())
+            .load::<TestRunner>(connection)
+            .map_err(|e| {
+                error!("Error getting test runners: {}", e);
+                FailedToGetTestRunners(GetTestRunnerError(e))
+            })?;
+
+        Ok(runners)
+    }
+}
@@ -0,0 +1,120 @@
+use crate::schema::websocket_events::table as websocket_events_table;
+use crate::service::database::models::{NewWebsocketEvent, WebsocketEvent};
+use crate::shared::errors::{
+    CreateWebsocketEventError, DeleteWebsocketEventError, GetWebsocketEventError,
+    RepositoryError::{
+        FailedToCreateWebsocketEvent, FailedToDeleteWebsocketEvents, FailedToGetWebsocketEvents,
+    },
+};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use diesel::{prelude::*, sql_types::BigInt};
+use log::error;
+use uuid::Uuid;
+
+// Advisory lock taken while an event is stored, see `WebsocketEvent::create`
+const EVENT_ID_LOCK: i64 = 4_109_301_175;
+
+impl NewWebsocketEvent {
+    pub fn new(
+        user_id: Option<&Uuid>,
+        topic: &str,
+        message_type: &str,
+        payload: serde_json::Value,
+    ) -> Self {
+        NewWebsocketEvent {
+            user_id: user_id.copied(),
+            topic: topic.to_string(),
+            message_type: message_type.to_string(),
+            payload,
+        }
+    }
+}
+
+impl WebsocketEvent {
+    /// Stores an event. Ids are handed out under a lock held until the
+    /// event is committed, so they become visible in the order of their ids
+    /// and a client that got an event can't miss one with a lower id by
+    /// asking for the ones after it.
+    pub fn create(
+        connection: &mut PgConnection,
+        event: NewWebsocketEvent,
+    ) -> Result<WebsocketEvent> {
+        let event = connection
+            .transaction(|conn| {
+                diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+                    .bind::<BigInt, _>(EVENT_ID_LOCK)
+                    .execute(conn)?;
+                diesel::insert_into(websocket_events_table)
+                    .values(event)
+                    .returning(WebsocketEvent::as_returning())
+                    .get_result(conn)
+            })
+            .map_err(|e| {
+                error!("Error creating websocket event: {}", e);
+                FailedToCreateWebsocketEvent(CreateWebsocketEventError(e))
+            })?;
+
+        Ok(event)
+    }
+
+    pub fn get(connection: &mut PgConnection, id: i64) -> Result<Option<WebsocketEvent>> {
+        let event = websocket_events_table
+            .find(id)
+            .select(WebsocketEvent::as_select())
+            .first(connection)
+            .optional()
+            .map_err(|e| {
+                error!("Error getting websocket event: {}", e);
+                FailedToGetWebsocketEvents(GetWebsocketEventError(e))
+            })?;
+
+        Ok(event)
+    }
+
+    /// Events after `last_event_id` and `since` that belong to the user, or
+    /// to nobody and are on one of `shared_topics`, oldest first.
+    pub fn get_missed(
+        connection: &mut PgConnection,
+        user_id: &Uuid,
+        shared_topics: &[String],
+        last_event_id: i64,
+        since: NaiveDateTime,
+        limit: i64,
+    ) -> Result<Vec<WebsocketEvent>> {
+        use crate::schema::websocket_events::dsl::{created_at, id, topic, user_id as user_id_col};
+
+        let events = websocket_events_table
+            .filter(id.gt(last_event_id))
+            .filter(created_at.gt(since))
+            .filter(
+                user_id_col
+                    .eq(user_id)
+                    .or(user_id_col.is_null().and(topic.eq_any(shared_topics))),
+            )
+            .order(id.asc())
+            .limit(limit)
+            .select(WebsocketEvent::as_select())
+            .load::<WebsocketEvent>(connection)
+            .map_err(|e| {
+                error!("Error getting websocket events: {}", e);
+                FailedToGetWebsocketEvents(GetWebsocketEventError(e))
+            })?;
+
+        Ok(events)
+    }
+
+    /// Deletes events created before `cutoff`, returning how many were.
+    pub fn delete_before(connection: &mut PgConnection, cutoff: NaiveDateTime) -> Result<usize> {
+        use crate::schema::websocket_events::dsl::created_at;
+
+        let deleted = diesel::delete(websocket_events_table.filter(created_at.lt(cutoff)))
+            .execute(connection)
+            .map_err(|e| {
+                error!("Error deleting websocket events: {}", e);
+                FailedToDeleteWebsocketEvents(DeleteWebsocketEventError(e))
+            })?;
+
+        Ok(deleted)
+    }
+}
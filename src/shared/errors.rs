@@ -10,6 +10,8 @@ pub enum RepositoryError {
     NotFound(String),
     #[error("{0}")]
     DatabaseError(String),
+    #[error("Git service error: {0}")]
+    GitServiceError(String),
     #[error("User with the same username, github username, or email already exists")]
     UserAlreadyExists,
     #[error("Failed to create user")]
@@ -34,16 +36,34 @@ pub enum RepositoryError {
     FailedToGetProgress(#[from] GetProgressError),
     #[error("Failed to update progress")]
     FailedToUpdateProgress(#[from] UpdateProgressError),
+    #[error("Failed to delete progress")]
+    FailedToDeleteProgress(#[from] DeleteProgressError),
+    #[error("Failed to archive progress")]
+    FailedToCreateProgressArchive(#[from] CreateProgressArchiveError),
     #[error("Repository already exists")]
     RepositoryAlreadyExists,
     #[error("Failed to create repository")]
     FailedToCreateRepository(#[from] CreateRepositoryError),
     #[error("Failed to get repository")]
     FailedToGetRepository(#[from] GetRepositoryError),
+    #[error("Failed to update repository")]
+    FailedToUpdateRepository(#[from] UpdateRepositoryError),
+    #[error("Failed to delete repository")]
+    FailedToDeleteRepository(#[from] DeleteRepositoryError),
+    #[error("Failed to create repository push")]
+    FailedToCreateRepositoryPush(#[from] CreateRepositoryPushError),
+    #[error("Failed to get repository push")]
+    FailedToGetRepositoryPush(#[from] GetRepositoryPushError),
+    #[error("Failed to delete repository push")]
+    FailedToDeleteRepositoryPush(#[from] DeleteRepositoryPushError),
     #[error("Failed to create submission")]
     FailedToCreateSubmission(#[from] CreateSubmissionError),
     #[error("Failed to get submission")]
     FailedToGetSubmission(#[from] GetSubmissionError),
+    #[error("Failed to update submission")]
+    FailedToUpdateSubmission(#[from] UpdateSubmissionError),
+    #[error("Failed to delete submission")]
+    FailedToDeleteSubmission(#[from] DeleteSubmissionError),
     #[error("Failed to create leaderboard")]
     FailedToCreateLeaderboard(#[from] CreateLeaderboardError),
     #[error("Failed to get leaderboard")]
@@ -62,6 +82,50 @@ pub enum RepositoryError {
     FailedToDeleteChallenge(#[from] DeleteChallengeError),
     #[error("Failed to update challenge")]
     FailedToUpdateChallenge(#[from] UpdateChallengeError),
+    #[error("{0}")]
+    IdempotencyConflict(String),
+    #[error("Failed to create idempotency key")]
+    FailedToCreateIdempotencyKey(#[from] CreateIdempotencyKeyError),
+    #[error("Failed to get idempotency key")]
+    FailedToGetIdempotencyKey(#[from] GetIdempotencyKeyError),
+    #[error("Failed to update idempotency key")]
+    FailedToUpdateIdempotencyKey(#[from] UpdateIdempotencyKeyError),
+    #[error("Failed to delete idempotency key")]
+    FailedToDeleteIdempotencyKey(#[from] DeleteIdempotencyKeyError),
+    #[error("Failed to create inbound event")]
+    FailedToCreateInboundEvent(#[from] CreateInboundEventError),
+    #[error("Failed to get inbound event")]
+    FailedToGetInboundEvent(#[from] GetInboundEventError),
+    #[error("Failed to update inbound event")]
+    FailedToUpdateInboundEvent(#[from] UpdateInboundEventError),
+    #[error("Failed to create submission test cases")]
+    FailedToCreateSubmissionTestCases(#[from] CreateSubmissionTestCaseError),
+    #[error("Failed to get submission test cases")]
+    FailedToGetSubmissionTestCases(#[from] GetSubmissionTestCaseError),
+    #[error("Failed to create test run")]
+    FailedToCreateTestRun(#[from] CreateTestRunError),
+    #[error("Failed to get test run")]
+    FailedToGetTestRun(#[from] GetTestRunError),
+    #[error("Failed to update test run")]
+    FailedToUpdateTestRun(#[from] UpdateTestRunError),
+    #[error("Failed to delete test run")]
+    FailedToDeleteTestRun(#[from] DeleteTestRunError),
+    #[error("Failed to create test run output chunk")]
+    FailedToCreateTestRunOutputChunk(#[from] CreateTestRunOutputChunkError),
+    #[error("Failed to get test run output chunks")]
+    FailedToGetTestRunOutputChunks(#[from] GetTestRunOutputChunkError),
+    #[error("Failed to update test runner")]
+    FailedToUpdateTestRunner(#[from] UpdateTestRunnerError),
+    #[error("Failed to get test runners")]
+    FailedToGetTestRunners(#[from] GetTestRunnerError),
+    #[error("Failed to create websocket event")]
+    FailedToCreateWebsocketEvent(#[from] CreateWebsocketEventError),
+    #[error("Failed to get websocket events")]
+    FailedToGetWebsocketEvents(#[from] GetWebsocketEventError),
+    #[error("Failed to delete websocket events")]
+    FailedToDeleteWebsocketEvents(#[from] DeleteWebsocketEventError),
+    #[error("Failed to update orphaned remotes")]
+    FailedToUpdateOrphanedRemotes(#[from] UpdateOrphanedRemoteError),
 }
 
 impl From<diesel::result::Error> for RepositoryError {
@@ -127,6 +191,10 @@ pub struct GetProgressError(#[from] pub diesel::result::Error);
 #[error("Database error while updating progress: {0}")]
 pub struct UpdateProgressError(#[from] pub diesel::result::Error);
 
+#[derive(Error, Debug)]
+#[error("Database error while deleting progress: {0}")]
+pub struct DeleteProgressError(#[from] pub diesel::result::Error);
+
 #[derive(Error, Debug)]
 #[error("Database error while creating repository: {0}")]
 pub struct CreateRepositoryError(#[from] pub diesel::result::Error);
@@ -135,6 +203,30 @@ pub struct CreateRepositoryError(#[from] pub diesel::result::Error);
 #[error("Database error while getting repository: {0}")]
 pub struct GetRepositoryError(#[from] pub diesel::result::Error);
 
+#[derive(Error, Debug)]
+#[error("Database error while updating repository: {0}")]
+pub struct UpdateRepositoryError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while deleting repository: {0}")]
+pub struct DeleteRepositoryError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating progress archive: {0}")]
+pub struct CreateProgressArchiveError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating repository push: {0}")]
+pub struct CreateRepositoryPushError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting repository push: {0}")]
+pub struct GetRepositoryPushError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while deleting repository push: {0}")]
+pub struct DeleteRepositoryPushError(#[from] pub diesel::result::Error);
+
 #[derive(Error, Debug)]
 #[error("Database error while creating submission: {0}")]
 pub struct CreateSubmissionError(#[from] pub diesel::result::Error);
@@ -143,6 +235,14 @@ pub struct CreateSubmissionError(#[from] pub diesel::result::Error);
 #[error("Database error while getting submission: {0}")]
 pub struct GetSubmissionError(#[from] pub diesel::result::Error);
 
+#[derive(Error, Debug)]
+#[error("Database error while updating submission: {0}")]
+pub struct UpdateSubmissionError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while deleting submission: {0}")]
+pub struct DeleteSubmissionError(#[from] pub diesel::result::Error);
+
 #[derive(Error, Debug)]
 #[error("Database error while creating leaderboard: {0}")]
 pub struct CreateLeaderboardError(#[from] pub diesel::result::Error);
@@ -178,3 +278,87 @@ pub struct DeleteChallengeError(#[from] pub diesel::result::Error);
 #[derive(Error, Debug)]
 #[error("Database error while updating challenge: {0}")]
 pub struct UpdateChallengeError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating idempotency key: {0}")]
+pub struct CreateIdempotencyKeyError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting idempotency key: {0}")]
+pub struct GetIdempotencyKeyError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while updating idempotency key: {0}")]
+pub struct UpdateIdempotencyKeyError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while deleting idempotency key: {0}")]
+pub struct DeleteIdempotencyKeyError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating inbound event: {0}")]
+pub struct CreateInboundEventError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting inbound event: {0}")]
+pub struct GetInboundEventError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while updating inbound event: {0}")]
+pub struct UpdateInboundEventError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating submission test cases: {0}")]
+pub struct CreateSubmissionTestCaseError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting submission test cases: {0}")]
+pub struct GetSubmissionTestCaseError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating test run: {0}")]
+pub struct CreateTestRunError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting test run: {0}")]
+pub struct GetTestRunError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while updating test run: {0}")]
+pub struct UpdateTestRunError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while deleting test run: {0}")]
+pub struct DeleteTestRunError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating test run output chunk: {0}")]
+pub struct CreateTestRunOutputChunkError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting test run output chunks: {0}")]
+pub struct GetTestRunOutputChunkError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while updating test runner: {0}")]
+pub struct UpdateTestRunnerError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting test runners: {0}")]
+pub struct GetTestRunnerError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while creating websocket event: {0}")]
+pub struct CreateWebsocketEventError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while getting websocket events: {0}")]
+pub struct GetWebsocketEventError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while deleting websocket events: {0}")]
+pub struct DeleteWebsocketEventError(#[from] pub diesel::result::Error);
+
+#[derive(Error, Debug)]
+#[error("Database error while updating orphaned remotes: {0}")]
+pub struct UpdateOrphanedRemoteError(#[from] pub diesel::result::Error);
@@ -97,6 +97,29 @@ impl Status {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ArchiveReason {
+    Reset,
+    Abandoned,
+}
+
+impl ArchiveReason {
+    pub fn from_str(reason: &str) -> Result<ArchiveReason, &'static str> {
+        match reason {
+            "reset" => Ok(ArchiveReason::Reset),
+            "abandoned" => Ok(ArchiveReason::Abandoned),
+            _ => Err("Invalid archive reason"),
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            ArchiveReason::Reset => "reset",
+            ArchiveReason::Abandoned => "abandoned",
+        }
+    }
+}
+
 pub enum SubmissionStatus {
     Pending,
     Failed,
@@ -122,6 +145,134 @@ impl SubmissionStatus {
     }
 }
 
+/// Outcome of a single test case reported by the test runner.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestCaseStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+impl TestCaseStatus {
+    pub fn from_str(status: &str) -> Result<TestCaseStatus, &'static str> {
+        match status {
+            "passed" => Ok(TestCaseStatus::Passed),
+            "failed" => Ok(TestCaseStatus::Failed),
+            "skipped" => Ok(TestCaseStatus::Skipped),
+            _ => Err("Invalid test case status"),
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            TestCaseStatus::Passed => "passed",
+            TestCaseStatus::Failed => "failed",
+            TestCaseStatus::Skipped => "skipped",
+        }
+    }
+}
+
+/// Where a test run is between being queued and its result.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TestRunStatus {
+    Queued,
+    Running,
+    Finished,
+    TimedOut,
+}
+
+impl TestRunStatus {
+    pub fn from_str(status: &str) -> Result<TestRunStatus, &'static str> {
+        match status {
+            "queued" => Ok(TestRunStatus::Queued),
+            "running" => Ok(TestRunStatus::Running),
+            "finished" => Ok(TestRunStatus::Finished),
+            "timed_out" => Ok(TestRunStatus::TimedOut),
+            _ => Err("Invalid test run status"),
+        }
+    }
+
+    pub fn to_str(self) -> &'static str {
+        match self {
+            TestRunStatus::Queued => "queued",
+            TestRunStatus::Running => "running",
+            TestRunStatus::Finished => "finished",
+            TestRunStatus::TimedOut => "timed_out",
+        }
+    }
+
+    /// Statuses a run can move to this one from. A result that arrives after
+    /// the run timed out still finishes it.
+    pub fn reachable_from(self) -> &'static [TestRunStatus] {
+        match self {
+            TestRunStatus::Queued => &[],
+            TestRunStatus::Running => &[TestRunStatus::Queued],
+            TestRunStatus::Finished => &[
+                TestRunStatus::Queued,
+                TestRunStatus::Running,
+                TestRunStatus::TimedOut,
+            ],
+            TestRunStatus::TimedOut => &[TestRunStatus::Queued, TestRunStatus::Running],
+        }
+    }
+}
+
+/// What an entry on the attempt timeline records.
+pub enum RepositoryEventKind {
+    Push,
+    BranchCreated,
+    TagCreated,
+}
+
+impl RepositoryEventKind {
+    pub fn from_str(kind: &str) -> Result<RepositoryEventKind, &'static str> {
+        match kind {
+            "push" => Ok(RepositoryEventKind::Push),
+            "branch_created" => Ok(RepositoryEventKind::BranchCreated),
+            "tag_created" => Ok(RepositoryEventKind::TagCreated),
+            _ => Err("Invalid repository event kind"),
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            RepositoryEventKind::Push => "push",
+            RepositoryEventKind::BranchCreated => "branch_created",
+            RepositoryEventKind::TagCreated => "tag_created",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum InboundEventStatus {
+    Received,
+    Processed,
+    Failed,
+    Replaying,
+}
+
+impl InboundEventStatus {
+    pub fn from_str(status: &str) -> Result<InboundEventStatus, &'static str> {
+        match status {
+            "received" => Ok(InboundEventStatus::Received),
+            "processed" => Ok(InboundEventStatus::Processed),
+            "failed" => Ok(InboundEventStatus::Failed),
+            "replaying" => Ok(InboundEventStatus::Replaying),
+            _ => Err("Invalid inbound event status"),
+        }
+    }
+
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            InboundEventStatus::Received => "received",
+            InboundEventStatus::Processed => "processed",
+            InboundEventStatus::Failed => "failed",
+            InboundEventStatus::Replaying => "replaying",
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PaginationParams {
     pub page: Option<i64>,
@@ -144,3 +295,9 @@ pub enum Period {
     ThisMonth,
     AllTime,
 }
+
+#[derive(Debug, Deserialize)]
+pub enum AttemptSort {
+    RecentActivity,
+    PushCount,
+}
@@ -1,4 +1,3 @@
-use actix_ws::{Item, Message};
 use rand::{distributions::Alphanumeric, Rng};
 use std::str::FromStr;
 use uuid::Uuid;
@@ -17,23 +16,22 @@ pub fn generate_session_token() -> String {
     format!("hxckr_{}", random_string)
 }
 
-pub fn clone_websocket_message(msg: &Message) -> Message {
-    match msg {
-        Message::Text(text) => Message::Text(text.clone()),
-        Message::Binary(bin) => Message::Binary(bin.clone()),
-        Message::Ping(bytes) => Message::Ping(bytes.clone()),
-        Message::Pong(bytes) => Message::Pong(bytes.clone()),
-        Message::Close(reason) => Message::Close(reason.clone()),
-        Message::Continuation(item) => Message::Continuation(clone_websocket_item(item)),
-        Message::Nop => Message::Nop,
-    }
+/// Removes the `user:token@` part from a clone URL, e.g.
+/// `http://token@host/repo` becomes `http://host/repo`.
+/// Returns `None` if the URL has no scheme or no credentials.
+pub fn strip_url_credentials(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once("://")?;
+    rest.split_once('@')
+        .map(|(_, path)| format!("{}://{}", scheme, path))
 }
 
-pub fn clone_websocket_item(item: &Item) -> Item {
-    match item {
-        Item::FirstText(bytes) => Item::FirstText(bytes.clone()),
-        Item::FirstBinary(bytes) => Item::FirstBinary(bytes.clone()),
-        Item::Continue(bytes) => Item::Continue(bytes.clone()),
-        Item::Last(bytes) => Item::Last(bytes.clone()),
+/// Returns the last path segment of a repository URL without a `.git`
+/// suffix, e.g. `http://host/alice__btc-tx.git` gives `alice__btc-tx`.
+pub fn repo_name_from_url(url: &str) -> Option<String> {
+    let name = url.trim_end_matches('/').rsplit('/').next()?;
+    let name = name.strip_suffix(".git").unwrap_or(name);
+    if name.is_empty() || name.contains("://") {
+        return None;
     }
+    Some(name.to_string())
 }